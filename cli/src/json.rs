@@ -0,0 +1,76 @@
+//! The documents which commands print when `--json` is given.
+
+use firmware_manager::{HistoryEntry, ReleaseInfo};
+use serde::Serialize;
+use std::io::{self, Write};
+
+/// The outcome of updating a device.
+#[derive(Serialize)]
+pub struct UpdateJson<'a> {
+    pub device:   &'a str,
+    pub id:       &'a str,
+    pub previous: Option<&'a str>,
+    pub version:  Option<&'a str>,
+    pub outcome:  &'a str,
+    pub error:    Option<&'a str>,
+}
+
+/// An update which was recorded in the history.
+#[derive(Serialize)]
+pub struct HistoryJson<'a> {
+    pub timestamp: u64,
+    pub device:    &'a str,
+    pub id:        &'a str,
+    pub previous:  &'a str,
+    pub version:   &'a str,
+    pub checksum:  Option<String>,
+    pub outcome:   String,
+}
+
+impl<'a> From<&'a HistoryEntry> for HistoryJson<'a> {
+    fn from(entry: &'a HistoryEntry) -> Self {
+        Self {
+            timestamp: entry.timestamp,
+            device:    &entry.device,
+            id:        &entry.device_id,
+            previous:  &entry.previous,
+            version:   &entry.version,
+            checksum:  entry.checksum.as_ref().map(ToString::to_string),
+            outcome:   entry.outcome.to_string(),
+        }
+    }
+}
+
+/// The releases that updating a device would install, from newest to oldest.
+#[derive(Serialize)]
+pub struct ChangelogJson<'a> {
+    pub device:   &'a str,
+    pub id:       &'a str,
+    pub current:  Option<&'a str>,
+    pub releases: Vec<ReleaseJson<'a>>,
+}
+
+/// A release of firmware, and what it changes.
+#[derive(Serialize)]
+pub struct ReleaseJson<'a> {
+    pub version:   &'a str,
+    pub date:      Option<String>,
+    pub changelog: String,
+}
+
+impl<'a> From<&'a ReleaseInfo> for ReleaseJson<'a> {
+    fn from(release: &'a ReleaseInfo) -> Self {
+        Self {
+            version:   release.version.as_str(),
+            date:      release.date.map(|date| date.to_string()),
+            changelog: release.changelog.to_string(),
+        }
+    }
+}
+
+/// Prints a document to stdout as indented JSON.
+pub fn print<T: Serialize>(document: &T) -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+    serde_json::to_writer_pretty(&mut stdout, document)?;
+    writeln!(stdout)
+}
@@ -0,0 +1,663 @@
+mod json;
+mod localize;
+mod session;
+
+use self::{
+    json::{ChangelogJson, HistoryJson, ReleaseJson, UpdateJson},
+    session::{Device, Inventory, Kind, Session},
+};
+use clap::{Arg, ArgAction, ArgGroup, ArgMatches, Command};
+use firmware_manager::{
+    authorize, format_error, fwupd_refresh, fwupd_update_events, get_client, history, Action,
+    Config, Entities, Entity, FirmwareEvent, FirmwareSignal, FwupdClient, FwupdError,
+    HistoryEntry, LockOperation, OperationLock, ReleaseDate, ReportFormat, Verification, Version,
+};
+use i18n_embed::DesktopLanguageRequester;
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, IsTerminal, Write},
+    path::PathBuf,
+    process::exit,
+};
+
+// Exit codes, which scripts may rely upon. Usage errors exit with 2, as clap does.
+const SUCCESS: i32 = 0;
+const FAILED: i32 = 1;
+const USAGE: i32 = 2;
+const UPDATES_AVAILABLE: i32 = 3;
+const NOT_AUTHORIZED: i32 = 4;
+const NOT_FOUND: i32 = 5;
+const NOT_CONFIRMED: i32 = 6;
+const REBOOT_REQUIRED: i32 = 7;
+
+/// The result of updating a device.
+enum UpdateOutcome {
+    Updated,
+    /// The update will be applied on the next reboot.
+    Scheduled,
+    Failed(String),
+}
+
+fn main() {
+    translate();
+
+    let matches = command().get_matches();
+
+    let code = match matches.subcommand() {
+        Some(("list", args)) => list(args),
+        Some(("refresh", _)) => refresh(),
+        Some(("update", args)) => update(args),
+        Some(("history", args)) => show_history(args),
+        Some(("changelog", args)) => changelog(args),
+        Some(("report", args)) => report(args),
+        _ => unreachable!("clap requires a subcommand"),
+    };
+
+    exit(code);
+}
+
+fn command() -> Command {
+    let json = || Arg::new("json").long("json").action(ArgAction::SetTrue).help("print JSON");
+
+    let device = || {
+        Arg::new("device").help("the ID or name of a device, as shown by `firmware-manager list`")
+    };
+
+    Command::new("firmware-manager")
+        .about("Manages system and device firmware")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .after_help(
+            "Exit codes: 0 success, 1 failure, 2 usage error, 3 updates available, \
+             4 not authorized, 5 device not found, 6 not confirmed, 7 reboot required",
+        )
+        .subcommand(
+            Command::new("list")
+                .about("lists the firmware of every device, and the updates available")
+                .arg(json())
+                .arg(
+                    Arg::new("check")
+                        .long("check")
+                        .action(ArgAction::SetTrue)
+                        .help("exit with 3 if updates are available"),
+                ),
+        )
+        .subcommand(
+            Command::new("refresh").about("refreshes the metadata of the fwupd remotes now"),
+        )
+        .subcommand(
+            Command::new("update")
+                .about("updates the firmware of the given devices, or of every device")
+                .arg(device().action(ArgAction::Append))
+                .arg(
+                    Arg::new("all")
+                        .long("all")
+                        .action(ArgAction::SetTrue)
+                        .help("update every device which has an update available"),
+                )
+                .group(ArgGroup::new("devices").args(["device", "all"]).required(true))
+                .arg(
+                    Arg::new("yes")
+                        .short('y')
+                        .long("yes")
+                        .action(ArgAction::SetTrue)
+                        .help("update without asking for confirmation"),
+                )
+                .arg(json()),
+        )
+        .subcommand(
+            Command::new("history")
+                .about("shows the updates which were installed, and their outcomes")
+                .arg(device())
+                .arg(json()),
+        )
+        .subcommand(
+            Command::new("changelog")
+                .about("shows the changelog of the releases newer than the installed firmware")
+                .arg(device().required(true))
+                .arg(json()),
+        )
+        .subcommand(
+            Command::new("report")
+                .about("writes an inventory of the firmware on this system")
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .value_parser(["json", "csv"])
+                        .help("the format of the report; determined by the output by default"),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .value_parser(clap::value_parser!(PathBuf))
+                        .help("the file to write the report to, or - for stdout"),
+                ),
+        )
+}
+
+fn list(args: &ArgMatches) -> i32 {
+    let inventory = Session::start().scan(&mut Entities::default());
+
+    if args.get_flag("json") {
+        print!("{}", inventory.report.to_json());
+    } else {
+        let rows = inventory
+            .devices
+            .iter()
+            .map(|device| {
+                let status = if device.can_update() {
+                    fl!("status-update-available")
+                } else if device.info.is_upgradeable() {
+                    fl!("status-blocked")
+                } else {
+                    fl!("status-up-to-date")
+                };
+
+                [
+                    device.info.name.to_string(),
+                    device.id().to_owned(),
+                    device.info.current_label().to_owned(),
+                    device.info.latest.as_ref().map_or_else(String::new, Version::to_string),
+                    status,
+                ]
+            })
+            .collect::<Vec<_>>();
+
+        let header = [
+            fl!("column-device"),
+            fl!("column-id"),
+            fl!("column-current"),
+            fl!("column-latest"),
+            fl!("column-status"),
+        ];
+
+        print_table(header, &rows);
+
+        for metadata in &inventory.report.metadata {
+            if let Some(days) = metadata.days() {
+                let source = metadata.source.to_string();
+                println!("{}", fl!("metadata-age", source = source, days = days));
+            }
+        }
+    }
+
+    let upgradeable = inventory.devices.iter().any(|device| device.info.is_upgradeable());
+    if args.get_flag("check") && upgradeable {
+        UPDATES_AVAILABLE
+    } else {
+        SUCCESS
+    }
+}
+
+fn refresh() -> i32 {
+    if Config::load().offline {
+        eprintln!("{}", fl!("error-offline"));
+        return FAILED;
+    }
+
+    if !authorize(Action::ModifyRemotes).is_authorized() {
+        eprintln!("{}", fl!("error-not-authorized"));
+        return NOT_AUTHORIZED;
+    }
+
+    let fwupd = get_client::<_, _, FwupdError>(
+        "fwupd",
+        || true,
+        || {
+            let client = FwupdClient::new()?;
+            client.ping()?;
+            Ok(client)
+        },
+    );
+
+    let Some(client) = fwupd else {
+        eprintln!("{}", fl!("error-fwupd-unavailable"));
+        return FAILED;
+    };
+
+    // Remotes are not refreshed while another process updates firmware.
+    let _lock = match OperationLock::acquire(LockOperation::Refresh, None) {
+        Ok(lock) => lock,
+        Err(why) => {
+            eprintln!("{}: {}", fl!("error-refresh"), format_error(&why));
+            return FAILED;
+        }
+    };
+
+    match fwupd_refresh(&client) {
+        Ok(()) => {
+            println!("{}", fl!("refreshed"));
+            SUCCESS
+        }
+        Err(why) => {
+            eprintln!("{}: {}", fl!("error-refresh"), format_error(&why));
+            FAILED
+        }
+    }
+}
+
+fn update(args: &ArgMatches) -> i32 {
+    let session = Session::start();
+    let inventory = session.scan(&mut Entities::default());
+
+    let mut selected: Vec<&Device> = Vec::new();
+
+    if args.get_flag("all") {
+        selected.extend(inventory.devices.iter().filter(|device| device.info.is_upgradeable()));
+    } else {
+        for selector in args.get_many::<String>("device").into_iter().flatten() {
+            match inventory.select(selector).as_slice() {
+                [] => {
+                    eprintln!("{}", fl!("error-not-found", device = selector.as_str()));
+                    return NOT_FOUND;
+                }
+                [device] => {
+                    if !selected.iter().any(|other| other.entity == device.entity) {
+                        selected.push(device);
+                    }
+                }
+                _ => {
+                    eprintln!("{}", fl!("error-ambiguous", device = selector.as_str()));
+                    return USAGE;
+                }
+            }
+        }
+    }
+
+    let mut blocked = false;
+    let mut updates = Vec::new();
+
+    for device in selected {
+        if device.can_update() {
+            updates.push(device);
+        } else if device.info.is_upgradeable() {
+            eprintln!("{}", fl!("error-blocked", device = device.info.name.as_ref()));
+            blocked = true;
+        } else {
+            eprintln!("{}", fl!("up-to-date", device = device.info.name.as_ref()));
+        }
+    }
+
+    // Thelio I/O boards are flashed together, so every board with an update is updated.
+    if updates.iter().any(|device| matches!(device.kind, Kind::ThelioIo { .. })) {
+        for device in &inventory.devices {
+            let is_board = matches!(device.kind, Kind::ThelioIo { .. });
+            if is_board && device.can_update() && !updates.iter().any(|d| d.entity == device.entity)
+            {
+                updates.push(device);
+            }
+        }
+    }
+
+    if updates.is_empty() {
+        if !blocked {
+            println!("{}", fl!("no-updates"));
+        }
+
+        return if blocked { FAILED } else { SUCCESS };
+    }
+
+    if !args.get_flag("yes") && !confirm(&updates) {
+        return NOT_CONFIRMED;
+    }
+
+    if !authorize(Action::UpdateFirmware).is_authorized() {
+        eprintln!("{}", fl!("error-not-authorized"));
+        return NOT_AUTHORIZED;
+    }
+
+    let outcomes = install(&session, &inventory, &updates);
+
+    if args.get_flag("json") {
+        let document = updates
+            .iter()
+            .map(|device| {
+                let (outcome, error) = match &outcomes[&device.entity] {
+                    UpdateOutcome::Updated => ("updated", None),
+                    UpdateOutcome::Scheduled => ("scheduled", None),
+                    UpdateOutcome::Failed(why) => ("failed", Some(why.as_str())),
+                };
+
+                UpdateJson {
+                    device:   &device.info.name,
+                    id:       device.id(),
+                    previous: device.info.current.as_ref().map(Version::as_str),
+                    version:  device.info.latest.as_ref().map(Version::as_str),
+                    outcome,
+                    error,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        // The updates were installed regardless, so their outcome decides the exit code.
+        if let Err(why) = json::print(&document) {
+            eprintln!("{}: {}", fl!("error-json"), why);
+        }
+    } else {
+        for device in &updates {
+            let name = device.info.name.as_ref();
+            let version = device.info.latest.as_ref().map_or("", Version::as_str);
+            let message = match &outcomes[&device.entity] {
+                UpdateOutcome::Updated => fl!("outcome-updated", device = name, version = version),
+                UpdateOutcome::Scheduled => {
+                    fl!("outcome-scheduled", device = name, version = version)
+                }
+                UpdateOutcome::Failed(why) => {
+                    fl!("outcome-failed", device = name, reason = why.as_str())
+                }
+            };
+
+            println!("{}", message);
+        }
+    }
+
+    let failed = outcomes.values().any(|outcome| matches!(outcome, UpdateOutcome::Failed(_)));
+    let scheduled = outcomes.values().any(|outcome| matches!(outcome, UpdateOutcome::Scheduled));
+
+    if failed || blocked {
+        FAILED
+    } else if scheduled {
+        REBOOT_REQUIRED
+    } else {
+        SUCCESS
+    }
+}
+
+/// Lists the updates, and asks whether they should be installed.
+///
+/// Confirmation cannot be given without a terminal, in which case `--yes` is required.
+fn confirm(updates: &[&Device]) -> bool {
+    for device in updates {
+        let latest = device.info.latest.as_ref().map_or("", Version::as_str);
+        eprintln!("  {}: {} → {}", device.info.name, device.info.current_label(), latest);
+    }
+
+    let stdin = io::stdin();
+    if !stdin.is_terminal() {
+        eprintln!("{}", fl!("error-confirm-noninteractive"));
+        return false;
+    }
+
+    eprint!("{} [y/N] ", fl!("confirm-update", count = updates.len()));
+    let _res = io::stderr().flush();
+
+    let mut answer = String::new();
+    if stdin.lock().read_line(&mut answer).is_err() {
+        return false;
+    }
+
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+/// Sends the updates to the event loop, and reports their progress until each has an outcome.
+///
+/// fwupd devices are updated first, in the order that composite devices require, and system
+/// firmware is scheduled last.
+fn install(
+    session: &Session,
+    inventory: &Inventory,
+    updates: &[&Device],
+) -> HashMap<Entity, UpdateOutcome> {
+    let mut fwupd = Vec::new();
+    let mut boards = Vec::new();
+    let mut thelio = None;
+    let mut system = None;
+
+    for device in updates {
+        match &device.kind {
+            Kind::Fwupd { device: fwupd_device, release: Some(release), .. } => {
+                fwupd.push((device.entity, fwupd_device.clone(), release.clone()));
+            }
+            Kind::System76(Some(digest)) => system = Some((device.entity, digest.clone())),
+            Kind::ThelioIo { board, digest: Some(digest) } => {
+                boards.push((device.entity, board.clone()));
+                if let (None, Some(latest)) = (&thelio, &device.info.latest) {
+                    thelio = Some((digest.clone(), latest.clone()));
+                }
+            }
+            _ => (),
+        }
+    }
+
+    // Only the devices of a composite device depend upon each other.
+    for event in fwupd_update_events(fwupd) {
+        session.send(event);
+    }
+
+    if let Some((digest, latest)) = thelio {
+        session.send(FirmwareEvent::ThelioIo(digest, latest, boards));
+    }
+
+    let system_entity = system.map(|(entity, digest)| {
+        session.send(FirmwareEvent::S76System(entity, digest));
+        entity
+    });
+
+    let name = |entity: Entity| inventory.get(entity).map_or("", |device| &device.info.name);
+    let mut outcomes = HashMap::new();
+
+    while outcomes.len() < updates.len() {
+        let Some(signal) = session.recv() else {
+            break;
+        };
+
+        match signal {
+            FirmwareSignal::DownloadBegin(entity, _) => {
+                eprintln!("{}", fl!("progress-downloading", device = name(entity)));
+            }
+            FirmwareSignal::Verifying(entity) => {
+                eprintln!("{}", fl!("progress-verifying", device = name(entity)));
+            }
+            FirmwareSignal::Verified(entity, Verification::Unverified(checksum)) => {
+                let checksum = checksum.to_string();
+                let warning = fl!("warning-unverified", device = name(entity), checksum = checksum);
+                eprintln!("{}", warning);
+            }
+            FirmwareSignal::DeviceFlashing(entity) => {
+                eprintln!("{}", fl!("progress-flashing", device = name(entity)));
+            }
+            FirmwareSignal::DeviceRequest(request) => {
+                let device = match request.entity {
+                    Some(entity) => name(entity),
+                    None => &request.device_id,
+                };
+                eprintln!("{}: {}", device, request.message);
+                if let Some(image) = request.image {
+                    eprintln!("  {}", image.display());
+                }
+            }
+            FirmwareSignal::DeviceUpdated(entity) => {
+                let scheduled = inventory.get(entity).map_or(false, Device::needs_reboot);
+                let outcome =
+                    if scheduled { UpdateOutcome::Scheduled } else { UpdateOutcome::Updated };
+                outcomes.insert(entity, outcome);
+            }
+            FirmwareSignal::SystemScheduled => {
+                if let Some(entity) = system_entity {
+                    outcomes.insert(entity, UpdateOutcome::Scheduled);
+                }
+            }
+            FirmwareSignal::Error(Some(entity), why) => {
+                outcomes.insert(entity, UpdateOutcome::Failed(format_error(&why)));
+            }
+            FirmwareSignal::Error(None, why) => eprintln!("{}", format_error(&why)),
+            _ => (),
+        }
+    }
+
+    // Should the event loop stop early, the remaining updates have no outcome.
+    for device in updates {
+        outcomes
+            .entry(device.entity)
+            .or_insert_with(|| UpdateOutcome::Failed(fl!("error-interrupted")));
+    }
+
+    outcomes
+}
+
+fn show_history(args: &ArgMatches) -> i32 {
+    let entries = match history() {
+        Ok(entries) => entries,
+        Err(why) => {
+            eprintln!("{}: {}", fl!("error-history"), format_error(&why));
+            return FAILED;
+        }
+    };
+
+    let entries = entries
+        .iter()
+        .filter(|entry| {
+            args.get_one::<String>("device").map_or(true, |selector| {
+                *entry.device_id == **selector || entry.device.eq_ignore_ascii_case(selector)
+            })
+        })
+        .collect::<Vec<&HistoryEntry>>();
+
+    if args.get_flag("json") {
+        let document = entries.iter().copied().map(HistoryJson::from).collect::<Vec<_>>();
+        if let Err(why) = json::print(&document) {
+            eprintln!("{}: {}", fl!("error-json"), why);
+            return FAILED;
+        }
+    } else if entries.is_empty() {
+        println!("{}", fl!("history-empty"));
+    } else {
+        let rows = entries
+            .iter()
+            .map(|entry| {
+                [
+                    ReleaseDate::from_timestamp(entry.timestamp)
+                        .map_or_else(String::new, |date| date.to_string()),
+                    entry.device.to_string(),
+                    entry.previous.to_string(),
+                    entry.version.to_string(),
+                    entry.outcome.to_string(),
+                ]
+            })
+            .collect::<Vec<_>>();
+
+        let header = [
+            fl!("column-date"),
+            fl!("column-device"),
+            fl!("column-previous"),
+            fl!("column-version"),
+            fl!("column-outcome"),
+        ];
+
+        print_table(header, &rows);
+    }
+
+    SUCCESS
+}
+
+fn changelog(args: &ArgMatches) -> i32 {
+    let selector = args.get_one::<String>("device").expect("device is required");
+    let inventory = Session::start().scan(&mut Entities::default());
+
+    let device = match inventory.select(selector).as_slice() {
+        [] => {
+            eprintln!("{}", fl!("error-not-found", device = selector.as_str()));
+            return NOT_FOUND;
+        }
+        [device] => *device,
+        _ => {
+            eprintln!("{}", fl!("error-ambiguous", device = selector.as_str()));
+            return USAGE;
+        }
+    };
+
+    // The newest release is shown first.
+    let mut releases = device.info.pending_releases().collect::<Vec<_>>();
+    releases.reverse();
+
+    if args.get_flag("json") {
+        let document = ChangelogJson {
+            device:   &device.info.name,
+            id:       device.id(),
+            current:  device.info.current.as_ref().map(Version::as_str),
+            releases: releases.iter().copied().map(ReleaseJson::from).collect(),
+        };
+
+        if let Err(why) = json::print(&document) {
+            eprintln!("{}: {}", fl!("error-json"), why);
+            return FAILED;
+        }
+    } else if releases.is_empty() {
+        println!("{}", fl!("up-to-date", device = device.info.name.as_ref()));
+    } else {
+        for (id, release) in releases.iter().enumerate() {
+            if id != 0 {
+                println!();
+            }
+
+            match release.date {
+                Some(date) => println!("{} ({})", release.version, date),
+                None => println!("{}", release.version),
+            }
+
+            for line in release.changelog.to_string().lines() {
+                println!("    {}", line);
+            }
+        }
+    }
+
+    SUCCESS
+}
+
+fn report(args: &ArgMatches) -> i32 {
+    let path = args.get_one::<PathBuf>("output").filter(|path| path.as_os_str() != "-");
+
+    let format = match args.get_one::<String>("format").map(String::as_str) {
+        Some("csv") => ReportFormat::Csv,
+        Some(_) => ReportFormat::Json,
+        None => path.and_then(|path| ReportFormat::from_path(path)).unwrap_or(ReportFormat::Json),
+    };
+
+    let report = Session::start().scan(&mut Entities::default()).report;
+
+    let result = match path {
+        Some(path) => report.write(path, format),
+        None => io::stdout().write_all(report.render(format).as_bytes()),
+    };
+
+    if let Err(why) = result {
+        eprintln!("{}: {}", fl!("error-report-write"), why);
+        return FAILED;
+    }
+
+    SUCCESS
+}
+
+/// Prints rows beneath a header, with each column padded to the width of its widest cell.
+fn print_table<const N: usize>(header: [String; N], rows: &[[String; N]]) {
+    let mut widths = [0; N];
+    for row in std::iter::once(&header).chain(rows) {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    for row in std::iter::once(&header).chain(rows) {
+        let line = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ");
+
+        println!("{}", line.trim_end());
+    }
+}
+
+fn translate() {
+    let localizer = crate::localize::localizer();
+    let requested_languages = DesktopLanguageRequester::requested_languages();
+
+    if let Err(error) = localizer.select(&requested_languages) {
+        eprintln!("Error while loading languages for firmware-manager-cli {}", error);
+    }
+
+    // Unicode isolation marks would be printed as-is to the terminal.
+    crate::localize::LANGUAGE_LOADER.set_use_isolating(false);
+}
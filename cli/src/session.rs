@@ -0,0 +1,161 @@
+//! Drives the event loop of the core from the command line.
+
+use firmware_manager::*;
+use std::{
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+};
+
+/// The identifier which selects System76 system firmware, as it has no device ID.
+pub const SYSTEM_ID: &str = "system76";
+
+/// The service-specific details of a device, which are needed to update it.
+pub enum Kind {
+    Fwupd {
+        device: Arc<FwupdDevice>,
+        release: Option<Arc<FwupdRelease>>,
+        /// The device is locked, or has a problem which prevents it from being updated.
+        blocked: bool,
+    },
+    System76(Option<System76Digest>),
+    ThelioIo {
+        board: Box<str>,
+        digest: Option<System76Digest>,
+    },
+}
+
+/// A device which was discovered by a scan.
+pub struct Device {
+    pub entity: Entity,
+    pub info: FirmwareInfo,
+    pub kind: Kind,
+}
+
+impl Device {
+    /// The identifier that the device may be selected by.
+    pub fn id(&self) -> &str {
+        match &self.kind {
+            Kind::Fwupd { device, .. } => device.device_id.as_ref(),
+            Kind::System76(_) => SYSTEM_ID,
+            Kind::ThelioIo { board, .. } => board,
+        }
+    }
+
+    /// Whether the selector names this device, by its identifier or by its name.
+    pub fn matches(&self, selector: &str) -> bool {
+        self.id() == selector || self.info.name.eq_ignore_ascii_case(selector)
+    }
+
+    /// Whether the latest firmware is newer, and everything needed to install it is available.
+    pub fn can_update(&self) -> bool {
+        self.info.is_upgradeable()
+            && match &self.kind {
+                Kind::Fwupd { release, blocked, .. } => release.is_some() && !blocked,
+                Kind::System76(digest) | Kind::ThelioIo { digest, .. } => digest.is_some(),
+            }
+    }
+
+    /// Whether the update will only be applied once the system is rebooted.
+    pub fn needs_reboot(&self) -> bool {
+        match &self.kind {
+            Kind::Fwupd { device, .. } => device.needs_reboot(),
+            Kind::System76(_) => true,
+            Kind::ThelioIo { .. } => false,
+        }
+    }
+}
+
+/// The devices and report assembled from a scan.
+#[derive(Default)]
+pub struct Inventory {
+    pub devices: Vec<Device>,
+    pub report: Report,
+}
+
+impl Inventory {
+    /// Finds the devices named by a selector.
+    pub fn select(&self, selector: &str) -> Vec<&Device> {
+        self.devices.iter().filter(|device| device.matches(selector)).collect()
+    }
+
+    /// The device which was assigned the entity.
+    pub fn get(&self, entity: Entity) -> Option<&Device> {
+        self.devices.iter().find(|device| device.entity == entity)
+    }
+}
+
+/// The event loop of the core, running in a background thread.
+pub struct Session {
+    sender: Sender<FirmwareEvent>,
+    receiver: Receiver<FirmwareSignal>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Session {
+    pub fn start() -> Self {
+        let (sender, events) = mpsc::channel();
+        let (signals, receiver) = mpsc::channel();
+        let handle = thread::spawn(move || event_loop(events, signals));
+        Self { sender, receiver, handle: Some(handle) }
+    }
+
+    /// Sends an event to the event loop.
+    pub fn send(&self, event: FirmwareEvent) { let _res = self.sender.send(event); }
+
+    /// Waits for the next signal, or `None` if the event loop has stopped.
+    pub fn recv(&self) -> Option<FirmwareSignal> { self.receiver.recv().ok() }
+
+    /// Scans for devices, assigning an entity to each device that is found.
+    ///
+    /// Errors which occur during the scan are printed, as they do not prevent other devices from
+    /// being found.
+    pub fn scan(&self, entities: &mut Entities) -> Inventory {
+        let mut inventory = Inventory { report: Report::new(), ..Inventory::default() };
+
+        self.send(FirmwareEvent::Scan);
+
+        while let Some(signal) = self.recv() {
+            inventory.report.add(&signal);
+
+            let (info, kind) = match signal {
+                FirmwareSignal::Fwupd(signal) => {
+                    let blocked = signal.is_locked() || signal.blocking_problems().next().is_some();
+                    let FwupdSignal { info, device, releases, .. } = signal;
+                    let release = releases.last().cloned().map(Arc::new);
+                    (info, Kind::Fwupd { device: Arc::new(device), release, blocked })
+                }
+                FirmwareSignal::S76System(info, digest) => (info, Kind::System76(digest)),
+                FirmwareSignal::ThelioIo(ThelioIoSignal { board, info, digest }) => {
+                    (info, Kind::ThelioIo { board, digest })
+                }
+                FirmwareSignal::Error(_, why) => {
+                    eprintln!("{}", format_error(&why));
+                    continue;
+                }
+                FirmwareSignal::ScanningComplete => break,
+                _ => continue,
+            };
+
+            let entity = entities.create();
+            if let Kind::System76(_) = kind {
+                entities.associate_system(entity);
+            }
+
+            inventory.devices.push(Device { entity, info, kind });
+        }
+
+        inventory
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        self.send(FirmwareEvent::Stop);
+        if let Some(handle) = self.handle.take() {
+            let _res = handle.join();
+        }
+    }
+}
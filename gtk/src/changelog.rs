@@ -1,4 +1,5 @@
 use crate::fl;
+use firmware_manager::{Block, Changelog, Inline, ReleaseInfo, ReleaseRange, Urgency};
 use gtk::prelude::*;
 
 pub fn generate_widget_none() -> gtk::Box {
@@ -14,10 +15,42 @@ pub fn generate_widget_none() -> gtk::Box {
         .build()
 }
 
-pub fn generate_widget<I, S>(changelog: I) -> gtk::Box
+/// Generates a changelog for the releases that an update will install, newest first.
+///
+/// Releases which have already been installed are collapsed beneath them. If there is no update,
+/// the installed releases are displayed instead.
+pub fn generate_range_widget(range: ReleaseRange) -> gtk::Box {
+    if range.pending.is_empty() {
+        return if range.installed.is_empty() {
+            generate_widget_none()
+        } else {
+            generate_widget(range.installed.iter().rev())
+        };
+    }
+
+    let whats_new = format!("<b>{}</b>", fl!("changelog-whats-new"));
+
+    let container = cascade! {
+        gtk::Box::new(gtk::Orientation::Vertical, 12);
+        ..add(&gtk::Label::builder().label(&whats_new).use_markup(true).xalign(0.0).build());
+        ..add(&generate_widget(range.pending.iter().rev()));
+    };
+
+    if !range.installed.is_empty() {
+        container.add(&cascade! {
+            gtk::Expander::new(Some(&fl!("changelog-earlier")));
+            ..set_expanded(false);
+            ..add(&generate_widget(range.installed.iter().rev()));
+        });
+    }
+
+    container.show_all();
+    container
+}
+
+pub fn generate_widget<'a, I>(changelog: I) -> gtk::Box
 where
-    S: AsRef<str>,
-    I: Iterator<Item = (S, S, S)>,
+    I: Iterator<Item = &'a ReleaseInfo>,
 {
     let changelog_entries = cascade! {
         gtk::Box::new(gtk::Orientation::Vertical, 12);
@@ -25,11 +58,11 @@ where
     };
 
     let mut initiated = false;
-    changelog.for_each(|(version, date, entry)| {
-        let markdown = if entry.as_ref().is_empty() {
-            fl!("changelog-unavailable")
+    changelog.for_each(|release| {
+        let markup = if release.changelog.is_empty() {
+            glib::markup_escape_text(&fl!("changelog-unavailable")).to_string()
         } else {
-            html2md::parse_html(entry.as_ref()).trim().to_string()
+            markup(&release.changelog)
         };
 
         // NOTE: If we don't set a max width in chars, the label resizes its parent.
@@ -38,10 +71,10 @@ where
 
         const PADDING: i32 = 48;
 
-        let version_label = if !date.as_ref().is_empty() {
-            format!("<b>{}</b> ({})", version.as_ref(), date.as_ref())
-        } else {
-            format!("<b>{}</b>", version.as_ref())
+        let version = glib::markup_escape_text(release.version.as_str());
+        let version_label = match release.date {
+            Some(date) => format!("<b>{}</b> ({})", version, date),
+            None => format!("<b>{}</b>", version),
         };
 
         let version = gtk::Label::builder()
@@ -54,7 +87,8 @@ where
             .build();
 
         let changelog = gtk::Label::builder()
-            .label(&*markdown)
+            .label(&*markup)
+            .use_markup(true)
             .wrap(true)
             .xalign(0.0)
             .max_width_chars(40)
@@ -68,8 +102,179 @@ where
 
         initiated = true;
         changelog_entries.add(&version);
+
+        let details = release_summary(release);
+        if !details.is_empty() {
+            changelog_entries.add(&cascade! {
+                gtk::Label::builder()
+                    .label(&details)
+                    .wrap(true)
+                    .xalign(0.0)
+                    .max_width_chars(40)
+                    .margin_start(PADDING)
+                    .margin_end(PADDING)
+                    .build();
+                ..style_context().add_class(&gtk::STYLE_CLASS_DIM_LABEL);
+            });
+        }
+
         changelog_entries.add(&changelog);
     });
 
     changelog_entries
 }
+
+/// Renders a changelog as Pango markup, for display in a `gtk::Label`.
+pub fn markup(changelog: &Changelog) -> String {
+    let mut markup = String::new();
+
+    for (id, block) in changelog.blocks.iter().enumerate() {
+        if id != 0 {
+            markup.push_str("\n\n");
+        }
+
+        match block {
+            Block::Paragraph(inlines) => markup_inlines(&mut markup, inlines),
+            Block::List { ordered, items } => {
+                for (id, item) in items.iter().enumerate() {
+                    if id != 0 {
+                        markup.push('\n');
+                    }
+
+                    if *ordered {
+                        markup.push_str(&format!("{}. ", id + 1));
+                    } else {
+                        markup.push_str("• ");
+                    }
+
+                    markup_inlines(&mut markup, item);
+                }
+            }
+        }
+    }
+
+    markup
+}
+
+fn markup_inlines(markup: &mut String, inlines: &[Inline]) {
+    for inline in inlines {
+        let text = glib::markup_escape_text(inline.text());
+        match inline {
+            Inline::Text(_) => markup.push_str(&text),
+            Inline::Emphasis(_) => markup.push_str(&format!("<i>{}</i>", text)),
+            Inline::Strong(_) => markup.push_str(&format!("<b>{}</b>", text)),
+            Inline::Code(_) => markup.push_str(&format!("<tt>{}</tt>", text)),
+            Inline::Link { url, .. } => markup.push_str(&format!(
+                "<a href=\"{}\">{}</a>",
+                glib::markup_escape_text(url),
+                text
+            )),
+        }
+    }
+}
+
+/// A grid describing the metadata of a release, which is displayed in the update dialog.
+pub fn generate_details(release: &ReleaseInfo) -> gtk::Grid {
+    let grid = gtk::Grid::builder().column_spacing(12).row_spacing(6).build();
+
+    let mut rows = Vec::new();
+
+    if let Some(date) = release.date {
+        rows.push((fl!("release-date"), date.to_string()));
+    }
+
+    if let Some(size) = release.size {
+        rows.push((fl!("release-size"), format_size(size)));
+    }
+
+    if let Some(urgency) = urgency_label(release.urgency) {
+        rows.push((fl!("release-urgency"), urgency));
+    }
+
+    if !release.issues.is_empty() {
+        rows.push((fl!("release-issues"), release.issues.join(", ")));
+    }
+
+    if let Some(ref vendor) = release.vendor {
+        rows.push((fl!("release-vendor"), vendor.to_string()));
+    }
+
+    if let Some(ref license) = release.license {
+        rows.push((fl!("release-license"), license.to_string()));
+    }
+
+    if let Some(checksum) = release.checksums.first() {
+        rows.push((fl!("release-checksum"), checksum.to_string()));
+    }
+
+    for (row, (name, value)) in rows.into_iter().enumerate() {
+        let name = cascade! {
+            gtk::Label::builder().label(&name).xalign(1.0).valign(gtk::Align::Start).build();
+            ..style_context().add_class(&gtk::STYLE_CLASS_DIM_LABEL);
+        };
+
+        let value = gtk::Label::builder()
+            .label(&value)
+            .selectable(true)
+            .wrap(true)
+            .wrap_mode(gtk::pango::WrapMode::WordChar)
+            .xalign(0.0)
+            .build();
+
+        grid.attach(&name, 0, row as i32, 1, 1);
+        grid.attach(&value, 1, row as i32, 1, 1);
+    }
+
+    grid
+}
+
+/// A short, single-line summary of the metadata of a release, for the changelog.
+fn release_summary(release: &ReleaseInfo) -> String {
+    let mut details = Vec::new();
+
+    if let Some(urgency) = urgency_label(release.urgency) {
+        details.push(fl!("release-urgency-summary", urgency = urgency));
+    }
+
+    if let Some(size) = release.size {
+        details.push(format_size(size));
+    }
+
+    if !release.issues.is_empty() {
+        details.push(fl!("release-issues-summary", issues = release.issues.join(", ")));
+    }
+
+    details.join(" · ")
+}
+
+fn urgency_label(urgency: Urgency) -> Option<String> {
+    match urgency {
+        Urgency::Unknown => None,
+        Urgency::Low => Some(fl!("urgency-low")),
+        Urgency::Medium => Some(fl!("urgency-medium")),
+        Urgency::High => Some(fl!("urgency-high")),
+        Urgency::Critical => Some(fl!("urgency-critical")),
+    }
+}
+
+/// Formats a size in bytes in the largest fitting decimal unit.
+fn format_size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["kB", "MB", "GB"];
+
+    if bytes < 1000 {
+        return format!("{} B", bytes);
+    }
+
+    let mut size = bytes as f64 / 1000.0;
+    let mut unit = UNITS[0];
+    for next in &UNITS[1..] {
+        if size < 1000.0 {
+            break;
+        }
+
+        size /= 1000.0;
+        unit = next;
+    }
+
+    format!("{:.1} {}", size, unit)
+}
@@ -1,32 +1,51 @@
-use super::FirmwareUpdateDialog;
+use super::{FirmwareUpdateDialog, UpdateSummary};
 use crate::widgets::DeviceWidget;
-use firmware_manager::{Entity, FirmwareEvent, FwupdDevice, FwupdRelease};
+use firmware_manager::{Entity, FirmwareEvent, FwupdDevice, FwupdRelease, ReleaseRange};
 use gtk::prelude::*;
 use std::sync::{mpsc::Sender, Arc};
 
-/// An instance of the firmware update dialog specific to fwupd-managed system devices.
-pub struct FwupdDialog<'a> {
+/// An update of a fwupd-managed device, which may be one of several that are installed together.
+pub struct FwupdUpdate<'a> {
+    pub changelog: ReleaseRange<'a>,
     pub device: &'a FwupdDevice,
     pub entity: Entity,
-    pub has_battery: bool,
     pub latest: &'a str,
     pub needs_reboot: bool,
     pub releases: &'a [FwupdRelease],
-    pub sender: &'a Sender<FirmwareEvent>,
     pub widgets: &'a DeviceWidget,
 }
 
+/// An instance of the firmware update dialog specific to fwupd-managed system devices.
+///
+/// Several devices of a composite device may be updated together, in which case the dialog lists
+/// every one of them.
+pub struct FwupdDialog<'a> {
+    pub has_battery: bool,
+    pub sender: &'a Sender<FirmwareEvent>,
+    pub updates: Vec<FwupdUpdate<'a>>,
+}
+
 impl<'a> FwupdDialog<'a> {
-    pub fn run(self) {
-        let log_entries = self
-            .releases
-            .iter()
-            .rev()
-            // TODO: Add release date
-            .map(|release| (release.version.as_ref(), "", release.description.as_ref()));
+    /// Returns `true` if the update was accepted and sent to the background thread.
+    ///
+    /// A single device is only confirmed if it requires a reboot, whereas the devices of a
+    /// composite device are always confirmed together.
+    pub fn run(self) -> bool {
+        let needs_reboot = self.updates.iter().any(|update| update.needs_reboot);
+
+        let response = if needs_reboot || self.updates.len() > 1 {
+            let summaries = self
+                .updates
+                .iter()
+                .map(|update| UpdateSummary {
+                    device: &update.device.name,
+                    version: update.latest,
+                    changelog: update.changelog,
+                })
+                .collect::<Vec<_>>();
 
-        let response = if self.needs_reboot {
-            let dialog = FirmwareUpdateDialog::new(self.latest, log_entries, self.has_battery);
+            let dialog =
+                FirmwareUpdateDialog::with_updates(&summaries, self.has_battery, needs_reboot);
 
             let response = dialog.run();
             dialog.close();
@@ -35,15 +54,30 @@ impl<'a> FwupdDialog<'a> {
             gtk::ResponseType::Accept
         };
 
-        if gtk::ResponseType::Accept == response {
-            // Exchange the button for a progress bar.
-            self.widgets.stack.switch_to_waiting();
-
-            let _ = self.sender.send(FirmwareEvent::Fwupd(
-                self.entity,
-                Arc::new(self.device.clone()),
-                Arc::new(self.releases.iter().last().expect("no release found").clone()),
-            ));
+        if gtk::ResponseType::Accept != response {
+            return false;
         }
+
+        let mut devices = self
+            .updates
+            .iter()
+            .map(|update| {
+                // Exchange the button for a progress bar.
+                update.widgets.stack.switch_to_waiting();
+
+                let release = update.releases.iter().last().expect("no release found");
+                (update.entity, Arc::new(update.device.clone()), Arc::new(release.clone()))
+            })
+            .collect::<Vec<_>>();
+
+        let event = if devices.len() == 1 {
+            let (entity, device, release) = devices.remove(0);
+            FirmwareEvent::Fwupd(entity, device, release)
+        } else {
+            FirmwareEvent::FwupdComposite(devices)
+        };
+
+        let _ = self.sender.send(event);
+        true
     }
 }
@@ -1,57 +1,103 @@
 mod fwupd;
+mod request;
 mod system76;
 
-pub use self::{fwupd::FwupdDialog, system76::System76Dialog};
+pub use self::{
+    fwupd::{FwupdDialog, FwupdUpdate},
+    request::RequestPrompt,
+    system76::System76Dialog,
+};
 
 use crate::fl;
+use firmware_manager::ReleaseRange;
 use gtk::prelude::*;
 
 /// A generic GTK dialog which is displayed for firmware which requires a system reboot.
 ///
-/// This dialog displays a changelog covering the details of the updates, and all prior updates, as
-/// well as a confirmation button that will initiate configuring the system to be rebooted into the
-/// firmware upgrade environment.
+/// This dialog displays a changelog covering the details of the updates, with prior updates
+/// collapsed, as well as a confirmation button that will initiate configuring the system to be
+/// rebooted into the firmware upgrade environment.
 #[derive(Shrinkwrap)]
 pub struct FirmwareUpdateDialog(gtk::Dialog);
 
+/// An update which the dialog describes, of one of the devices that will be updated.
+pub struct UpdateSummary<'a> {
+    /// The name of the device, which is shown when several devices are updated at once.
+    pub device: &'a str,
+    /// The version which the device will be updated to.
+    pub version: &'a str,
+    /// The releases between the installed version and `version`.
+    pub changelog: ReleaseRange<'a>,
+}
+
 impl FirmwareUpdateDialog {
-    pub fn new<S: AsRef<str>, I: Iterator<Item = (S, S, S)>>(
-        version: &str,
-        changelog: I,
-        has_battery: bool,
-    ) -> Self {
-        let changelog_entries = crate::changelog::generate_widget(changelog);
+    /// Creates the dialog from the releases between the installed version and `version`.
+    pub fn new(version: &str, changelog: ReleaseRange, has_battery: bool) -> Self {
+        Self::with_updates(&[UpdateSummary { device: "", version, changelog }], has_battery, true)
+    }
 
-        let mut header = fl!("update-available", version = version);
-        header.push(' ');
+    /// Creates the dialog for updates which are installed together, listing every device.
+    ///
+    /// The guide to rebooting into the update is only shown if `needs_reboot` is set.
+    pub fn with_updates(updates: &[UpdateSummary], has_battery: bool, needs_reboot: bool) -> Self {
+        let mut notice = String::new();
 
         if has_battery {
-            header.push_str(&fl!("update-connect-to-ac"));
-            header.push_str("\n\n")
+            notice.push_str(&fl!("update-connect-to-ac"));
         }
 
-        header.push_str(&fl!(
-            "update-guide",
-            url_tag_start = "<a href=\"https://support.system76.com/articles/system-firmware/\">",
-            url_tag_end = "</a>"
-        ));
+        if needs_reboot {
+            if !notice.is_empty() {
+                notice.push_str("\n\n");
+            }
 
-        let changelog_text = format!("<b>{}</b>", fl!("changelog"));
+            let article = "<a href=\"https://support.system76.com/articles/system-firmware/\">";
+            notice.push_str(&fl!("update-guide", url_tag_start = article, url_tag_end = "</a>"));
+        }
 
         let changelog_container = cascade! {
             gtk::Box::new(gtk::Orientation::Vertical, 12);
             ..set_vexpand(true);
-            ..add(&gtk::Label::builder().label(&*header).wrap(true).xalign(0.0).use_markup(true).build());
-            ..add(&gtk::Label::builder().label(&*changelog_text).use_markup(true).xalign(0.0).build());
-            ..add(&changelog_entries);
-            ..show_all();
         };
 
+        if let [update] = updates {
+            let mut header = fl!("update-available", version = update.version);
+            if !notice.is_empty() {
+                header.push(' ');
+                header.push_str(&notice);
+            }
+
+            changelog_container.add(&markup_label(&header));
+            add_update(&changelog_container, update);
+        } else {
+            if !notice.is_empty() {
+                changelog_container.add(&markup_label(&notice));
+            }
+
+            for update in updates {
+                let device = glib::markup_escape_text(update.device);
+                changelog_container.add(&markup_label(&format!("<b>{}</b>", device)));
+                let available = fl!("update-available", version = update.version);
+                changelog_container.add(&markup_label(&glib::markup_escape_text(&available)));
+                add_update(&changelog_container, update);
+            }
+        }
+
+        changelog_container.show_all();
+
         let cancel = gtk::Button::with_label(&fl!("button-cancel"));
 
+        let accept_label = if needs_reboot {
+            fl!("button-reboot-and-install")
+        } else if updates.len() > 1 {
+            fl!("button-update-all")
+        } else {
+            fl!("button-update")
+        };
+
         let reboot = cascade! {
             gtk::Button::builder()
-                .label(&fl!("button-reboot-and-install"))
+                .label(&accept_label)
                 .build();
             ..style_context().add_class(&gtk::STYLE_CLASS_SUGGESTED_ACTION);
         };
@@ -128,3 +174,19 @@ impl FirmwareUpdateDialog {
         Self(dialog)
     }
 }
+
+/// A label which wraps, and which may contain markup.
+fn markup_label(markup: &str) -> gtk::Label {
+    gtk::Label::builder().label(markup).wrap(true).xalign(0.0).use_markup(true).build()
+}
+
+/// Adds the details of the release which an update installs, and its changelog.
+fn add_update(container: &gtk::Box, update: &UpdateSummary) {
+    let details = match update.changelog.pending.last() {
+        Some(release) => crate::changelog::generate_details(release),
+        None => gtk::Grid::new(),
+    };
+
+    container.add(&details);
+    container.add(&crate::changelog::generate_range_widget(update.changelog));
+}
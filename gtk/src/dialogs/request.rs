@@ -0,0 +1,91 @@
+use crate::fl;
+use firmware_manager::{DeviceRequest, RequestKind, WellKnownRequest};
+use gtk::prelude::*;
+
+/// Instructs the user to interact with a device, in a modal popover attached to the device's row.
+#[derive(Shrinkwrap)]
+pub struct RequestPrompt(gtk::Popover);
+
+impl RequestPrompt {
+    /// Creates the prompt for `request`, pointing at `relative_to`.
+    pub fn new<W: IsA<gtk::Widget>>(relative_to: &W, request: &DeviceRequest) -> Self {
+        let heading = match request.kind {
+            RequestKind::Immediate => fl!("request-immediate"),
+            RequestKind::Post => fl!("request-post"),
+        };
+
+        let layout = cascade! {
+            gtk::Box::new(gtk::Orientation::Vertical, 12);
+            ..set_border_width(12);
+            ..add(&gtk::Label::builder()
+                .label(&format!("<b>{}</b>", heading))
+                .use_markup(true)
+                .xalign(0.0)
+                .build());
+        };
+
+        // Images are scaled down to fit the popover, as they may be of any size.
+        let image = request.image.as_ref().and_then(|path| {
+            gtk::gdk_pixbuf::Pixbuf::from_file_at_scale(path, 320, 240, true)
+                .map_err(|why| warn!("failed to load image of device request: {}", why))
+                .ok()
+        });
+
+        if let Some(image) = image {
+            layout.add(&gtk::Image::from_pixbuf(Some(&image)));
+        }
+
+        layout.add(
+            &gtk::Label::builder()
+                .label(&instructions(request))
+                .max_width_chars(40)
+                .wrap(true)
+                .xalign(0.0)
+                .build(),
+        );
+
+        let popover = cascade! {
+            gtk::Popover::new(Some(relative_to));
+            ..set_modal(true);
+            ..add(&layout);
+        };
+
+        let close = cascade! {
+            gtk::Button::with_label(&fl!("button-ok"));
+            ..set_halign(gtk::Align::End);
+            ..style_context().add_class(&gtk::STYLE_CLASS_SUGGESTED_ACTION);
+            ..connect_clicked({
+                let popover = popover.downgrade();
+                move |_| {
+                    if let Some(popover) = popover.upgrade() {
+                        popover.popdown();
+                    }
+                }
+            });
+        };
+
+        layout.add(&close);
+        layout.show_all();
+
+        Self(popover)
+    }
+}
+
+/// Localized instructions for well-known requests, or else the instructions that fwupd gave.
+fn instructions(request: &DeviceRequest) -> String {
+    let well_known = match request.well_known() {
+        Some(request) => request,
+        None if !request.message.is_empty() => return request.message.clone(),
+        None => return fl!("request-generic"),
+    };
+
+    match well_known {
+        WellKnownRequest::DoNotPowerOff => fl!("request-do-not-power-off"),
+        WellKnownRequest::InsertUsbCable => fl!("request-insert-usb-cable"),
+        WellKnownRequest::PressUnlock => fl!("request-press-unlock"),
+        WellKnownRequest::RemoveReplug => fl!("request-remove-replug"),
+        WellKnownRequest::RemoveUsbCable => fl!("request-remove-usb-cable"),
+        WellKnownRequest::ReplugInstall => fl!("request-replug-install"),
+        WellKnownRequest::ReplugPower => fl!("request-replug-power"),
+    }
+}
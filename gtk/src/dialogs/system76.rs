@@ -1,12 +1,12 @@
 use super::FirmwareUpdateDialog;
 use crate::widgets::DeviceWidget;
-use firmware_manager::{Entity, FirmwareEvent, System76Changelog, System76Digest};
+use firmware_manager::{Entity, FirmwareEvent, ReleaseRange, System76Digest};
 use gtk::prelude::*;
 use std::sync::mpsc::Sender;
 
 /// An instance of the firmware update dialog specific to system76-managed system devices.
 pub struct System76Dialog<'a> {
-    pub changelog: &'a System76Changelog,
+    pub changelog: ReleaseRange<'a>,
     pub digest: &'a System76Digest,
     pub entity: Entity,
     pub has_battery: bool,
@@ -17,11 +17,7 @@ pub struct System76Dialog<'a> {
 
 impl<'a> System76Dialog<'a> {
     pub fn run(self) {
-        let log_entries = self.changelog.versions.iter().map(|version| {
-            (version.bios.as_ref(), version.date.as_ref(), version.description.as_ref())
-        });
-
-        let dialog = FirmwareUpdateDialog::new(self.latest, log_entries, self.has_battery);
+        let dialog = FirmwareUpdateDialog::new(self.latest, self.changelog, self.has_battery);
 
         if gtk::ResponseType::Accept == dialog.run() {
             // Exchange the button for a progress bar.
@@ -32,9 +32,11 @@ use firmware_manager::*;
 use gtk::{self, prelude::*};
 use slotmap::DefaultKey as Entity;
 use std::{
+    cell::Cell,
     collections::HashSet,
-    error::Error as _,
+    path::PathBuf,
     process::Command,
+    rc::Rc,
     sync::{
         atomic::{AtomicBool, Ordering},
         mpsc::{channel, Receiver, Sender, TryRecvError},
@@ -56,22 +58,39 @@ pub(crate) enum ActivateEvent {
 /// The complete firmware manager, as a widget structure
 pub struct FirmwareWidget {
     container: gtk::Container,
+    pages: gtk::Stack,
+    /// Switches between the pages, unless the application places a switcher of its own.
+    inline_switcher: gtk::StackSwitcher,
     sender: Sender<FirmwareEvent>,
+    ui_sender: glib::Sender<Event>,
     background: Option<JoinHandle<()>>,
-    is_admin: bool,
+    /// Set once the user is authorized to update firmware, which they may be after unlocking.
+    is_admin: Rc<Cell<bool>>,
 }
 
 /// An event which the GTK UI may propagate to the event loop in the main context.
 #[derive(Debug)]
 enum UiEvent {
+    /// The user responded to a request to authenticate.
+    Authorized(Authorization),
     /// It was requested to hide the upgrade stack of an entity
     HideStack(Entity),
     /// An entity is scheduled to be revealed
     Reveal(Entity),
     /// An entity has been revealed
     Revealed(Entity, bool),
+    /// A request to act on a device, such as unlocking it, was made from its row.
+    Action(Entity, FirmwareEvent),
+    /// A report of the firmware found by the last scan is to be written to a file.
+    ExportReport(PathBuf, ReportFormat),
     /// The update button of an entity was triggered
     Update(Entity),
+    /// The button which updates every device of a composite device was triggered.
+    UpdateComposite(Entity),
+    /// The installed firmware of every device is to be verified.
+    Verify,
+    /// The installed firmware of every device is to be recorded as known-good.
+    VerifyBaseline,
 }
 
 /// An event that requests for the UI to perform a specific action.
@@ -97,6 +116,7 @@ impl FirmwareWidget {
 
         let view_devices = DevicesView::new();
         let view_empty = EmptyView::new();
+        let view_security = SecurityView::new();
 
         let info_bar_label = cascade! {
             gtk::Label::new(None);
@@ -133,23 +153,66 @@ impl FirmwareWidget {
             ..set_no_show_all(true);
         };
 
-        let is_admin = user_is_admin();
+        let (tx_events, rx_events) = glib::MainContext::channel::<Event>(glib::PRIORITY_DEFAULT);
+
+        let authorization = check_authorization(Action::UpdateFirmware);
+        let is_admin = Rc::new(Cell::new(authorization.is_authorized()));
 
-        if is_admin {
+        if is_admin.get() {
             stack.set_visible_child(view_empty.as_ref());
         } else {
-            let view = PermissionView::new();
+            let view = PermissionView::new(authorization.is_possible());
+
+            // Authentication blocks until the user responds, so it happens in the background.
+            let sender = tx_events.clone();
+            view.connect_unlock(move || {
+                let sender = sender.clone();
+                thread::spawn(move || {
+                    let authorization = authorize(Action::UpdateFirmware);
+                    let _ = sender.send(Event::Ui(UiEvent::Authorized(authorization)));
+                });
+            });
+
             stack.add(view.as_ref());
             stack.set_visible_child(view.as_ref());
             stack.show();
         }
 
+        // The stack of device views is hidden while scanning, so it is held in a box which
+        // remains visible to keep its page in the switcher.
+        let devices_page = cascade! {
+            gtk::Box::new(gtk::Orientation::Vertical, 0);
+            ..add(&stack);
+        };
+
+        let pages = cascade! {
+            gtk::Stack::new();
+            ..add_titled(&devices_page, "devices", &fl!("page-devices"));
+            ..add_titled(view_security.as_ref(), "security", &fl!("page-security"));
+        };
+
+        // Embedders such as GNOME Settings have no header bar of ours to place a switcher in.
+        let inline_switcher = cascade! {
+            gtk::StackSwitcher::new();
+            ..set_stack(Some(&pages));
+            ..set_halign(gtk::Align::Center);
+            ..set_margin_top(12);
+            ..set_no_show_all(true);
+            ..show();
+        };
+
+        let layout = cascade! {
+            gtk::Box::new(gtk::Orientation::Vertical, 0);
+            ..add(&inline_switcher);
+            ..pack_start(&pages, true, true, 0);
+        };
+
         let container = {
             let sender = sender.clone();
             let container = cascade! {
                 gtk::Overlay::new();
                 ..add_overlay(&info_bar);
-                ..add(&stack);
+                ..add(&layout);
                 ..set_can_default(true);
                 ..connect_key_press_event(move |_, event| {
                     gtk::Inhibit(if event.keyval() == gdk::keys::constants::F5 {
@@ -168,20 +231,21 @@ impl FirmwareWidget {
         info_bar.hide();
 
         let (tx_progress, rx_progress) = channel();
-        let (tx_events, rx_events) = glib::MainContext::channel::<Event>(glib::PRIORITY_DEFAULT);
 
         // Spawns a background thread to handle all background events.
         let background = Self::background(rx, tx_events.clone());
 
         let state = State::new(
+            is_admin.clone(),
             sender.clone(),
-            tx_events,
+            tx_events.clone(),
             tx_progress,
             stack.clone(),
             info_bar,
             info_bar_label,
             view_devices,
             view_empty,
+            view_security,
         );
 
         Self::attach_main_event_loop(state, rx_events);
@@ -190,8 +254,101 @@ impl FirmwareWidget {
         Self {
             background: Some(background),
             container: container.upcast::<gtk::Container>(),
+            inline_switcher,
             is_admin,
+            pages,
             sender,
+            ui_sender: tx_events,
+        }
+    }
+
+    /// Creates a switcher between the devices and security pages, for use in a header bar.
+    ///
+    /// The widget otherwise shows a switcher above its pages, which this hides.
+    pub fn switcher(&self) -> gtk::StackSwitcher {
+        self.inline_switcher.hide();
+        gtk::StackSwitcher::builder().stack(&self.pages).build()
+    }
+
+    /// A menu of the actions that the application may offer for this widget.
+    ///
+    /// The application is expected to provide the `app.export-report`, `app.verify`, and
+    /// `app.verify-baseline` actions, which should call `export_report`, `verify`, and
+    /// `verify_baseline`.
+    pub fn menu(&self) -> gio::Menu {
+        cascade! {
+            gio::Menu::new();
+            ..append(Some(&fl!("action-verify")), Some("app.verify"));
+            ..append(Some(&fl!("action-verify-baseline")), Some("app.verify-baseline"));
+            ..append(Some(&fl!("action-export-report")), Some("app.export-report"));
+        }
+    }
+
+    /// Verifies the installed firmware of every device which supports it against its known-good
+    /// checksums, and shows the result of each device in its row.
+    pub fn verify(&self) {
+        if self.is_admin.get() {
+            let _ = self.ui_sender.send(Event::Ui(UiEvent::Verify));
+        }
+    }
+
+    /// Records the installed firmware of every device which supports it as known-good, so that
+    /// later verifications can detect changes to it.
+    pub fn verify_baseline(&self) {
+        if self.is_admin.get() {
+            let _ = self.ui_sender.send(Event::Ui(UiEvent::VerifyBaseline));
+        }
+    }
+
+    /// Asks where to save a report of the firmware found by the last scan, and writes it there.
+    ///
+    /// The format is determined by the extension of the file name, or else by the selected
+    /// filter.
+    pub fn export_report(&self, parent: Option<&gtk::Window>) {
+        let json = cascade! {
+            gtk::FileFilter::new();
+            ..set_name(Some("JSON"));
+            ..add_pattern("*.json");
+        };
+
+        let csv = cascade! {
+            gtk::FileFilter::new();
+            ..set_name(Some("CSV"));
+            ..add_pattern("*.csv");
+        };
+
+        let dialog = cascade! {
+            gtk::FileChooserNative::new(
+                Some(&fl!("export-report-title")),
+                parent,
+                gtk::FileChooserAction::Save,
+                None,
+                None,
+            );
+            ..set_do_overwrite_confirmation(true);
+            ..set_current_name("firmware-report.json");
+            ..add_filter(&json);
+            ..add_filter(&csv);
+        };
+
+        if dialog.run() != gtk::ResponseType::Accept {
+            return;
+        }
+
+        if let Some(mut path) = dialog.filename() {
+            let format = match ReportFormat::from_path(&path) {
+                Some(format) => format,
+                None if dialog.filter().as_ref() == Some(&csv) => {
+                    path.set_extension("csv");
+                    ReportFormat::Csv
+                }
+                None => {
+                    path.set_extension("json");
+                    ReportFormat::Json
+                }
+            };
+
+            let _ = self.ui_sender.send(Event::Ui(UiEvent::ExportReport(path, format)));
         }
     }
 
@@ -201,7 +358,7 @@ impl FirmwareWidget {
     /// devices view with new devices, if found. If devices are not found, the empty view
     /// will be displayed instead.
     pub fn scan(&self) {
-        if self.is_admin {
+        if self.is_admin.get() {
             let _ = self.sender.send(FirmwareEvent::Scan);
         }
     }
@@ -223,18 +380,46 @@ impl FirmwareWidget {
         // TODO: Use a better approach than an Arc<AtomicBool>
         let firmware_flashing = Arc::new(AtomicBool::new(false));
         let firmware_flashing_ = firmware_flashing.clone();
+        let firmware_flashing_session = firmware_flashing.clone();
         let tx_udev = state.sender.clone();
-        let usb_trigger = usb_hotplug_event_loop(move || {
-            if !firmware_flashing_.load(Ordering::SeqCst) {
+        let tx_session = state.sender.clone();
+        let hotplug_trigger = hotplug_event_loop(HotplugConfig::default(), move |devices| {
+            // Only devices which come and go, and displays which are connected or disconnected,
+            // change what is found by a scan. Other changes, such as mode sets, are ignored.
+            let rescan = devices.iter().any(HotplugDevice::needs_rescan);
+
+            // Devices replug while they are flashed, by this process or by another.
+            if rescan && !firmware_flashing_.load(Ordering::SeqCst) && lock_holder().is_none() {
                 let _ = tx_udev.send(FirmwareEvent::Scan);
             }
         });
 
+        // Without udev, such as in a sandbox, devices are found by rescanning manually.
+        if let Err(ref why) = hotplug_trigger {
+            warn!("hotplugged devices will not be detected: {}", why);
+        }
+
+        // Devices may be connected while suspended, or while the session is locked.
+        let session = session_event_loop(move |_| {
+            if !firmware_flashing_session.load(Ordering::SeqCst) && lock_holder().is_none() {
+                let _ = tx_session.send(FirmwareEvent::Scan);
+            }
+        });
+
+        if let Err(why) = session {
+            warn!("devices will not be rescanned after resuming: {}", why);
+        }
+
         receiver.attach(None, move |event| {
-            // Capture the USB trigger in the lifetime of the attached receiver.
-            let _ = usb_trigger;
+            // Capture the hotplug trigger in the lifetime of the attached receiver.
+            let _ = hotplug_trigger;
 
             trace!("received UI event: {:#?}", Paint::yellow(&event));
+
+            if let Firmware(ref signal) = event {
+                state.report.add(signal);
+            }
+
             match event {
                 // When a device begins flashing, we can begin moving the progress bar based on
                 // its duration.
@@ -250,12 +435,34 @@ impl FirmwareWidget {
                     widget.stack.switch_to_progress(&message);
                     state.progress_activate(&widget.stack.progress);
                 }
+                // The known-good firmware of a device was recorded.
+                Firmware(BaselineRecorded(entity)) => {
+                    if let Some(widget) = state.components.device_widgets.get(entity) {
+                        widget.set_verification(&fl!("verify-baseline-recorded"), None);
+                    }
+                }
+                // The installed firmware of a device is being verified.
+                Firmware(DeviceVerifying(entity)) => {
+                    if let Some(widget) = state.components.device_widgets.get(entity) {
+                        widget.set_verification(&fl!("action-verifying"), None);
+                    }
+                }
+                // The result of verifying the installed firmware of a device.
+                Firmware(DeviceVerified(entity, integrity)) => {
+                    state.device_verified(entity, integrity)
+                }
+                // Activating or unlocking a device changes its flags, so the devices are rescanned.
+                Firmware(DeviceActivated(_)) | Firmware(DeviceUnlocked(_)) => {
+                    let _ = state.sender.send(FirmwareEvent::Scan);
+                }
                 // An event that occurs when firmware has successfully updated.
                 Firmware(DeviceUpdated(entity)) => {
                     state.widgets.info_bar.set_visible(false);
                     firmware_flashing.store(false, Ordering::SeqCst);
-                    let latest = state.components.latest.remove(entity);
-                    state.device_updated(entity, latest.expect("updated device without version"))
+                    // A device which was updated before has no later version to be updated to.
+                    if let Some(latest) = state.components.latest.remove(entity) {
+                        state.device_updated(entity, latest);
+                    }
                 }
                 // Firmware for a device has begun downloading.
                 Firmware(DownloadBegin(entity, size)) => {
@@ -277,20 +484,11 @@ impl FirmwareWidget {
                     widget.stack.progress.set_fraction(progress.0 as f64 / progress.1 as f64);
                 }
                 // Device has requested interaction.
-                Firmware(DeviceRequest(message)) => {
-                    state.widgets.info_bar.set_visible(true);
-                    state.widgets.info_bar_label.set_text(message.as_str());
-                }
+                Firmware(DeviceRequest(request)) => state.device_request(&request),
                 // An error occurred in the background thread, which we shall display in the UI.
                 Firmware(Error(entity, why)) => {
                     firmware_flashing.store(false, Ordering::SeqCst);
-                    // Convert the error and its causes into a string.
-                    let mut error_message = format!("{}", why);
-                    let mut cause = why.source();
-                    while let Some(error) = cause {
-                        error_message.push_str(format!(": {}", error).as_str());
-                        cause = error.source();
-                    }
+                    let error_message = format_error(&why);
 
                     error!("firmware widget error: {}", error_message);
 
@@ -303,14 +501,38 @@ impl FirmwareWidget {
                         state.components.firmware_download.remove(entity);
                         state.progress_deactivate(&widget.stack.progress);
                     }
+
+                    if let firmware_manager::Error::Locked(LockError::Busy(_)) = why {
+                        state.watch_operation();
+                    }
                 }
                 // An event that occurs when fwupd firmware is found.
                 Firmware(Fwupd(signal)) => state.fwupd(signal),
+                // Metadata which was not refreshed may be missing newer firmware.
+                Firmware(Metadata(age)) => {
+                    if age.offline {
+                        let when = match age.days() {
+                            Some(days) => fl!("metadata-age", days = days),
+                            None => fl!("metadata-age-unknown"),
+                        };
+
+                        let source = age.source.to_string();
+                        let message = fl!("metadata-offline", source = source, age = when);
+                        warn!("{}", message);
+                        state.widgets.info_bar.set_visible(true);
+                        state.widgets.info_bar_label.set_text(&message);
+                    }
+                }
+                // The security attributes of the host were fetched from fwupd.
+                Firmware(Security(security, previous)) => {
+                    state.widgets.view_security.update(&security, previous.as_ref())
+                }
                 // Begins searching for devices that have firmware upgrade support
                 Firmware(Scanning) => {
                     state.widgets.view_devices.clear();
                     last_active_revealer = None;
                     state.entities.clear();
+                    state.components.device_ids.clear();
 
                     let _ = state.progress_sender.send(ActivateEvent::Clear);
 
@@ -332,10 +554,40 @@ impl FirmwareWidget {
                 // An event that occurs when System76 system firmware has been found.
                 Firmware(S76System(info, data)) => state.system76_system(info, data),
                 // An event that occurs when a Thelio I/O board was discovered.
-                Firmware(ThelioIo(info, digest)) => state.thelio_io(info, digest),
+                Firmware(ThelioIo(signal)) => state.thelio_io(signal),
+                // The checksum of downloaded firmware is being verified.
+                Firmware(Verifying(entity)) => {
+                    let widget = &state.components.device_widgets[entity];
+                    widget.stack.switch_to_progress(&fl!("action-verifying"));
+                    widget.stack.progress.set_fraction(1.0);
+                }
+                // A mismatch is followed by an error, so the result only needs to be logged.
+                Firmware(Verified(entity, verification)) => match verification {
+                    Verification::Verified(checksum) => {
+                        info!("verified firmware of {:?}: {}", entity, checksum)
+                    }
+                    Verification::Unverified(checksum) => {
+                        warn!("firmware of {:?} has no checksum to verify: {}", entity, checksum)
+                    }
+                    Verification::Mismatch { expected, actual } => error!(
+                        "firmware of {:?} has checksum {}, expected {}",
+                        entity, actual, expected
+                    ),
+                },
                 // Schedules the given firmware for an update, and show a dialog if it requires a
                 // reboot.
                 Ui(Update(entity)) => state.update(entity),
+                // Updates every device of a composite device, in the order that fwupd requires.
+                Ui(UpdateComposite(entity)) => state.update_composite(entity),
+                // Verifies, or records, the installed firmware of every device which supports it.
+                Ui(Verify) => state.verify(false),
+                Ui(VerifyBaseline) => state.verify(true),
+                // Scans for firmware once the user has authenticated.
+                Ui(Authorized(authorization)) => state.authorized(authorization),
+                // Forwards an action on a device to the background thread.
+                Ui(Action(entity, event)) => state.device_action(entity, event),
+                // Writes a report of the firmware found by the last scan.
+                Ui(ExportReport(path, format)) => state.export_report(&path, format),
                 // Hides the entity's stack.
                 Ui(HideStack(entity)) => {
                     if let Some(widget) = state.components.device_widgets.get(entity) {
@@ -371,6 +623,9 @@ impl FirmwareWidget {
     }
 
     /// Manages all firmware client interactions from a background thread.
+    ///
+    /// Events are handed to the firmware manager service when it is available, so that the
+    /// operations of every frontend are shared, unless a session is being recorded or replayed.
     fn background(
         receiver: Receiver<FirmwareEvent>,
         sender: glib::Sender<Event>,
@@ -390,7 +645,15 @@ impl FirmwareWidget {
 
 
         thread::spawn(move || {
-            firmware_manager::event_loop(receiver, tx);
+            let recording = [RECORD_VARIABLE, REPLAY_VARIABLE]
+                .iter()
+                .any(|variable| std::env::var_os(variable).is_some());
+
+            match Some(Bus::System).filter(|_| !recording).and_then(ServiceClient::connect) {
+                Some(client) => firmware_manager::service_event_loop(client, receiver, tx),
+                None => firmware_manager::event_loop(receiver, tx),
+            }
+
             info!("firmware manager event loop stopped");
             let _ = sender.send(Event::Stop);
         })
@@ -31,10 +31,64 @@ fn main() {
         let widget = Rc::new(FirmwareWidget::new());
         widget.scan();
 
+        let export_report = cascade! {
+            gio::SimpleAction::new("export-report", None);
+            ..connect_activate({
+                let weak_widget = Rc::downgrade(&widget);
+                let app = app.clone();
+                move |_, _| {
+                    if let Some(widget) = weak_widget.upgrade() {
+                        widget.export_report(app.active_window().as_ref());
+                    }
+                }
+            });
+        };
+
+        app.add_action(&export_report);
+        app.set_accels_for_action("app.export-report", &["<Primary>e"]);
+
+        let verify = cascade! {
+            gio::SimpleAction::new("verify", None);
+            ..connect_activate({
+                let weak_widget = Rc::downgrade(&widget);
+                move |_, _| {
+                    if let Some(widget) = weak_widget.upgrade() {
+                        widget.verify();
+                    }
+                }
+            });
+        };
+
+        let verify_baseline = cascade! {
+            gio::SimpleAction::new("verify-baseline", None);
+            ..connect_activate({
+                let weak_widget = Rc::downgrade(&widget);
+                move |_, _| {
+                    if let Some(widget) = weak_widget.upgrade() {
+                        widget.verify_baseline();
+                    }
+                }
+            });
+        };
+
+        app.add_action(&verify);
+        app.add_action(&verify_baseline);
+
+        let menu = gtk::MenuButton::builder()
+            .image(gtk::Image::builder()
+                .icon_name("open-menu-symbolic")
+                .icon_size(gtk::IconSize::SmallToolbar.into())
+                .build()
+                .upcast_ref::<gtk::Widget>()
+            )
+            .menu_model(&widget.menu())
+            .build();
+
         let weak_widget = Rc::downgrade(&widget);
         let headerbar = cascade! {
             gtk::HeaderBar::builder()
                 .title("Firmware Manager")
+                .custom_title(&widget.switcher())
                 .show_close_button(true)
                 .build();
             ..pack_end(&cascade! {
@@ -52,6 +106,7 @@ fn main() {
                     }
                 });
             });
+            ..pack_end(&menu);
         };
 
         let _window = cascade! {
@@ -3,10 +3,18 @@ use firmware_manager::*;
 
 use gtk::prelude::*;
 use slotmap::{DefaultKey as Entity, SecondaryMap, SparseSecondaryMap};
-use std::sync::mpsc::Sender;
+use std::{
+    cell::Cell,
+    collections::HashMap,
+    path::Path,
+    rc::Rc,
+    sync::{mpsc::Sender, Arc},
+};
 
 /// Manages all state and state interactions with the UI.
 pub(crate) struct State {
+    /// Whether the user is authorized to update firmware, which is shared with the widget.
+    pub(crate) authorized: Rc<Cell<bool>>,
     /// Components that have been associated with entities.
     pub(crate) components: Components,
     /// All devices will be created as an entity here
@@ -15,10 +23,14 @@ pub(crate) struct State {
     pub(crate) has_battery: bool,
     /// Sends events to the progress signal
     pub(crate) progress_sender: Sender<ActivateEvent>,
+    /// An inventory of the firmware found by the last scan.
+    pub(crate) report: Report,
     /// A sender to send firmware requests to the background thread
     pub(crate) sender: Sender<FirmwareEvent>,
     /// Events to be processed by the main event loop
     pub(crate) ui_sender: glib::Sender<Event>,
+    /// Whether an operation of another process is being followed in the info bar.
+    pub(crate) watching: Rc<Cell<bool>>,
     /// Widgets that will be actively managed.
     pub(crate) widgets: Widgets,
 }
@@ -35,6 +47,8 @@ pub(crate) struct Widgets {
     pub(crate) view_devices: DevicesView,
     /// The empty view is displayed when a scan found no devices.
     pub(crate) view_empty: EmptyView,
+    /// The security view shows the security attributes of the host.
+    pub(crate) view_security: SecurityView,
 }
 
 /// Components are optional pieces of data that are assigned to entities
@@ -46,17 +60,37 @@ pub(crate) struct Components {
     /// Tracks progress of a firmware download.
     pub(crate) firmware_download: SecondaryMap<Entity, (u64, u64)>,
 
+    /// The currently-installed version of a device, if the device reports it.
+    pub(crate) current: SecondaryMap<Entity, Option<Version>>,
+
     /// The latest version associated with a device, if one exists.
-    pub(crate) latest: SecondaryMap<Entity, Box<str>>,
+    pub(crate) latest: SecondaryMap<Entity, Version>,
+
+    /// Metadata of the releases available for a device, from oldest to newest.
+    pub(crate) releases: SecondaryMap<Entity, Vec<ReleaseInfo>>,
+
+    /// The entities of fwupd devices, by the ID that fwupd assigned to them.
+    ///
+    /// This must be cleared when the entities are.
+    pub(crate) device_ids: HashMap<Box<str>, Entity>,
+
+    /// The parent device of each device which belongs to a composite device.
+    pub(crate) parents: SparseSecondaryMap<Entity, Entity>,
+
+    /// fwupd devices which can be updated to their latest release right now.
+    pub(crate) ready: SparseSecondaryMap<Entity, ()>,
 
     /// Details about a fwupd device
     pub(crate) fwupd: SparseSecondaryMap<Entity, (FwupdDevice, Vec<FwupdRelease>)>,
 
+    /// fwupd devices whose installed firmware can be verified.
+    pub(crate) verifiable: SparseSecondaryMap<Entity, Arc<FwupdDevice>>,
+
     /// Details about system76 system firmware.
-    pub(crate) system76: SparseSecondaryMap<Entity, (System76Digest, System76Changelog)>,
+    pub(crate) system76: SparseSecondaryMap<Entity, System76Digest>,
 
-    /// Details about thelio I/O firmware
-    pub(crate) thelio: SparseSecondaryMap<Entity, System76Digest>,
+    /// Details about thelio I/O firmware, and the identifier of the board
+    pub(crate) thelio: SparseSecondaryMap<Entity, (Box<str>, System76Digest)>,
 }
 
 impl State {
@@ -64,6 +98,7 @@ impl State {
     /// context.
     #[allow(clippy::too_many_arguments)]
     pub fn new(
+        authorized: Rc<Cell<bool>>,
         sender: Sender<FirmwareEvent>,
         ui_sender: glib::Sender<Event>,
         progress_sender: Sender<ActivateEvent>,
@@ -72,21 +107,44 @@ impl State {
         info_bar_label: gtk::Label,
         view_devices: DevicesView,
         view_empty: EmptyView,
+        view_security: SecurityView,
     ) -> Self {
         let has_battery =
             upower_dbus::UPower::new(-1).and_then(|upower| upower.on_battery()).unwrap_or(false);
 
         Self {
+            authorized,
             entities: Entities::default(),
             components: Components::default(),
             has_battery,
             progress_sender,
+            report: Report::new(),
             sender,
-            widgets: Widgets { info_bar, info_bar_label, stack, view_devices, view_empty },
+            watching: Rc::default(),
+            widgets: Widgets {
+                info_bar,
+                info_bar_label,
+                stack,
+                view_devices,
+                view_empty,
+                view_security,
+            },
             ui_sender,
         }
     }
 
+    /// Begins scanning for firmware once the user authenticated to update it.
+    pub fn authorized(&mut self, authorization: Authorization) {
+        if !authorization.is_authorized() {
+            info!("user was not authorized to update firmware");
+            return;
+        }
+
+        self.authorized.set(true);
+        self.widgets.stack.set_visible_child(self.widgets.view_empty.as_ref());
+        let _ = self.sender.send(FirmwareEvent::Scan);
+    }
+
     /// The base method for creating a new firmware device entity.
     pub fn create_device<F: FnOnce(&mut Self, Entity) -> DeviceWidget>(&mut self, func: F) {
         let entity = self.entities.create();
@@ -97,10 +155,13 @@ impl State {
     }
 
     /// An event that occurs when firmware has successfully updated.
-    pub fn device_updated(&mut self, entity: Entity, latest: Box<str>) {
+    pub fn device_updated(&mut self, entity: Entity, latest: Version) {
+        self.unready(entity);
+        self.components.current.insert(entity, Some(latest.clone()));
+
         if let Some(widget) = self.components.device_widgets.get(entity) {
             widget.stack.progress.set_fraction(1.0);
-            widget.label.set_text(latest.as_ref());
+            widget.label.set_text(latest.as_str());
 
             self.progress_deactivate(&widget.stack.progress);
             if self.entities.is_system(entity) {
@@ -117,28 +178,199 @@ impl State {
         }
     }
 
+    /// Follows the operation of another process in the info bar, until it releases the lock.
+    ///
+    /// Devices are scanned again once the operation ends, as it may have updated their firmware.
+    pub fn watch_operation(&mut self) {
+        self.widgets.info_bar.set_visible(true);
+        self.widgets.info_bar_label.set_text(&operation_message(lock_holder().as_ref()));
+
+        if self.watching.replace(true) {
+            return;
+        }
+
+        let watching = self.watching.clone();
+        let info_bar = self.widgets.info_bar.clone();
+        let label = self.widgets.info_bar_label.clone();
+        let sender = self.sender.clone();
+        glib::timeout_add_seconds_local(1, move || match lock_holder() {
+            Some(holder) => {
+                label.set_text(&operation_message(Some(&holder)));
+                glib::Continue(true)
+            }
+            None => {
+                watching.set(false);
+                info_bar.set_visible(false);
+                let _ = sender.send(FirmwareEvent::Scan);
+                glib::Continue(false)
+            }
+        });
+    }
+
+    /// Prompts the user to act upon a request of a device, beside the row of the device.
+    ///
+    /// Requests of devices which were not acted upon are attached to the devices view instead.
+    pub fn device_request(&mut self, request: &DeviceRequest) {
+        info!("device {} requested {}: {}", request.device_id, request.id, request.message);
+
+        let widget = request.entity.and_then(|entity| self.components.device_widgets.get(entity));
+        let prompt = match widget {
+            Some(widget) => RequestPrompt::new(&widget.event_box, request),
+            None => RequestPrompt::new(&self.widgets.stack, request),
+        };
+
+        prompt.popup();
+    }
+
+    /// Sends a request to act on a device, such as unlocking it, to the background thread.
+    pub fn device_action(&mut self, entity: Entity, event: FirmwareEvent) {
+        if let Some(widget) = self.components.device_widgets.get(entity) {
+            widget.stack.switch_to_waiting();
+        }
+
+        let _ = self.sender.send(event);
+    }
+
+    /// Verifies the installed firmware of every device which supports it, or records it as the
+    /// known-good baseline of those devices.
+    pub fn verify(&mut self, baseline: bool) {
+        let devices = self
+            .components
+            .verifiable
+            .iter()
+            .map(|(entity, device)| (entity, device.clone()))
+            .collect::<Vec<_>>();
+
+        if devices.is_empty() {
+            self.widgets.info_bar.set_visible(true);
+            self.widgets.info_bar_label.set_text(&fl!("verify-unsupported"));
+            return;
+        }
+
+        let _ = self.sender.send(if baseline {
+            FirmwareEvent::VerifyBaseline(devices)
+        } else {
+            FirmwareEvent::Verify(devices)
+        });
+    }
+
+    /// Shows the result of verifying the installed firmware of a device.
+    pub fn device_verified(&mut self, entity: Entity, integrity: Integrity) {
+        let widget = match self.components.device_widgets.get(entity) {
+            Some(widget) => widget,
+            None => return,
+        };
+
+        match integrity {
+            Integrity::Passed => widget.set_verification(&fl!("verify-passed"), Some(true)),
+            Integrity::Failed(why) => {
+                let message = [fl!("verify-failed"), ": ".into(), why.into()].concat();
+                widget.set_verification(&message, Some(false));
+            }
+            Integrity::NoBaseline => widget.set_verification(&fl!("verify-no-baseline"), None),
+        }
+    }
+
+    /// Writes an inventory of the firmware found by the last scan to a file.
+    pub fn export_report(&mut self, path: &Path, format: ReportFormat) {
+        info!("exporting firmware report to {}", path.display());
+        if let Err(why) = self.report.write(path, format) {
+            let message = [fl!("error-export-report"), ": ".into(), why.to_string()].concat();
+            error!("{}", message);
+            self.widgets.info_bar.set_visible(true);
+            self.widgets.info_bar_label.set_text(&message);
+        }
+    }
+
     /// An event that occurs when fwupd firmware is found.
     pub fn fwupd(&mut self, signal: FwupdSignal) {
         self.create_device(move |state, entity| {
-            let FwupdSignal { info, device, upgradeable, releases } = signal;
-            let widget = if device.needs_reboot() {
+            // Parents are found before their children, so children are listed beneath them.
+            let parent = signal
+                .parent
+                .as_deref()
+                .and_then(|parent| state.components.device_ids.get(parent))
+                .copied()
+                .filter(|&parent| state.components.device_widgets.contains_key(parent));
+
+            let widget = if signal.device.needs_reboot() {
                 state.entities.associate_system(entity);
-                state.widgets.view_devices.system(&info)
+                state.widgets.view_devices.system(&signal.info)
+            } else if let Some(parent) = parent {
+                state.components.parents.insert(entity, parent);
+                let parent = &state.components.device_widgets[parent];
+                state.widgets.view_devices.child(parent, &signal.info)
             } else {
-                state.widgets.view_devices.device(&info)
+                state.widgets.view_devices.device(&signal.info)
             };
 
+            let device_id: &str = signal.device.device_id.as_ref();
+            state.components.device_ids.insert(device_id.into(), entity);
+
             widget.stack.hide();
+            state.components.current.insert(entity, signal.info.current.clone());
+
+            // Explain why the device cannot be updated right now, if it cannot.
+            let mut problems = signal.blocking_problems().map(problem_text).collect::<Vec<_>>();
+            if signal.is_locked() {
+                problems.insert(0, fl!("device-locked"));
+            } else if signal.needs_activation() {
+                problems.insert(0, fl!("device-needs-activation"));
+            }
+
+            if !problems.is_empty() {
+                widget.set_problem(&problems.join(" "));
+            }
+
+            // Locked devices and staged updates must be acted upon before anything else.
+            let needs_action = signal.is_locked() || signal.needs_activation();
+            if needs_action {
+                let (label, event): (_, fn(Entity, Arc<FwupdDevice>) -> FirmwareEvent) =
+                    if signal.is_locked() {
+                        (fl!("button-unlock"), FirmwareEvent::Unlock)
+                    } else {
+                        (fl!("button-activate"), FirmwareEvent::Activate)
+                    };
+
+                widget.stack.button.set_label(&label);
+                widget.stack.show();
+
+                let device = Arc::new(signal.device.clone());
+                let sender = state.ui_sender.clone();
+                widget.connect_upgrade_clicked(move || {
+                    let event = event(entity, device.clone());
+                    let _ = sender.send(Event::Ui(UiEvent::Action(entity, event)));
+                });
+            }
+
+            if signal.can_verify() {
+                state.components.verifiable.insert(entity, Arc::new(signal.device.clone()));
+            }
+
+            let can_update = signal.can_update() && !needs_action;
+            let FwupdSignal { info, device, upgradeable, releases, .. } = signal;
+
+            let sender = state.ui_sender.clone();
+            widget.connect_update_all_clicked(move || {
+                let _ = sender.send(Event::Ui(UiEvent::UpdateComposite(entity)));
+            });
 
             if let Some(latest) = info.latest {
                 state.components.latest.insert(entity, latest);
+                state.components.releases.insert(entity, info.releases);
                 state.components.fwupd.insert(entity, (device, releases));
-                if upgradeable {
+                if can_update {
+                    state.components.ready.insert(entity, ());
                     let sender = state.ui_sender.clone();
                     widget.stack.show();
                     widget.connect_upgrade_clicked(move || {
                         let _ = sender.send(Event::Ui(UiEvent::Update(entity)));
                     });
+                } else if upgradeable && !needs_action {
+                    // The update is available, but cannot be installed until the problems
+                    // which are explained in the row are resolved.
+                    widget.stack.show();
+                    widget.stack.button.set_sensitive(false);
                 }
             }
 
@@ -147,10 +379,87 @@ impl State {
                 let _ = sender.send(Event::Ui(UiEvent::Reveal(entity)));
             });
 
+            // A composite device may be updated as a whole once several of its devices can be.
+            if let Some(parent) = parent {
+                let root = state.root(parent);
+                if state.composite_updates(root).len() > 1 {
+                    state.components.device_widgets[root].update_all.show();
+                }
+            }
+
             widget
         });
     }
 
+    /// The device at the root of the composite device which the device belongs to.
+    fn root(&self, mut entity: Entity) -> Entity {
+        while let Some(&parent) = self.components.parents.get(entity) {
+            entity = parent;
+        }
+
+        entity
+    }
+
+    /// The devices of a composite device which can be updated right now.
+    fn composite_updates(&self, root: Entity) -> Vec<Entity> {
+        self.components.ready.keys().filter(|&entity| self.root(entity) == root).collect()
+    }
+
+    /// Marks a device as no longer ready to be updated, once its update has begun or succeeded.
+    ///
+    /// The composite device it belongs to can no longer be updated as a whole once fewer than two
+    /// of its devices are ready.
+    fn unready(&mut self, entity: Entity) {
+        if self.components.ready.remove(entity).is_none() {
+            return;
+        }
+
+        let root = self.root(entity);
+        if self.composite_updates(root).len() < 2 {
+            if let Some(widget) = self.components.device_widgets.get(root) {
+                widget.update_all.hide();
+            }
+        }
+    }
+
+    /// Updates every device of a composite device which can be updated, in the order that fwupd
+    /// requires, once the user confirms the update of every device.
+    pub fn update_composite(&mut self, root: Entity) {
+        let entities = self.composite_updates(root);
+
+        let dialog = FwupdDialog {
+            has_battery: self.has_battery,
+            sender: &self.sender,
+            updates: entities.iter().filter_map(|&entity| self.fwupd_update(entity)).collect(),
+        };
+
+        if dialog.run() {
+            for entity in entities {
+                self.components.ready.remove(entity);
+            }
+
+            self.components.device_widgets[root].update_all.hide();
+        }
+    }
+
+    /// The update of a fwupd device to its latest release, if it has one.
+    fn fwupd_update(&self, entity: Entity) -> Option<FwupdUpdate<'_>> {
+        let latest = self.components.latest.get(entity)?;
+        let (device, releases) = self.components.fwupd.get(entity)?;
+        let infos = self.components.releases.get(entity).map_or(&[][..], Vec::as_slice);
+        let current = self.components.current.get(entity).and_then(Option::as_ref);
+
+        Some(FwupdUpdate {
+            changelog: ReleaseRange::new(infos, current, latest),
+            device,
+            entity,
+            latest: latest.as_str(),
+            needs_reboot: self.entities.is_system(entity),
+            releases,
+            widgets: &self.components.device_widgets[entity],
+        })
+    }
+
     /// Activates progress bar handling for the given widget.
     pub fn progress_activate(&self, progress: &gtk::ProgressBar) {
         let event = ActivateEvent::Activate(progress.clone());
@@ -170,35 +479,17 @@ impl State {
         let revealer = &widget.revealer;
         let sender = &self.ui_sender;
 
-        if let Some((_, releases)) = self.components.fwupd.get(entity) {
-            reveal(revealer, sender, entity, move || {
-                let releases = &releases;
-                let log_entries = releases
-                    .iter()
-                    .rev()
-                    // TODO: Add release date
-                    .map(|release| (release.version.as_ref(), "", release.description.as_ref()));
-
-                crate::changelog::generate_widget(log_entries).upcast::<gtk::Container>()
-            });
-
-            return;
-        }
+        if let Some(releases) = self.components.releases.get(entity) {
+            let current = self.components.current[entity].as_ref();
+            if let Some(latest) = self.components.latest.get(entity).or(current) {
+                let range = ReleaseRange::new(releases, current, latest);
 
-        if let Some((_, changelog)) = self.components.system76.get(entity) {
-            reveal(revealer, &sender, entity, || {
-                let log_entries = changelog.versions.iter().map(|version| {
-                    (
-                        version.bios.as_ref(),
-                        version.date.as_ref(),
-                        version.description.as_ref(),
-                    )
+                reveal(revealer, sender, entity, move || {
+                    crate::changelog::generate_range_widget(range).upcast::<gtk::Container>()
                 });
 
-                crate::changelog::generate_widget(log_entries).upcast::<gtk::Container>()
-            });
-
-            return;
+                return;
+            }
         }
 
         // When changelog information is not available.
@@ -208,18 +499,16 @@ impl State {
     }
 
     /// An event that occurs when System76 system firmware has been found.
-    pub fn system76_system(
-        &mut self,
-        info: FirmwareInfo,
-        downloaded: Option<(System76Digest, System76Changelog)>,
-    ) {
+    pub fn system76_system(&mut self, info: FirmwareInfo, digest: Option<System76Digest>) {
         self.create_device(move |state, entity| {
             let widget = state.widgets.view_devices.system(&info);
             widget.stack.hide();
             state.entities.associate_system(entity);
+            state.components.current.insert(entity, info.current.clone());
 
+            let upgradeable = info.is_upgradeable();
             if let Some(latest) = info.latest {
-                if latest != info.current {
+                if upgradeable {
                     widget.stack.show();
                     let sender = state.ui_sender.clone();
                     widget.connect_upgrade_clicked(move || {
@@ -228,8 +517,9 @@ impl State {
                 }
 
                 state.components.latest.insert(entity, latest);
-                if let Some(data) = downloaded {
-                    state.components.system76.insert(entity, data);
+                if let Some(digest) = digest {
+                    state.components.releases.insert(entity, info.releases);
+                    state.components.system76.insert(entity, digest);
                 }
             }
 
@@ -243,21 +533,22 @@ impl State {
     }
 
     /// An event that occurs when a Thelio I/O board was discovered.
-    pub fn thelio_io(&mut self, info: FirmwareInfo, digest: Option<System76Digest>) {
+    pub fn thelio_io(&mut self, signal: ThelioIoSignal) {
         self.create_device(move |state, entity| {
+            let ThelioIoSignal { board, info, digest } = signal;
             let widget = state.widgets.view_devices.device(&info);
+            state.components.current.insert(entity, info.current.clone());
 
             let sender = state.ui_sender.clone();
-            let mut upgradeable = false;
+            let upgradeable = digest.is_some() && info.is_upgradeable();
 
             if let (Some(digest), Some(latest)) = (digest, info.latest) {
-                upgradeable = info.current.as_ref() != latest.as_ref();
                 widget.connect_upgrade_clicked(move || {
                     let _ = sender.send(Event::Ui(UiEvent::Update(entity)));
                 });
 
                 state.components.latest.insert(entity, latest);
-                state.components.thelio.insert(entity, digest);
+                state.components.thelio.insert(entity, (board, digest));
             }
 
             {
@@ -282,41 +573,54 @@ impl State {
     pub fn update(&mut self, entity: Entity) {
         if let Some(latest) = self.components.latest.get(entity) {
             let widgets = &self.components.device_widgets[entity];
+            let releases = self.components.releases.get(entity).map_or(&[][..], Vec::as_slice);
+            let current = self.components.current[entity].as_ref();
+            let changelog = ReleaseRange::new(releases, current, latest);
 
-            if let Some((device, releases)) = self.components.fwupd.get(entity) {
+            if let Some(update) = self.fwupd_update(entity) {
                 let dialog = FwupdDialog {
-                    device: &device,
-                    entity,
                     has_battery: self.has_battery,
-                    latest: &latest,
-                    needs_reboot: self.entities.is_system(entity),
-                    releases: &releases,
                     sender: &self.sender,
-                    widgets,
+                    updates: vec![update],
                 };
 
-                dialog.run();
+                if dialog.run() {
+                    self.unready(entity);
+                }
 
                 return;
             }
 
-            if let Some((digest, changelog)) = self.components.system76.get(entity) {
+            if let Some(digest) = self.components.system76.get(entity) {
                 let dialog = System76Dialog {
-                    changelog: &changelog,
+                    changelog,
                     digest: &digest,
                     entity,
                     has_battery: self.has_battery,
-                    latest: &latest,
+                    latest: latest.as_str(),
                     sender: &self.sender,
                     widgets,
                 };
 
                 dialog.run();
-            } else if let Some(digest) = self.components.thelio.get(entity) {
-                // Exchange the button for a progress bar.
-                widgets.stack.switch_to_waiting();
-                self.progress_activate(&widgets.stack.progress);
-                let _ = self.sender.send(FirmwareEvent::ThelioIo(entity, digest.clone()));
+            } else if let Some((_, digest)) = self.components.thelio.get(entity) {
+                // All boards are flashed together, so every board will be updated.
+                let boards = self
+                    .components
+                    .thelio
+                    .iter()
+                    .map(|(entity, (board, _))| (entity, board.clone()))
+                    .collect::<Vec<_>>();
+
+                for &(entity, _) in &boards {
+                    // Exchange the button for a progress bar.
+                    let widgets = &self.components.device_widgets[entity];
+                    widgets.stack.switch_to_waiting();
+                    self.progress_activate(&widgets.stack.progress);
+                }
+
+                let event = FirmwareEvent::ThelioIo(digest.clone(), latest.clone(), boards);
+                let _ = self.sender.send(event);
             }
         } else {
             error!("attempted to update firmware for a device which did not have updated firmware");
@@ -357,3 +661,30 @@ fn reveal<F: FnMut() -> gtk::Container>(
     let _ = sender.send(Event::Ui(UiEvent::Revealed(entity, reveal)));
     revealer.set_reveal_child(reveal);
 }
+
+/// Explains a problem which prevents a device from being updated.
+fn problem_text(problem: DeviceProblem) -> String {
+    match problem {
+        DeviceProblem::SystemPowerTooLow => fl!("problem-system-power-too-low"),
+        DeviceProblem::Unreachable => fl!("problem-unreachable"),
+        DeviceProblem::PowerTooLow => fl!("problem-power-too-low"),
+        DeviceProblem::UpdatePending => fl!("problem-update-pending"),
+        DeviceProblem::RequireAcPower => fl!("problem-require-ac-power"),
+        DeviceProblem::LidIsClosed => fl!("problem-lid-is-closed"),
+        DeviceProblem::IsEmulated => fl!("problem-is-emulated"),
+        DeviceProblem::MissingLicense => fl!("problem-missing-license"),
+        DeviceProblem::SystemInhibit => fl!("problem-system-inhibit"),
+        DeviceProblem::UpdateInProgress => fl!("problem-update-in-progress"),
+        DeviceProblem::InUse => fl!("problem-in-use"),
+        DeviceProblem::DisplayRequired => fl!("problem-display-required"),
+        DeviceProblem::LowerPriority => fl!("problem-lower-priority"),
+    }
+}
+
+/// Describes the operation of another process which holds the operation lock.
+fn operation_message(holder: Option<&LockHolder>) -> String {
+    match holder {
+        Some(holder) => fl!("operation-in-progress", holder = holder.to_string()),
+        None => fl!("operation-in-progress-unknown"),
+    }
+}
@@ -1,23 +1,28 @@
 use crate::{fl, traits::DynamicGtkResize, widgets::DeviceWidget};
 use firmware_manager::FirmwareInfo;
 use gtk::prelude::*;
-use std::num::NonZeroU8;
+use std::{cell::Cell, num::NonZeroU8};
 
 /// The devices view is displayed when devices are found.
 ///
 /// It consists of a collection of system firmware which requires a reboot to flash, and device
 /// firmware which does not. The `system()` and `device()` methods will create and add device
 /// widges to their appropriate list boxes, and will return the created device widget so that we
-/// can program its signals.
+/// can program its signals. Devices with security updates are listed before all other devices.
+/// The devices of a composite device are listed beneath their parent with `child()`.
 #[derive(Shrinkwrap)]
 pub struct DevicesView {
     #[shrinkwrap(main_field)]
     container: gtk::Container,
     device_firmware: gtk::ListBox,
     device_header: gtk::Label,
+    /// The number of devices with security updates at the top of the device section.
+    device_security: Cell<i32>,
     sg: gtk::SizeGroup,
     system_firmware: gtk::ListBox,
     system_header: gtk::Label,
+    /// The number of devices with security updates at the top of the system section.
+    system_security: Cell<i32>,
 }
 
 impl DevicesView {
@@ -125,8 +130,8 @@ impl DevicesView {
             ..add_widget(&device_firmware);
         };
 
-        device_firmware.set_header_func(Some(Box::new(separator_header)));
-        system_firmware.set_header_func(Some(Box::new(separator_header)));
+        device_firmware.set_header_func(Some(Box::new(super::separator_header)));
+        system_firmware.set_header_func(Some(Box::new(super::separator_header)));
 
         let container = cascade! {
             gtk::ScrolledWindow::new(None::<&gtk::Adjustment>, None::<&gtk::Adjustment>);
@@ -139,9 +144,11 @@ impl DevicesView {
             container: container.upcast(),
             device_firmware,
             device_header,
+            device_security: Cell::new(0),
             sg: gtk::SizeGroup::new(gtk::SizeGroupMode::Vertical),
             system_firmware,
             system_header,
+            system_security: Cell::new(0),
         }
     }
 
@@ -153,18 +160,29 @@ impl DevicesView {
         self.device_firmware.foreach(|x| unsafe {
             x.destroy();
         });
+
+        self.system_security.set(0);
+        self.device_security.set(0);
     }
 
     /// Creates and attaches a new device widget to the device section.
     pub fn device(&self, info: &FirmwareInfo) -> DeviceWidget {
         self.show_devices();
-        self.append(&self.device_firmware, info)
+        self.append(&self.device_firmware, &self.device_security, info)
+    }
+
+    /// Creates a new device widget, and lists it beneath the widget of its parent device.
+    pub fn child(&self, parent: &DeviceWidget, info: &FirmwareInfo) -> DeviceWidget {
+        let widget = DeviceWidget::new(info);
+        self.sg.add_widget(&widget.event_box);
+        parent.add_child(&widget);
+        widget
     }
 
     /// Creates and attaches a new device widget to the system section.
     pub fn system(&self, info: &FirmwareInfo) -> DeviceWidget {
         self.show_systems();
-        self.append(&self.system_firmware, info)
+        self.append(&self.system_firmware, &self.system_security, info)
     }
 
     /// Hides the device section so that it does not appear to the end user.
@@ -194,18 +212,23 @@ impl DevicesView {
     /// Convenience method shared by both the device and system methods.
     ///
     /// This is responsible for creating a device widget and assigning it to the given parent
-    /// container.
-    fn append(&self, parent: &impl gtk::traits::ContainerExt, info: &FirmwareInfo) -> DeviceWidget {
+    /// container. Security updates are inserted after the last security update in the list.
+    fn append(
+        &self,
+        parent: &gtk::ListBox,
+        security: &Cell<i32>,
+        info: &FirmwareInfo,
+    ) -> DeviceWidget {
         let widget = DeviceWidget::new(info);
         self.sg.add_widget(&widget.event_box);
-        parent.add(widget.as_ref());
-        widget
-    }
-}
 
-/// Inserts a separator as a header between rows in a list box.
-fn separator_header(current: &gtk::ListBoxRow, before: Option<&gtk::ListBoxRow>) {
-    if before.is_some() {
-        current.set_header(Some(&gtk::Separator::new(gtk::Orientation::Horizontal)));
+        if info.severity().map_or(false, |severity| severity.is_security()) {
+            parent.insert(widget.as_ref(), security.get());
+            security.set(security.get() + 1);
+        } else {
+            parent.add(widget.as_ref());
+        }
+
+        widget
     }
 }
@@ -7,22 +7,66 @@ pub struct EmptyView(gtk::Container);
 
 impl EmptyView {
     pub fn new() -> Self {
-        Self(error_view("firmware-manager-symbolic", &fl!("view-empty")))
+        Self(error_view("firmware-manager-symbolic", &fl!("view-empty"), None))
     }
 }
 
-/// View displayed to users who lack administrative permissions.
+/// View displayed to users who are not authorized to update firmware.
+///
+/// Users who may authenticate to update firmware are offered a button to unlock the view.
 #[derive(Shrinkwrap)]
-pub struct PermissionView(gtk::Container);
+pub struct PermissionView {
+    #[shrinkwrap(main_field)]
+    container: gtk::Container,
+    unlock: gtk::Button,
+}
 
 impl PermissionView {
-    pub fn new() -> Self {
-        Self(error_view("system-lock-screen-symbolic", &fl!("view-permission")))
+    pub fn new(can_unlock: bool) -> Self {
+        let unlock = cascade! {
+            gtk::Button::builder()
+                .label(&fl!("button-unlock"))
+                .halign(gtk::Align::Start)
+                .build();
+            ..style_context().add_class(&gtk::STYLE_CLASS_SUGGESTED_ACTION);
+        };
+
+        let container = if can_unlock {
+            error_view("system-lock-screen-symbolic", &fl!("view-permission-unlock"), Some(&unlock))
+        } else {
+            error_view("system-lock-screen-symbolic", &fl!("view-permission"), None)
+        };
+
+        Self { container, unlock }
+    }
+
+    /// Activates when the unlock button is clicked.
+    pub fn connect_unlock<F: Fn() + 'static>(&self, func: F) {
+        self.unlock.connect_clicked(move |_| func());
     }
 }
 
-/// Template for creating new error views.
-fn error_view(icon: &str, reason: &str) -> gtk::Container {
+/// Template for creating new error views, with an optional button to resolve the error.
+fn error_view(icon: &str, reason: &str, action: Option<&gtk::Button>) -> gtk::Container {
+    let label = cascade! {
+        gtk::Label::builder()
+            .label(reason)
+            .wrap(true)
+            .xalign(0.0)
+            .yalign(0.0)
+            .build();
+        ..style_context().add_class(&gtk::STYLE_CLASS_DIM_LABEL);
+    };
+
+    let column = cascade! {
+        gtk::Box::new(gtk::Orientation::Vertical, 12);
+        ..add(&label);
+    };
+
+    if let Some(action) = action {
+        column.add(action);
+    }
+
     let container = cascade! {
         gtk::Box::new(gtk::Orientation::Horizontal, 24);
         ..set_halign(gtk::Align::Center);
@@ -34,15 +78,7 @@ fn error_view(icon: &str, reason: &str) -> gtk::Container {
                 .pixel_size(64)
                 .build()
         );
-        ..add(&cascade! {
-            gtk::Label::builder()
-                .label(reason)
-                .wrap(true)
-                .xalign(0.0)
-                .yalign(0.0)
-                .build();
-            ..style_context().add_class(&gtk::STYLE_CLASS_DIM_LABEL);
-        });
+        ..add(&column);
         ..show_all();
     };
 
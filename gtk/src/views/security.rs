@@ -0,0 +1,236 @@
+use crate::{fl, traits::DynamicGtkResize};
+use firmware_manager::{HostSecurity, Remediation, SecurityAttribute, SecuritySnapshot};
+use gtk::prelude::*;
+use std::num::NonZeroU8;
+
+/// The security view shows the security attributes of the host which fwupd reported.
+///
+/// Each check is listed with whether it passed and how to fix it if it failed, and checks whose
+/// results changed since the last run of the application are listed above them.
+#[derive(Shrinkwrap)]
+pub struct SecurityView {
+    #[shrinkwrap(main_field)]
+    container: gtk::Container,
+    attributes: gtk::ListBox,
+    changes: gtk::ListBox,
+    changes_header: gtk::Label,
+    hsi: gtk::Label,
+    hsi_details: gtk::Label,
+    stack: gtk::Stack,
+}
+
+impl SecurityView {
+    pub fn new() -> Self {
+        let hsi = cascade! {
+            gtk::Label::new(None);
+            ..set_use_markup(true);
+            ..set_xalign(0.0);
+        };
+
+        let hsi_details = cascade! {
+            gtk::Label::new(None);
+            ..set_line_wrap(true);
+            ..set_no_show_all(true);
+            ..set_xalign(0.0);
+            ..style_context().add_class(&gtk::STYLE_CLASS_DIM_LABEL);
+        };
+
+        let changes_text = format!("<b>{}</b>", fl!("security-changes"));
+        let changes_header = cascade! {
+            gtk::Label::new(Some(&changes_text));
+            ..set_no_show_all(true);
+            ..set_use_markup(true);
+            ..set_xalign(0.0);
+        };
+
+        let changes = cascade! {
+            gtk::ListBox::new();
+            ..set_no_show_all(true);
+            ..set_margin_bottom(12);
+            ..set_selection_mode(gtk::SelectionMode::None);
+        };
+
+        let attributes_text = format!("<b>{}</b>", fl!("security-attributes"));
+        let attributes_header = cascade! {
+            gtk::Label::new(Some(&attributes_text));
+            ..set_use_markup(true);
+            ..set_xalign(0.0);
+        };
+
+        let attributes = cascade! {
+            gtk::ListBox::new();
+            ..set_selection_mode(gtk::SelectionMode::None);
+        };
+
+        changes.set_header_func(Some(Box::new(super::separator_header)));
+        attributes.set_header_func(Some(Box::new(super::separator_header)));
+
+        cascade! {
+            gtk::SizeGroup::new(gtk::SizeGroupMode::Horizontal);
+            ..add_widget(&changes);
+            ..add_widget(&attributes);
+        };
+
+        let unavailable = cascade! {
+            gtk::Label::new(Some(&fl!("security-unavailable")));
+            ..set_line_wrap(true);
+            ..set_xalign(0.0);
+            ..style_context().add_class(&gtk::STYLE_CLASS_DIM_LABEL);
+        };
+
+        let layout: gtk::Box = cascade! {
+            gtk::Box::new(gtk::Orientation::Vertical, 12);
+            ..set_halign(gtk::Align::Center);
+            ..set_margin_top(24);
+            ..set_margin_bottom(24);
+            ..add(&hsi);
+            ..add(&hsi_details);
+            ..add(&changes_header);
+            ..add(&changes);
+            ..add(&attributes_header);
+            ..add(&attributes);
+        };
+
+        let stack = cascade! {
+            gtk::Stack::new();
+            ..add_named(&unavailable, "unavailable");
+            ..add_named(&layout, "security");
+            ..set_visible_child_name("unavailable");
+        };
+
+        let container = cascade! {
+            gtk::ScrolledWindow::new(None::<&gtk::Adjustment>, None::<&gtk::Adjustment>);
+            ..add(&stack);
+            ..show_all();
+            ..dynamic_resize(layout, NonZeroU8::new(66), None);
+        };
+
+        Self {
+            container: container.upcast(),
+            attributes,
+            changes,
+            changes_header,
+            hsi,
+            hsi_details,
+            stack,
+        }
+    }
+
+    /// Displays the security of the host, and what changed since the previous run.
+    pub fn update(&self, security: &HostSecurity, previous: Option<&SecuritySnapshot>) {
+        self.clear();
+
+        let hsi = match security.hsi {
+            Some(level) => fl!("security-hsi", level = level),
+            None => fl!("security-hsi-unknown"),
+        };
+
+        self.hsi.set_markup(&format!("<span size='x-large'><b>{}</b></span>", hsi));
+
+        let mut details = Vec::new();
+
+        if security.runtime_issues {
+            details.push(fl!("security-hsi-runtime"));
+        }
+
+        if let Some(level) = previous.and_then(|previous| previous.hsi) {
+            if security.hsi != Some(level) {
+                details.push(fl!("security-hsi-previous", level = level));
+            }
+        }
+
+        self.hsi_details.set_text(&details.join(" "));
+        self.hsi_details.set_visible(!details.is_empty());
+
+        let changes = previous.map_or_else(Vec::new, |previous| security.changes(previous));
+        for change in &changes {
+            let status = match change.previous {
+                None => fl!("security-change-new"),
+                Some(_) if change.passed => fl!("security-change-pass"),
+                Some(_) => fl!("security-change-fail"),
+            };
+
+            self.changes.add(&row(&change.name, &status, change.passed, None));
+        }
+
+        self.changes.set_visible(!changes.is_empty());
+        self.changes_header.set_visible(!changes.is_empty());
+
+        for attribute in &security.attributes {
+            let status = if attribute.passed { fl!("security-pass") } else { fl!("security-fail") };
+            let details = details(attribute);
+            let row = row(&attribute.name, &status, attribute.passed, details.as_deref());
+            self.attributes.add(&row);
+        }
+
+        self.changes.show_all();
+        self.attributes.show_all();
+
+        self.stack.set_visible_child_name("security");
+    }
+
+    /// Removes all security checks from the view.
+    fn clear(&self) {
+        self.changes.foreach(|x| unsafe {
+            x.destroy();
+        });
+        self.attributes.foreach(|x| unsafe {
+            x.destroy();
+        });
+    }
+}
+
+/// The remediation and description of a failed check, or the description of a passed check.
+fn details(attribute: &SecurityAttribute) -> Option<String> {
+    let remediation = attribute.remediation.map(|remediation| match remediation {
+        Remediation::ContactOem => fl!("security-remediation-contact-oem"),
+        Remediation::ConfigureFirmware => fl!("security-remediation-config-fw"),
+        Remediation::ConfigureOs => fl!("security-remediation-config-os"),
+    });
+
+    let lines = [remediation.as_deref(), attribute.description.as_deref()];
+    let lines = lines.iter().flatten().copied().collect::<Vec<_>>();
+
+    if lines.is_empty() {
+        None
+    } else {
+        Some(lines.join("\n"))
+    }
+}
+
+fn row(name: &str, status: &str, passed: bool, details: Option<&str>) -> gtk::Box {
+    let status = cascade! {
+        gtk::Label::new(Some(status));
+        ..set_halign(gtk::Align::End);
+        ..set_hexpand(true);
+        ..style_context().add_class(if passed { "success" } else { &gtk::STYLE_CLASS_ERROR });
+    };
+
+    let header = cascade! {
+        gtk::Box::new(gtk::Orientation::Horizontal, 12);
+        ..add(&gtk::Label::builder().label(name).xalign(0.0).build());
+        ..add(&status);
+    };
+
+    let container = cascade! {
+        gtk::Box::new(gtk::Orientation::Vertical, 4);
+        ..set_margin_start(12);
+        ..set_margin_end(12);
+        ..set_margin_top(8);
+        ..set_margin_bottom(8);
+        ..add(&header);
+    };
+
+    if let Some(details) = details {
+        container.add(&cascade! {
+            gtk::Label::builder()
+                .label(details)
+                .wrap(true)
+                .xalign(0.0)
+                .build();
+            ..style_context().add_class(&gtk::STYLE_CLASS_DIM_LABEL);
+        });
+    }
+
+    container
+}
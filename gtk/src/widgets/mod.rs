@@ -1,5 +1,5 @@
 use crate::fl;
-use firmware_manager::FirmwareInfo;
+use firmware_manager::{FirmwareInfo, Severity};
 use gtk::prelude::*;
 
 #[derive(Shrinkwrap)]
@@ -35,6 +35,14 @@ pub struct DeviceWidget {
     pub event_box: gtk::EventBox,
     pub revealer: gtk::Revealer,
     pub label: gtk::Label,
+    /// Explains why the device cannot be updated right now.
+    pub problem: gtk::Label,
+    /// The result of verifying the firmware installed on the device.
+    pub verification: gtk::Label,
+    /// The devices of a composite device are listed beneath it.
+    pub children: gtk::Box,
+    /// Updates every device of a composite device.
+    pub update_all: gtk::Button,
     pub stack: DeviceWidgetStack,
 }
 
@@ -47,15 +55,59 @@ impl DeviceWidget {
             .valign(gtk::Align::End)
             .build();
 
+        // Security updates are badged so that they stand out from other updates.
+        let badge = info.severity().filter(|severity| severity.is_security()).map(|severity| {
+            let text = if severity == Severity::Critical {
+                fl!("badge-security-critical")
+            } else {
+                fl!("badge-security")
+            };
+
+            cascade! {
+                gtk::Label::builder().label(&text).xalign(0.0).valign(gtk::Align::End).build();
+                ..style_context().add_class(if severity == Severity::Critical {
+                    &gtk::STYLE_CLASS_ERROR
+                } else {
+                    &gtk::STYLE_CLASS_WARNING
+                });
+            }
+        });
+
+        let name = cascade! {
+            gtk::Box::new(gtk::Orientation::Horizontal, 6);
+            ..add(&device);
+        };
+
+        if let Some(ref badge) = badge {
+            name.add(badge);
+        }
+
         let label = cascade! {
             gtk::Label::builder()
-                .label(info.current.as_ref())
+                .label(info.current_label())
                 .xalign(0.0)
                 .valign(gtk::Align::Start)
                 .build();
             ..style_context().add_class(&gtk::STYLE_CLASS_DIM_LABEL);
         };
 
+        let problem = cascade! {
+            gtk::Label::builder()
+                .xalign(0.0)
+                .valign(gtk::Align::Start)
+                .wrap(true)
+                .no_show_all(true)
+                .build();
+            ..style_context().add_class(&gtk::STYLE_CLASS_WARNING);
+        };
+
+        let verification = gtk::Label::builder()
+            .xalign(0.0)
+            .valign(gtk::Align::Start)
+            .wrap(true)
+            .no_show_all(true)
+            .build();
+
         let button = cascade! {
             gtk::Button::builder()
                 .label(&fl!("button-update"))
@@ -122,27 +174,50 @@ impl DeviceWidget {
                     .row_spacing(3)
                     .build();
                 ..attach(&dropdown_image, 0, 0, 1, 2);
-                ..attach(&device, 1, 0, 1, 1);
+                ..attach(&name, 1, 0, 1, 1);
                 ..attach(&label, 1, 1, 1, 1);
+                ..attach(&problem, 1, 2, 1, 1);
+                ..attach(&verification, 1, 3, 1, 1);
                 ..attach(&stack, 2, 0, 1, 2);
             });
         };
 
+        let children = cascade! {
+            gtk::Box::new(gtk::Orientation::Vertical, 0);
+            ..set_margin_start(24);
+            ..set_no_show_all(true);
+        };
+
+        let update_all = cascade! {
+            gtk::Button::builder()
+                .label(&fl!("button-update-all"))
+                .halign(gtk::Align::End)
+                .no_show_all(true)
+                .build();
+            ..style_context().add_class(&gtk::STYLE_CLASS_SUGGESTED_ACTION);
+        };
+
         let container = cascade! {
             gtk::Box::new(gtk::Orientation::Vertical, 6);
             ..set_border_width(12);
             ..set_can_focus(false);
             ..add(&event_box);
             ..add(&revealer);
+            ..add(&children);
+            ..add(&update_all);
             ..show_all();
         };
 
         DeviceWidget {
+            children,
             container: container.upcast::<gtk::Container>(),
             event_box,
             label,
+            problem,
             revealer,
+            verification,
             stack: DeviceWidgetStack { button, stack, progress, waiting },
+            update_all,
         }
     }
 
@@ -155,6 +230,40 @@ impl DeviceWidget {
         });
     }
 
+    /// Lists a device of this composite device beneath it.
+    pub fn add_child(&self, child: &DeviceWidget) {
+        self.children.add(child.as_ref());
+        self.children.show();
+    }
+
+    /// Activates when the button which updates every device of the composite device is clicked.
+    pub fn connect_update_all_clicked<F: Fn() + 'static>(&self, func: F) {
+        self.update_all.connect_clicked(move |_| func());
+    }
+
+    /// Explains why the device cannot be updated right now.
+    pub fn set_problem(&self, problem: &str) {
+        self.problem.set_text(problem);
+        self.problem.show();
+    }
+
+    /// Shows the result of verifying the installed firmware, which is highlighted once it is
+    /// known whether the verification passed.
+    pub fn set_verification(&self, message: &str, passed: Option<bool>) {
+        let context = self.verification.style_context();
+        context.remove_class("success");
+        context.remove_class(&gtk::STYLE_CLASS_ERROR);
+
+        match passed {
+            Some(true) => context.add_class("success"),
+            Some(false) => context.add_class(&gtk::STYLE_CLASS_ERROR),
+            None => (),
+        }
+
+        self.verification.set_text(message);
+        self.verification.show();
+    }
+
     /// Activates when the widget's container's button is clicked.
     pub fn connect_upgrade_clicked<F: Fn() + 'static>(&self, func: F) {
         self.stack.button.connect_clicked(move |_| func());
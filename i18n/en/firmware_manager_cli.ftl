@@ -0,0 +1,52 @@
+column-current = CURRENT
+column-date = DATE
+column-device = DEVICE
+column-id = ID
+column-latest = LATEST
+column-outcome = OUTCOME
+column-previous = PREVIOUS
+column-status = STATUS
+column-version = VERSION
+
+status-blocked = Blocked
+status-up-to-date = Up to date
+status-update-available = Update available
+
+metadata-age = {$source} metadata was refreshed {$days ->
+    [0] today
+    [one] {$days} day ago
+   *[other] {$days} days ago
+}
+refreshed = The metadata of the fwupd remotes was refreshed.
+
+history-empty = No firmware updates have been recorded.
+no-updates = No firmware updates are available.
+up-to-date = {$device} is up to date.
+
+confirm-update = Install {$count ->
+    [one] this update
+   *[other] these {$count} updates
+}?
+
+progress-downloading = Downloading firmware for {$device}
+progress-flashing = Flashing {$device}
+progress-verifying = Verifying firmware for {$device}
+
+warning-unverified = {$device}: the release has no checksum to verify the firmware against; its checksum is {$checksum}
+
+outcome-failed = {$device}: failed: {$reason}
+outcome-scheduled = {$device}: {$version} will be installed on the next reboot
+outcome-updated = {$device}: updated to {$version}
+
+error-ambiguous = More than one device is named {$device}; select it by its ID instead
+error-blocked = {$device} cannot be updated until it is unlocked, or its problems are resolved
+error-confirm-noninteractive = Confirmation is required, but there is no terminal to ask for it; pass --yes to update
+error-fwupd-unavailable = The fwupd daemon is not available
+error-history = Failed to read the update history
+error-interrupted = The firmware manager stopped before the update completed
+error-json = Failed to write JSON
+error-not-authorized = Not authorized to manage firmware
+error-not-found = No device matches {$device}
+error-offline = Remotes are not refreshed while offline mode is enabled
+error-refresh = Failed to refresh fwupd remotes
+error-report-write = Failed to write report
@@ -1,14 +1,87 @@
 action-downloading = Downloading
 action-scheduling = Scheduling
 action-flashing = Flashing
+action-verifying = Verifying
 action-waiting = Waiting
+action-export-report = Export report…
+action-verify = Verify firmware
+action-verify-baseline = Record known-good firmware
 
+export-report-title = Export Firmware Report
+error-export-report = Failed to export firmware report
+
+badge-security = Security update
+badge-security-critical = Critical security update
+
+button-activate = Activate
 button-cancel = Cancel
+button-ok = OK
 button-reboot-and-install = Reboot and Install
+button-unlock = Unlock
 button-update = Update
+button-update-all = Update All
+
+device-locked = This device is locked, and must be unlocked before it can be updated.
+device-needs-activation = An update has been installed, and must be activated to take effect.
+
+problem-system-power-too-low = The system battery is too low to update.
+problem-unreachable = The device is unreachable.
+problem-power-too-low = The device battery is too low to update.
+problem-update-pending = An update is waiting to be installed.
+problem-require-ac-power = The system must be connected to AC power.
+problem-lid-is-closed = The lid of the system must be opened.
+problem-is-emulated = The device is emulated.
+problem-missing-license = The firmware license is missing.
+problem-system-inhibit = Updates are inhibited by another application.
+problem-update-in-progress = An update is already in progress.
+problem-in-use = The device is in use.
+problem-display-required = A display must be connected.
+problem-lower-priority = The device is managed by another plugin.
 
 changelog = Changelog
 changelog-unavailable = No changelog available
+changelog-whats-new = What's new in this update
+changelog-earlier = Earlier releases
+
+release-date = Released
+release-size = Download size
+release-urgency = Urgency
+release-urgency-summary = {$urgency} urgency
+release-issues = Fixes
+release-issues-summary = Fixes {$issues}
+release-vendor = Vendor
+release-license = License
+release-checksum = Checksum
+
+metadata-offline = Working offline. Firmware information from {$source} was last updated {$age}.
+metadata-age = { $days ->
+    [0] today
+    [1] yesterday
+    *[other] {$days} days ago
+}
+metadata-age-unknown = at an unknown time
+
+operation-in-progress = Another firmware operation is in progress: {$holder}
+operation-in-progress-unknown = Another firmware operation is in progress.
+
+page-devices = Devices
+page-security = Security
+
+security-hsi = Host Security ID: HSI:{$level}
+security-hsi-unknown = Host Security ID: Unknown
+security-hsi-runtime = Some runtime security checks failed.
+security-hsi-previous = This was HSI:{$level} when the firmware manager was last run.
+security-changes = Changes Since Last Run
+security-attributes = Security Checks
+security-pass = Pass
+security-fail = Fail
+security-change-pass = Now passes
+security-change-fail = Now fails
+security-change-new = Newly checked
+security-remediation-contact-oem = Contact the manufacturer of this system for a firmware update which fixes this.
+security-remediation-config-fw = This can be fixed by changing a setting in the firmware setup.
+security-remediation-config-os = This can be fixed by changing the configuration of the operating system.
+security-unavailable = Security information is unavailable, as fwupd did not report the security attributes of this system.
 
 header-system-firmware = System Firmware
 header-device-firmware = Device Firmware
@@ -16,6 +89,11 @@ header-firmware-update = Firmware Update
 
 update-available = Firmware version {$version} is available.
 
+urgency-low = Low
+urgency-medium = Medium
+urgency-high = High
+urgency-critical = Critical
+
 update-connect-to-ac = Connect your computer to power. <b>USB Type-C</b> charging is not supported for firmware updates.
 
 update-guide = After the firmware update is complete, it may be necessary to press the power button more than once. See {$url_tag_start}this support article{$url_tag_end} for more information.
@@ -27,3 +105,24 @@ view-empty = Managed Firmware Unavailable
 view-permission = Permission Required
 
  Only administrator accounts may update firmware.
+
+view-permission-unlock = Permission Required
+
+ Authenticate as an administrator to update firmware.
+
+verify-passed = Firmware verified
+verify-failed = Firmware does not match its known-good checksums
+verify-no-baseline = No known-good checksums to verify against
+verify-baseline-recorded = Known-good firmware recorded
+verify-unsupported = None of the devices support firmware verification.
+
+request-immediate = Action Required
+request-post = Action Required After Update
+request-generic = The device requires your attention to complete the update.
+request-do-not-power-off = Do not turn off your computer or remove the device until the update has completed.
+request-insert-usb-cable = Insert the USB cable of the device.
+request-press-unlock = Press the unlock button on the device.
+request-remove-replug = Unplug the device, and then plug it back in.
+request-remove-usb-cable = Remove the USB cable of the device.
+request-replug-install = Unplug the device, and then plug it back in to install the update.
+request-replug-power = Unplug the power cable of the device, and then plug it back in.
@@ -1,4 +1,12 @@
 summary = Firmware updates are available.
 body = Click here to install them.
 
-error-fwupd = Failed to update fwupd remotes
\ No newline at end of file
+summary-security = Security updates for firmware are available.
+body-security = These updates fix security vulnerabilities. Click here to install them.
+
+error-fwupd = Failed to update fwupd remotes
+error-argument = Unknown argument: {$argument}
+error-report-path = A path is required for --report, or - to write to stdout
+error-report-format = The report format must be either json or csv
+error-report-write = Failed to write report
+error-service = Failed to check for updates with the firmware manager service
@@ -0,0 +1,55 @@
+title = Firmware Manager
+help = ↑/↓ select · Enter changelog · u update · r refresh · q quit
+scanning = Scanning for firmware…
+
+header-system-firmware = System Firmware
+header-device-firmware = Device Firmware
+header-firmware-update = Firmware Update
+
+button-action = Unlock or activate (u)
+button-update = Update (u)
+
+changelog-none = No changelog is available.
+
+device-locked = This device is locked, and must be unlocked before it can be updated.
+device-needs-activation = An update has been installed, and must be activated to take effect.
+
+problem-system-power-too-low = The system battery is too low to update.
+problem-unreachable = The device is unreachable.
+problem-power-too-low = The device battery is too low to update.
+problem-update-pending = An update is waiting to be installed.
+problem-require-ac-power = The system must be connected to AC power.
+problem-lid-is-closed = The lid of the system must be opened.
+problem-is-emulated = The device is emulated.
+problem-missing-license = The firmware license is missing.
+problem-system-inhibit = Updates are inhibited by another application.
+problem-update-in-progress = An update is already in progress.
+problem-in-use = The device is in use.
+problem-display-required = A display must be connected.
+problem-lower-priority = The device is managed by another plugin.
+
+progress-waiting = Waiting…
+progress-downloading = Downloading
+progress-verifying = Verifying…
+progress-flashing = Flashing…
+
+status-updated = Updated.
+
+prompt-reboot = The firmware of {$device} will be installed when the system reboots. The system will be rebooted now.
+prompt-confirm = Enter to reboot and install · Esc to cancel
+prompt-dismiss = Enter or Esc to dismiss
+
+request-immediate = Action Required
+request-post = Action Required After Update
+request-generic = The device requires your attention to complete the update.
+request-image = An illustration of this request is at {$path}
+request-do-not-power-off = Do not turn off your computer or remove the device until the update has completed.
+request-insert-usb-cable = Insert the USB cable of the device.
+request-press-unlock = Press the unlock button on the device.
+request-remove-replug = Unplug the device, and then plug it back in.
+request-remove-usb-cable = Remove the USB cable of the device.
+request-replug-install = Unplug the device, and then plug it back in to install the update.
+request-replug-power = Unplug the power cable of the device, and then plug it back in.
+
+error-flashing = Firmware is being flashed, and must not be interrupted.
+error-not-authorized = Not authorized to update firmware, so updates are disabled.
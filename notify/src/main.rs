@@ -1,28 +1,69 @@
 mod localize;
 
-use firmware_manager::{get_client, FirmwareSignal, FwupdError, FwupdSignal};
+use firmware_manager::{
+    check_authorization, format_error, fwupd_metadata_age, fwupd_scan, fwupd_updates, get_client,
+    s76_firmware_is_active, s76_scan, Action, Bus, Config, FirmwareSignal, FwupdClient, FwupdError,
+    FwupdSignal, LockError, LockOperation, OperationLock, Report, ReportFormat, ServiceClient,
+    ServiceSignal, Severity, System76Client, ThelioIoSignal,
+};
 use i18n_embed::DesktopLanguageRequester;
-use notify_rust::{Notification, Timeout};
+use notify_rust::{Notification, Timeout, Urgency};
 use std::{
-    path::Path,
+    error::Error,
+    io::Write,
+    path::{Path, PathBuf},
     process::{exit, Command},
+    sync::mpsc,
+    time::{Duration, Instant},
 };
 
+const REPORT_FAILED: i32 = 1;
 const UPDATES_FOUND: i32 = 3;
 
+/// How long to wait for the service to scan for updates.
+const SERVICE_SCAN_TIMEOUT: Duration = Duration::from_secs(300);
+
 const GNOME_CONTROL_CENTER: &str = "/usr/share/applications/gnome-firmware-panel.desktop";
 
-use firmware_manager::{
-    fwupd_scan, fwupd_updates, s76_firmware_is_active, s76_scan, FwupdClient, System76Client,
-};
+/// Where an inventory report should be written, if one was requested instead of a notification.
+struct ReportRequest {
+    /// The file to write to, or `None` for stdout.
+    path: Option<PathBuf>,
+    format: ReportFormat,
+}
 
 fn main() {
     translate();
 
-    if !firmware_manager::user_is_admin() {
+    let report = match report_argument() {
+        Ok(report) => report,
+        Err(why) => {
+            eprintln!("{}", why);
+            exit(REPORT_FAILED);
+        }
+    };
+
+    // Users who could authenticate to update firmware are notified of updates.
+    if report.is_none() && !check_authorization(Action::UpdateFirmware).is_possible() {
         return;
     }
 
+    let config = Config::load();
+
+    // The service, if it is installed, scans on behalf of every frontend.
+    if report.is_none() {
+        let service = [Bus::System, Bus::Session].into_iter().find_map(ServiceClient::connect);
+        if let Some(client) = service {
+            match service_severity(&client) {
+                Ok(severity) => {
+                    notify_severity(severity, &config);
+                    return;
+                }
+                Err(why) => eprintln!("{}: {}", fl!("error-service"), why),
+            }
+        }
+    }
+
     let s76 = get_client("system76", s76_firmware_is_active, System76Client::new);
 
     let fwupd = get_client::<_, _, FwupdError>(
@@ -35,42 +76,153 @@ fn main() {
         },
     );
 
-    let (tx, rx) = std::sync::mpsc::channel();
+    let (tx, rx) = mpsc::channel();
 
     if let Some(ref client) = s76 {
-        s76_scan(client, tx.clone());
+        s76_scan(client, &config, tx.clone());
     }
 
     if let Some(ref client) = fwupd {
-        if let Err(why) = fwupd_updates(client) {
-            eprintln!("{}: {}", fl!("error-fwupd"), why);
+        if !config.offline && check_authorization(Action::ModifyRemotes).is_authorized() {
+            // Remotes are left as they are while another process updates firmware.
+            match OperationLock::acquire(LockOperation::Refresh, None) {
+                Ok(_lock) => {
+                    if let Err(why) = fwupd_updates(client) {
+                        eprintln!("{}: {}", fl!("error-fwupd"), format_error(&why));
+                    }
+                }
+                Err(LockError::Busy(_)) => (),
+                Err(why) => eprintln!("{}: {}", fl!("error-fwupd"), format_error(&why)),
+            }
         }
 
-        fwupd_scan(client, tx);
+        let _res = tx.send(FirmwareSignal::Metadata(fwupd_metadata_age(config.offline)));
+        fwupd_scan(client, tx.clone());
     }
 
-    for message in rx {
-        match message {
-            FirmwareSignal::Fwupd(FwupdSignal { upgradeable, .. }) => {
-                if upgradeable {
-                    notify();
-                }
+    drop(tx);
+
+    if let Some(request) = report {
+        let mut report = Report::new();
+        rx.into_iter().for_each(|signal| report.add(&signal));
+        write_report(&report, request);
+        return;
+    }
+
+    // Find the most severe of all available updates.
+    let severity = rx
+        .into_iter()
+        .filter_map(|message| match message {
+            FirmwareSignal::Fwupd(FwupdSignal { info, .. })
+            | FirmwareSignal::S76System(info, ..)
+            | FirmwareSignal::ThelioIo(ThelioIoSignal { info, .. }) => info.severity(),
+            _ => None,
+        })
+        .max();
+
+    notify_severity(severity, &config);
+}
+
+/// Asks the service to scan for updates, and finds the most severe of those available.
+///
+/// Updates which the service is already installing are not notified of.
+fn service_severity(client: &ServiceClient) -> Result<Option<Severity>, Box<dyn Error>> {
+    if !client.operations()?.is_empty() {
+        return Ok(None);
+    }
+
+    let (tx, rx) = mpsc::channel();
+    client.subscribe(tx)?;
+
+    // Should the service already be scanning, this waits for that scan instead.
+    client.scan()?;
+
+    let deadline = Instant::now() + SERVICE_SCAN_TIMEOUT;
+    while !rx.try_iter().any(|signal| signal == ServiceSignal::ScanningComplete) {
+        if Instant::now() > deadline {
+            return Err("timed out waiting for the scan to complete".into());
+        }
+
+        client.process(Duration::from_secs(1))?;
+    }
+
+    Ok(client.devices()?.into_iter().filter_map(|device| device.severity).max())
+}
+
+fn notify_severity(severity: Option<Severity>, config: &Config) {
+    if let Some(severity) = severity {
+        if config.security_only_notifications && !severity.is_security() {
+            return;
+        }
+
+        notify(severity);
+    }
+}
+
+/// Parses `--report <path>` and `--format <json|csv>` from the command line.
+///
+/// A path of `-` writes the report to stdout. If a format is not given, it is determined from the
+/// extension of the path, defaulting to JSON.
+fn report_argument() -> Result<Option<ReportRequest>, String> {
+    let mut path = None;
+    let mut format = None;
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--report" => {
+                path = Some(args.next().ok_or_else(|| fl!("error-report-path"))?);
             }
-            FirmwareSignal::S76System(info, ..) | FirmwareSignal::ThelioIo(info, ..) => {
-                if info.latest.as_ref().map_or(false, |latest| latest.as_ref() != info.current.as_ref())
-                {
-                    notify();
-                }
+            "--format" => {
+                format = Some(match args.next().as_deref() {
+                    Some("json") => ReportFormat::Json,
+                    Some("csv") => ReportFormat::Csv,
+                    _ => return Err(fl!("error-report-format")),
+                });
             }
-            _ => (),
+            _ => return Err(fl!("error-argument", argument = arg)),
         }
     }
+
+    let path = match path {
+        Some(path) if path == "-" => None,
+        Some(path) => Some(PathBuf::from(path)),
+        None if format.is_some() => return Err(fl!("error-report-path")),
+        None => return Ok(None),
+    };
+
+    let format = format
+        .or_else(|| path.as_deref().and_then(ReportFormat::from_path))
+        .unwrap_or(ReportFormat::Json);
+
+    Ok(Some(ReportRequest { path, format }))
 }
 
-fn notify() {
-    Notification::new()
-        .summary(&fl!("summary"))
-        .body(&fl!("body"))
+fn write_report(report: &Report, request: ReportRequest) {
+    let result = match request.path {
+        Some(ref path) => report.write(path, request.format),
+        None => std::io::stdout().write_all(report.render(request.format).as_bytes()),
+    };
+
+    if let Err(why) = result {
+        eprintln!("{}: {}", fl!("error-report-write"), why);
+        exit(REPORT_FAILED);
+    }
+}
+
+fn notify(severity: Severity) {
+    let mut notification = Notification::new();
+
+    if severity.is_security() {
+        notification
+            .summary(&fl!("summary-security"))
+            .body(&fl!("body-security"))
+            .urgency(Urgency::Critical);
+    } else {
+        notification.summary(&fl!("summary")).body(&fl!("body"));
+    }
+
+    notification
         .icon("firmware-manager")
         .appname("firmware-manager")
         .action("default", "default")
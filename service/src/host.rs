@@ -0,0 +1,472 @@
+//! The state which the service keeps on behalf of its clients.
+
+use firmware_manager::*;
+use slotmap::SparseSecondaryMap;
+use std::{fmt, mem, sync::mpsc::Sender, sync::Arc};
+
+/// The identifier which selects System76 system firmware, as it has no device ID.
+const SYSTEM_ID: &str = "system76";
+
+/// Errors which are returned to the clients of the service.
+#[derive(Debug)]
+pub enum HostError {
+    /// An update is in progress.
+    Busy,
+    /// The device does not exist, or has no update which may be installed.
+    NotFound(String),
+}
+
+impl HostError {
+    /// The name of the error on the bus.
+    pub fn name(&self) -> &'static str {
+        match self {
+            HostError::Busy => ERROR_BUSY,
+            HostError::NotFound(_) => ERROR_NOT_FOUND,
+        }
+    }
+}
+
+impl fmt::Display for HostError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HostError::Busy => f.write_str("firmware is being updated"),
+            HostError::NotFound(device) => write!(f, "no update can be installed for {}", device),
+        }
+    }
+}
+
+/// An event of a client, which the service acts upon once the client is authorized.
+pub struct Dispatched {
+    /// The entities of the client, paired with the entities of the service.
+    pub entities: Vec<(EntityId, EntityId)>,
+    /// The events which are sent to the event loop of the core.
+    pub events: Vec<FirmwareEvent>,
+}
+
+/// The service-specific details of a device, which are needed to update it.
+enum Kind {
+    Fwupd { device: Arc<FwupdDevice>, release: Option<Arc<FwupdRelease>> },
+    System76(Option<System76Digest>),
+    ThelioIo { board: Box<str>, digest: Option<System76Digest>, latest: Option<Version> },
+}
+
+/// A device which was discovered by the last scan.
+struct Device {
+    entity: Entity,
+    description: ServiceDevice,
+    kind: Kind,
+}
+
+/// The devices and operations of the service, which every client shares.
+pub struct Host {
+    sender: Sender<FirmwareEvent>,
+    entities: Entities,
+    devices: Vec<Device>,
+
+    /// Operations which are in flight, and how far each has progressed.
+    operations: SparseSecondaryMap<Entity, Operation>,
+
+    /// The bytes of each download which were relayed to clients in the signals of the core.
+    relayed: SparseSecondaryMap<Entity, u64>,
+
+    /// Signals which are waiting to be emitted on the bus.
+    pending: Vec<ServiceSignal>,
+
+    scanning: bool,
+
+    /// A client asked for a scan while one was in progress, which it may have missed the start of.
+    rescan: bool,
+}
+
+impl Host {
+    pub fn new(sender: Sender<FirmwareEvent>) -> Self {
+        Self {
+            sender,
+            entities: Entities::default(),
+            devices: Vec::new(),
+            operations: SparseSecondaryMap::new(),
+            relayed: SparseSecondaryMap::new(),
+            pending: Vec::new(),
+            scanning: false,
+            rescan: false,
+        }
+    }
+
+    /// Scans for devices, unless a scan is already in progress.
+    ///
+    /// The devices of operations in flight must not be forgotten, so scans wait until they end.
+    pub fn scan(&mut self) -> Result<(), HostError> {
+        if !self.operations.is_empty() {
+            return Err(HostError::Busy);
+        }
+
+        if !self.scanning {
+            self.scanning = true;
+            let _res = self.sender.send(FirmwareEvent::Scan);
+        }
+
+        Ok(())
+    }
+
+    /// The devices which the last scan discovered.
+    pub fn devices(&self) -> Vec<DeviceArg> {
+        self.devices.iter().map(|device| device.description.to_arg()).collect()
+    }
+
+    /// The operations which are in flight.
+    pub fn operations(&self) -> Vec<OperationArg> {
+        self.operations.values().map(Operation::to_arg).collect()
+    }
+
+    /// Handles an event of the core on behalf of a client.
+    ///
+    /// The entities of the client are returned paired with those of the devices that they refer
+    /// to, along with the events which wait until the client is authorized.
+    pub fn dispatch(&mut self, event: EventData) -> Result<Dispatched, HostError> {
+        let mut entities = Vec::new();
+        for (client, id) in event.targets() {
+            let id = if id.is_empty() { SYSTEM_ID } else { id };
+            let device = self.devices.iter().find(|device| &*device.description.id == id);
+            let device = device.ok_or_else(|| HostError::NotFound(id.to_owned()))?;
+            entities.push((client, EntityId::from(device.entity)));
+        }
+
+        let ids = || event.targets().iter().map(|(_, id)| id.to_string()).collect::<Vec<_>>();
+
+        let events = match &event {
+            EventData::Scan => {
+                if self.scanning {
+                    self.rescan = true;
+                }
+
+                // Operations in flight scan again once they end.
+                if self.operations.is_empty() {
+                    self.scan()?;
+                }
+
+                Vec::new()
+            }
+            EventData::Stop => Vec::new(),
+            EventData::Fwupd { .. }
+            | EventData::FwupdComposite { .. }
+            | EventData::ThelioIo { .. } => self.prepare(&ids())?,
+            EventData::S76System { .. } => self.prepare(&[SYSTEM_ID.to_owned()])?,
+            EventData::Activate { .. } => {
+                let (entity, device) = self.fwupd_device(&entities[0])?;
+                vec![FirmwareEvent::Activate(entity, device)]
+            }
+            EventData::Unlock { .. } => {
+                let (entity, device) = self.fwupd_device(&entities[0])?;
+                vec![FirmwareEvent::Unlock(entity, device)]
+            }
+            EventData::Verify { .. } => {
+                let devices = entities.iter().map(|pair| self.fwupd_device(pair));
+                vec![FirmwareEvent::Verify(devices.collect::<Result<_, _>>()?)]
+            }
+            EventData::VerifyBaseline { .. } => {
+                let devices = entities.iter().map(|pair| self.fwupd_device(pair));
+                vec![FirmwareEvent::VerifyBaseline(devices.collect::<Result<_, _>>()?)]
+            }
+        };
+
+        Ok(Dispatched { entities, events })
+    }
+
+    /// Prepares the updates of the selected devices, which wait until they are authorized.
+    ///
+    /// fwupd devices are updated first, in the order that composite devices require, and system
+    /// firmware is scheduled last.
+    pub fn prepare(&mut self, selected: &[String]) -> Result<Vec<FirmwareEvent>, HostError> {
+        if self.scanning || !self.operations.is_empty() {
+            return Err(HostError::Busy);
+        }
+
+        let mut updates = Vec::new();
+        for id in selected {
+            match self.devices.iter().find(|device| &*device.description.id == id) {
+                Some(device) if device.description.can_update => updates.push(device),
+                _ => return Err(HostError::NotFound(id.clone())),
+            }
+        }
+
+        // Thelio I/O boards are flashed together, so every board with an update is updated.
+        if updates.iter().any(|device| matches!(device.kind, Kind::ThelioIo { .. })) {
+            for device in &self.devices {
+                let is_board = matches!(device.kind, Kind::ThelioIo { .. });
+                if is_board
+                    && device.description.can_update
+                    && !updates.iter().any(|other| other.entity == device.entity)
+                {
+                    updates.push(device);
+                }
+            }
+        }
+
+        let mut fwupd = Vec::new();
+        let mut boards = Vec::new();
+        let mut thelio = None;
+        let mut system = None;
+
+        for device in &updates {
+            match &device.kind {
+                Kind::Fwupd { device: fwupd_device, release: Some(release) } => {
+                    fwupd.push((device.entity, fwupd_device.clone(), release.clone()));
+                }
+                Kind::System76(Some(digest)) => system = Some((device.entity, digest.clone())),
+                Kind::ThelioIo { board, digest: Some(digest), latest: Some(latest) } => {
+                    boards.push((device.entity, board.clone()));
+                    if thelio.is_none() {
+                        thelio = Some((digest.clone(), latest.clone()));
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        // Only the devices of a composite device depend upon each other.
+        let mut events = fwupd_update_events(fwupd);
+
+        if let Some((digest, latest)) = thelio {
+            events.push(FirmwareEvent::ThelioIo(digest, latest, boards));
+        }
+
+        if let Some((entity, digest)) = system {
+            events.push(FirmwareEvent::S76System(entity, digest));
+        }
+
+        let waiting = updates
+            .iter()
+            .map(|device| {
+                let id = device.description.id.clone();
+                let operation =
+                    Operation { device: id, stage: Stage::Waiting, downloaded: 0, total: 0 };
+                (device.entity, operation)
+            })
+            .collect::<Vec<_>>();
+
+        for (entity, operation) in waiting {
+            self.operations.insert(entity, operation);
+            self.emit_progress(entity);
+        }
+
+        Ok(events)
+    }
+
+    /// Begins the prepared events if they were authorized, or else abandons them.
+    ///
+    /// The clients which dispatched the events are told of the devices that were not acted upon.
+    pub fn start(&mut self, dispatched: Dispatched, authorized: bool) {
+        if authorized {
+            for event in dispatched.events {
+                let _res = self.sender.send(event);
+            }
+
+            return;
+        }
+
+        let why = "not authorized to update firmware";
+
+        for (_, entity) in dispatched.entities {
+            self.pending.push(ServiceSignal::Core(Box::new(SignalData::Error {
+                entity: Some(entity),
+                message: why.into(),
+            })));
+        }
+
+        let abandoned = mem::take(&mut self.operations);
+        for (_, operation) in abandoned {
+            self.pending.push(ServiceSignal::Error(Some(operation.device), why.into()));
+        }
+    }
+
+    /// Updates the state of the service from a signal of the event loop.
+    pub fn signal(&mut self, signal: FirmwareSignal) {
+        // The progress of downloads is relayed with the summaries of operations below.
+        if !matches!(signal, FirmwareSignal::DownloadUpdate(..)) {
+            self.pending.push(ServiceSignal::Core(Box::new(SignalData::from(&signal))));
+        }
+
+        match signal {
+            FirmwareSignal::Scanning => {
+                self.scanning = true;
+                self.entities.clear();
+                self.devices.clear();
+                self.pending.push(ServiceSignal::Scanning);
+            }
+            FirmwareSignal::ScanningComplete => {
+                self.scanning = false;
+                self.pending.push(ServiceSignal::ScanningComplete);
+
+                if mem::take(&mut self.rescan) {
+                    let _res = self.scan();
+                }
+            }
+            FirmwareSignal::Fwupd(signal) => {
+                let blocked = signal.is_locked() || signal.blocking_problems().next().is_some();
+                let release = signal.releases.last().cloned().map(Arc::new);
+                let can_update = signal.upgradeable && release.is_some() && !blocked;
+                let needs_reboot = signal.device.needs_reboot();
+
+                let FwupdSignal { info, device, .. } = signal;
+                let id = Box::from(device.device_id.as_ref());
+                let kind = Kind::Fwupd { device: Arc::new(device), release };
+                self.add(id, MetadataSource::Fwupd, info, can_update, needs_reboot, kind);
+            }
+            FirmwareSignal::S76System(info, digest) => {
+                let can_update = info.is_upgradeable() && digest.is_some();
+                let kind = Kind::System76(digest);
+                self.add(SYSTEM_ID.into(), MetadataSource::System76, info, can_update, true, kind);
+            }
+            FirmwareSignal::ThelioIo(ThelioIoSignal { board, info, digest }) => {
+                let can_update = info.is_upgradeable() && digest.is_some();
+                let latest = info.latest.clone();
+                let kind = Kind::ThelioIo { board: board.clone(), digest, latest };
+                self.add(board, MetadataSource::ThelioIo, info, can_update, false, kind);
+            }
+            FirmwareSignal::DownloadBegin(entity, total) => {
+                self.stage(entity, Stage::Downloading);
+                if let Some(operation) = self.operations.get_mut(entity) {
+                    operation.total = total;
+                }
+                self.relayed.insert(entity, 0);
+                self.emit_progress(entity);
+            }
+            FirmwareSignal::DownloadUpdate(entity, downloaded) => {
+                let Some(operation) = self.operations.get_mut(entity) else {
+                    return;
+                };
+
+                // Progress is only emitted when the percentage changes.
+                let percent = |downloaded: u64| downloaded * 100 / operation.total.max(1);
+                let before = percent(operation.downloaded);
+                operation.downloaded += downloaded as u64;
+                if percent(operation.downloaded) != before {
+                    let total = operation.downloaded;
+                    let relayed = self.relayed.insert(entity, total).unwrap_or(0);
+                    self.pending.push(ServiceSignal::Core(Box::new(SignalData::DownloadUpdate {
+                        entity: entity.into(),
+                        downloaded: total - relayed,
+                    })));
+
+                    self.emit_progress(entity);
+                }
+            }
+            FirmwareSignal::Verifying(entity) => {
+                self.stage(entity, Stage::Verifying);
+                self.emit_progress(entity);
+            }
+            FirmwareSignal::DeviceFlashing(entity) => {
+                self.stage(entity, Stage::Flashing);
+                self.emit_progress(entity);
+            }
+            FirmwareSignal::DeviceUpdated(entity) => {
+                if let Some(operation) = self.operations.remove(entity) {
+                    self.pending.push(ServiceSignal::DeviceUpdated(operation.device));
+                    self.finished();
+                }
+            }
+            FirmwareSignal::SystemScheduled => {
+                let system = self.entities.system.keys().collect::<Vec<_>>();
+                for entity in system {
+                    self.operations.remove(entity);
+                }
+
+                self.pending.push(ServiceSignal::SystemScheduled);
+                self.finished();
+            }
+            FirmwareSignal::Error(entity, why) => {
+                let operation = entity.and_then(|entity| self.operations.remove(entity));
+                let ended = operation.is_some();
+                let device = operation.map(|operation| operation.device);
+                self.pending.push(ServiceSignal::Error(device, format_error(&why)));
+
+                if ended {
+                    self.finished();
+                }
+            }
+            FirmwareSignal::DeviceRequest(request) => {
+                let device = request.entity.and_then(|entity| self.device(entity)).map_or_else(
+                    || request.device_id.clone(),
+                    |device| device.description.id.clone(),
+                );
+
+                self.pending.push(ServiceSignal::Request(ServiceRequest {
+                    device,
+                    kind: request.kind,
+                    id: request.id,
+                    message: request.message,
+                    image: request.image,
+                }));
+            }
+            _ => (),
+        }
+    }
+
+    /// Takes the signals which are waiting to be emitted.
+    pub fn take_signals(&mut self) -> Vec<ServiceSignal> { mem::take(&mut self.pending) }
+
+    fn add(
+        &mut self,
+        id: Box<str>,
+        source: MetadataSource,
+        info: FirmwareInfo,
+        can_update: bool,
+        needs_reboot: bool,
+        kind: Kind,
+    ) {
+        let entity = self.entities.create();
+        if let Kind::System76(_) = kind {
+            self.entities.associate_system(entity);
+        }
+
+        let description = ServiceDevice {
+            id,
+            name: info.name.clone(),
+            source,
+            current: info.current.as_ref().map(|current| current.as_str().into()),
+            latest: info.latest.as_ref().map(|latest| latest.as_str().into()),
+            upgradeable: info.is_upgradeable(),
+            can_update,
+            needs_reboot,
+            severity: info.severity(),
+        };
+
+        self.devices.push(Device { entity, description, kind });
+    }
+
+    fn device(&self, entity: Entity) -> Option<&Device> {
+        self.devices.iter().find(|device| device.entity == entity)
+    }
+
+    /// The fwupd device of an entity which a client's event was paired with.
+    fn fwupd_device(
+        &self,
+        &(_, entity): &(EntityId, EntityId),
+    ) -> Result<(Entity, Arc<FwupdDevice>), HostError> {
+        let device = self.device(entity.into()).expect("paired entities exist");
+        match &device.kind {
+            Kind::Fwupd { device: fwupd, .. } => Ok((device.entity, fwupd.clone())),
+            _ => Err(HostError::NotFound(device.description.id.to_string())),
+        }
+    }
+
+    fn stage(&mut self, entity: Entity, stage: Stage) {
+        if let Some(operation) = self.operations.get_mut(entity) {
+            operation.stage = stage;
+        }
+    }
+
+    fn emit_progress(&mut self, entity: Entity) {
+        if let Some(operation) = self.operations.get(entity) {
+            self.pending.push(ServiceSignal::Progress(operation.clone()));
+        }
+    }
+
+    /// Devices are scanned again once every operation has ended, as their firmware changed.
+    fn finished(&mut self) {
+        if self.operations.is_empty() && !self.scanning {
+            self.scanning = true;
+            let _res = self.sender.send(FirmwareEvent::Scan);
+        }
+    }
+}
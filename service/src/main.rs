@@ -0,0 +1,190 @@
+mod host;
+
+use self::host::{Dispatched, Host, HostError};
+use dbus::{
+    blocking::stdintf::org_freedesktop_dbus::RequestNameReply, channel::Sender as _,
+    message::MatchRule,
+};
+use dbus_crossroads::{Context, Crossroads, IfaceBuilder, MethodErr};
+use firmware_manager::*;
+use std::{
+    process::exit,
+    sync::{
+        mpsc::{self, TryRecvError},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+/// How long to wait for method calls before emitting the signals of the event loop.
+const PROCESS_TIMEOUT: Duration = Duration::from_millis(100);
+
+type SharedHost = Arc<Mutex<Host>>;
+
+fn main() {
+    let bus = match bus_argument() {
+        Ok(bus) => bus,
+        Err(why) => {
+            eprintln!("{}", why);
+            exit(1);
+        }
+    };
+
+    if let Err(why) = run(bus) {
+        eprintln!("firmware-manager-service: {}", why);
+        exit(1);
+    }
+}
+
+/// Parses `--session` or `--system` from the command line, defaulting to the system bus.
+fn bus_argument() -> Result<Bus, String> {
+    let mut bus = Bus::System;
+
+    for arg in std::env::args().skip(1) {
+        bus = match arg.as_str() {
+            "--session" => Bus::Session,
+            "--system" => Bus::System,
+            _ => return Err(format!("unknown argument: {}", arg)),
+        };
+    }
+
+    Ok(bus)
+}
+
+fn run(bus: Bus) -> Result<(), dbus::Error> {
+    let connection = bus.connect()?;
+
+    let (event_tx, event_rx) = mpsc::channel();
+    let (signal_tx, signal_rx) = mpsc::channel();
+    let background = thread::spawn(move || event_loop(event_rx, signal_tx));
+
+    let host: SharedHost = Arc::new(Mutex::new(Host::new(event_tx.clone())));
+
+    let mut crossroads = Crossroads::new();
+    let interface = crossroads.register(SERVICE_INTERFACE, |builder| register(builder, bus));
+    crossroads.insert(SERVICE_PATH, &[interface], host.clone());
+
+    connection.start_receive(
+        MatchRule::new_method_call(),
+        Box::new(move |message, connection| {
+            let _res = crossroads.handle_message(message, connection);
+            true
+        }),
+    );
+
+    // The name is requested once the interface exists, as clients may call it right away.
+    if connection.request_name(SERVICE_NAME, false, false, true)? != RequestNameReply::PrimaryOwner
+    {
+        let _res = event_tx.send(FirmwareEvent::Stop);
+        let name = "com.system76.FirmwareManager.Error.Running";
+        return Err(dbus::Error::new_custom(name, "the service is already running"));
+    }
+
+    lock(&host).scan().expect("no operations are in flight at startup");
+
+    let result = 'main: loop {
+        if let Err(why) = connection.process(PROCESS_TIMEOUT) {
+            break Err(why);
+        }
+
+        let mut host = lock(&host);
+
+        loop {
+            match signal_rx.try_recv() {
+                Ok(signal) => host.signal(signal),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => break 'main Ok(()),
+            }
+        }
+
+        for signal in host.take_signals() {
+            let _res = connection.send(signal.to_message());
+        }
+    };
+
+    let _res = event_tx.send(FirmwareEvent::Stop);
+    let _res = background.join();
+
+    result
+}
+
+/// Defines the methods of the service's interface.
+///
+/// Signals are emitted by the main loop, as they originate from the event loop of the core.
+fn register(builder: &mut IfaceBuilder<SharedHost>, bus: Bus) {
+    builder.method("Scan", (), (), |_, host: &mut SharedHost, (): ()| {
+        lock(host).scan().map_err(method_error)
+    });
+
+    builder.method("Devices", (), ("devices",), |_, host: &mut SharedHost, (): ()| {
+        Ok((lock(host).devices(),))
+    });
+
+    builder.method("Operations", (), ("operations",), |_, host: &mut SharedHost, (): ()| {
+        Ok((lock(host).operations(),))
+    });
+
+    builder.method("History", (), ("history",), |_, _: &mut SharedHost, (): ()| {
+        let history = firmware_manager::history().map_err(|why| MethodErr::failed(&why))?;
+        Ok((history.iter().map(HistoryEntry::to_arg).collect::<Vec<_>>(),))
+    });
+
+    builder.method(
+        "Update",
+        ("devices",),
+        (),
+        move |context: &mut Context, host: &mut SharedHost, (devices,): (Vec<String>,)| {
+            let events = lock(host).prepare(&devices).map_err(method_error)?;
+            start_authorized(context, host, bus, Dispatched { entities: Vec::new(), events });
+            Ok(())
+        },
+    );
+
+    builder.method(
+        "Dispatch",
+        ("event",),
+        ("entities",),
+        move |context: &mut Context, host: &mut SharedHost, (event,): (String,)| {
+            let event = decode_record::<EventData>(&event)
+                .ok_or_else(|| MethodErr::invalid_arg("event"))?;
+
+            let dispatched = lock(host).dispatch(event).map_err(method_error)?;
+            let entities = dispatched
+                .entities
+                .iter()
+                .map(|&(client, service)| (client.0, service.0))
+                .collect::<Vec<EntityArg>>();
+
+            if !dispatched.events.is_empty() {
+                start_authorized(context, host, bus, dispatched);
+            }
+
+            Ok((entities,))
+        },
+    );
+}
+
+/// Starts the events of a client once it is authorized to update firmware.
+fn start_authorized(context: &Context, host: &SharedHost, bus: Bus, dispatched: Dispatched) {
+    let caller = context.message().sender().map(|name| name.to_string());
+    let host = host.clone();
+
+    // Authentication may take as long as the user needs, so other clients are served in the
+    // meantime.
+    thread::spawn(move || {
+        let authorization = match (bus, caller) {
+            (Bus::System, Some(caller)) => authorize_client(Action::UpdateFirmware, &caller),
+            (Bus::System, None) => Authorization::Denied,
+            (Bus::Session, _) => authorize(Action::UpdateFirmware),
+        };
+
+        lock(&host).start(dispatched, authorization.is_authorized());
+    });
+}
+
+fn lock(host: &SharedHost) -> std::sync::MutexGuard<'_, Host> {
+    host.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn method_error(why: HostError) -> MethodErr { MethodErr::from((why.name(), why.to_string())) }
@@ -0,0 +1,162 @@
+//! Authorization of the actions of the firmware manager through polkit.
+//!
+//! Systems without polkit, or without the firmware manager's policy installed, fall back to
+//! checking whether the user is a member of an administrative group.
+
+use crate::users::user_is_admin;
+use dbus::{
+    arg::{PropMap, Variant},
+    blocking::Connection,
+};
+use std::{collections::HashMap, fs, time::Duration};
+
+/// Seconds to wait for the user to authenticate.
+const AUTHENTICATION_TIMEOUT: u64 = 300;
+
+/// Allows polkit to ask the user to authenticate.
+const ALLOW_USER_INTERACTION: u32 = 1;
+
+/// Actions of the firmware manager which require authorization.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+    /// Install firmware on a device.
+    UpdateFirmware,
+    /// Refresh the metadata of firmware remotes.
+    ModifyRemotes,
+}
+
+impl Action {
+    /// The ID of the polkit action.
+    pub fn id(self) -> &'static str {
+        match self {
+            Action::UpdateFirmware => "com.system76.FirmwareManager.update-firmware",
+            Action::ModifyRemotes => "com.system76.FirmwareManager.modify-remotes",
+        }
+    }
+}
+
+/// Whether the user may perform an action.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Authorization {
+    /// The user may perform the action.
+    Authorized,
+    /// The user may perform the action once they have authenticated.
+    Challenge,
+    /// The user may not perform the action.
+    Denied,
+}
+
+impl Authorization {
+    /// Whether the user may perform the action right now.
+    pub fn is_authorized(self) -> bool { self == Authorization::Authorized }
+
+    /// Whether the user is authorized, or could be once they have authenticated.
+    pub fn is_possible(self) -> bool { self != Authorization::Denied }
+}
+
+/// Checks whether the user may perform an action, without asking them to authenticate.
+pub fn check_authorization(action: Action) -> Authorization { authorization(action, 0) }
+
+/// Authorizes the user to perform an action, asking them to authenticate if that is required.
+///
+/// This blocks until the user has authenticated, or dismissed the authentication dialog.
+pub fn authorize(action: Action) -> Authorization {
+    authorization(action, ALLOW_USER_INTERACTION)
+}
+
+/// Authorizes a client of the service to perform an action, by its unique name on the system bus.
+///
+/// Clients are asked to authenticate if that is required, and this blocks until they have. The
+/// groups of a client cannot be checked without polkit, so clients are denied if it fails.
+pub fn authorize_client(action: Action, bus_name: &str) -> Authorization {
+    let mut subject = PropMap::new();
+    subject.insert("name".into(), Variant(Box::new(bus_name.to_owned())));
+
+    match polkit_authorization(("system-bus-name", subject), action, ALLOW_USER_INTERACTION) {
+        Ok(authorization) => authorization,
+        Err(why) => {
+            warn!("failed to authorize {} for {} with polkit: {}", action.id(), bus_name, why);
+            Authorization::Denied
+        }
+    }
+}
+
+fn authorization(action: Action, flags: u32) -> Authorization {
+    match polkit_authorization(process_subject(), action, flags) {
+        Ok(authorization) => authorization,
+        Err(why) => {
+            warn!("failed to authorize {} with polkit: {}", action.id(), why);
+            if user_is_admin() {
+                Authorization::Authorized
+            } else {
+                Authorization::Denied
+            }
+        }
+    }
+}
+
+/// The polkit subject which identifies this process.
+fn process_subject() -> (&'static str, PropMap) {
+    let mut subject = PropMap::new();
+    subject.insert("pid".into(), Variant(Box::new(std::process::id())));
+    subject.insert("start-time".into(), Variant(Box::new(start_time().unwrap_or(0))));
+    ("unix-process", subject)
+}
+
+/// Asks polkit whether the subject may perform an action.
+fn polkit_authorization(
+    subject: (&'static str, PropMap),
+    action: Action,
+    flags: u32,
+) -> Result<Authorization, dbus::Error> {
+    let details: HashMap<&str, &str> = HashMap::new();
+
+    let connection = Connection::new_system()?;
+    let proxy = connection.with_proxy(
+        "org.freedesktop.PolicyKit1",
+        "/org/freedesktop/PolicyKit1/Authority",
+        Duration::from_secs(AUTHENTICATION_TIMEOUT),
+    );
+
+    let ((authorized, challenge, _details),): ((bool, bool, HashMap<String, String>),) = proxy
+        .method_call(
+            "org.freedesktop.PolicyKit1.Authority",
+            "CheckAuthorization",
+            (subject, action.id(), details, flags, ""),
+        )?;
+
+    Ok(if authorized {
+        Authorization::Authorized
+    } else if challenge {
+        Authorization::Challenge
+    } else {
+        Authorization::Denied
+    })
+}
+
+/// The time at which this process started, which polkit uses to identify it.
+fn start_time() -> Option<u64> {
+    parse_start_time(&fs::read_to_string("/proc/self/stat").ok()?)
+}
+
+/// Parses the start time from the contents of `/proc/<pid>/stat`.
+///
+/// The command name may contain spaces, so fields are counted from its closing parenthesis.
+fn parse_start_time(stat: &str) -> Option<u64> {
+    let (_, fields) = stat.rsplit_once(')')?;
+    fields.split_whitespace().nth(19)?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn start_time() {
+        let stat = "1234 (firmware manager) S 1 1234 1234 0 -1 4194560 5000 0 0 0 25 10 0 0 \
+                    20 0 4 0 987654 123456789 5000 18446744073709551615";
+
+        assert_eq!(parse_start_time(stat), Some(987654));
+        assert_eq!(parse_start_time("1234 (truncated"), None);
+    }
+}
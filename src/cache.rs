@@ -1,6 +1,7 @@
 use std::{
-    io,
+    fs, io,
     path::{Path, PathBuf},
+    time::SystemTime,
 };
 
 /// An error that may occur when attempting to get the cache directory.
@@ -19,3 +20,18 @@ pub fn cache<P: AsRef<Path>>(file: P) -> Result<PathBuf, Error> {
         .place_cache_file(file)
         .map_err(Error::Place)
 }
+
+/// Fetches the path of a file in the cache if it exists, without creating any directories.
+pub fn cached<P: AsRef<Path>>(file: P) -> Option<PathBuf> {
+    xdg::BaseDirectories::with_prefix("com.system76.FirmwareManager").ok()?.find_cache_file(file)
+}
+
+/// The time at which a file was last modified, in seconds since the UNIX Epoch.
+pub fn modified(path: &Path) -> Option<u64> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()?
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .ok()
+        .map(|duration| duration.as_secs())
+}
@@ -0,0 +1,696 @@
+//! A structured representation of release notes, shared by all frontends.
+//!
+//! fwupd describes releases with a subset of HTML defined by the AppStream specification, whereas
+//! System76 changelogs are plain text with Markdown-style lists. Both are parsed into the same
+//! [`Changelog`] document, so that frontends need only a single renderer.
+
+use std::fmt::{self, Write};
+
+/// A document of release notes, consisting of a sequence of blocks.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Changelog {
+    /// The blocks of the document, in order of appearance.
+    pub blocks: Vec<Block>,
+}
+
+/// A paragraph or list in a changelog.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Block {
+    /// A paragraph of text.
+    Paragraph(Vec<Inline>),
+    /// A list, where each item is a line of text.
+    List {
+        /// Whether the items of the list are numbered.
+        ordered: bool,
+        /// The items of the list.
+        items: Vec<Vec<Inline>>,
+    },
+}
+
+/// A span of text within a block.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Inline {
+    /// Text without any styling.
+    Text(Box<str>),
+    /// Emphasized text.
+    Emphasis(Box<str>),
+    /// Strongly emphasized text.
+    Strong(Box<str>),
+    /// Code, or other text which should be displayed in a monospace font.
+    Code(Box<str>),
+    /// A hyperlink.
+    Link {
+        /// The text to display for the link.
+        text: Box<str>,
+        /// The location that the link points to.
+        url: Box<str>,
+    },
+}
+
+impl Inline {
+    /// The text that is displayed for this span.
+    pub fn text(&self) -> &str {
+        match self {
+            Inline::Text(text)
+            | Inline::Emphasis(text)
+            | Inline::Strong(text)
+            | Inline::Code(text)
+            | Inline::Link { text, .. } => text,
+        }
+    }
+}
+
+impl Changelog {
+    /// Parses an AppStream description, as given by fwupd.
+    ///
+    /// Supports the `p`, `ul`, `ol`, `li`, `em`, `strong`, `code` and `a` elements, along with
+    /// their common aliases. Unknown elements are ignored, but their text is kept.
+    pub fn from_appstream(html: &str) -> Self {
+        let mut parser = AppStreamParser::default();
+        let mut rest = html;
+
+        while !rest.is_empty() {
+            match rest.find('<') {
+                Some(0) => match rest.find('>') {
+                    Some(end) => {
+                        parser.tag(&rest[1..end]);
+                        rest = &rest[end + 1..];
+                    }
+                    None => {
+                        parser.text(&decode_entities(rest));
+                        break;
+                    }
+                },
+                Some(start) => {
+                    parser.text(&decode_entities(&rest[..start]));
+                    rest = &rest[start..];
+                }
+                None => {
+                    parser.text(&decode_entities(rest));
+                    break;
+                }
+            }
+        }
+
+        parser.finish()
+    }
+
+    /// Parses the description of a version in a System76 changelog.
+    ///
+    /// Lines beginning with `-`, `*` or `+` are list items, as are lines beginning with a number
+    /// followed by `.` or `)`. Blank lines separate paragraphs. Backticks mark code, and both
+    /// Markdown links and bare URLs become links.
+    pub fn from_system76(text: &str) -> Self {
+        let mut blocks = Vec::new();
+        let mut paragraph = String::new();
+        let mut list: Option<(bool, Vec<String>)> = None;
+
+        fn flush_paragraph(blocks: &mut Vec<Block>, paragraph: &mut String) {
+            if !paragraph.is_empty() {
+                blocks.push(Block::Paragraph(parse_plain_inlines(paragraph)));
+                paragraph.clear();
+            }
+        }
+
+        fn flush_list(blocks: &mut Vec<Block>, list: &mut Option<(bool, Vec<String>)>) {
+            if let Some((ordered, items)) = list.take() {
+                let items = items.iter().map(|item| parse_plain_inlines(item)).collect();
+                blocks.push(Block::List { ordered, items });
+            }
+        }
+
+        for line in text.lines() {
+            let trimmed = line.trim();
+
+            if trimmed.is_empty() {
+                flush_paragraph(&mut blocks, &mut paragraph);
+                flush_list(&mut blocks, &mut list);
+                continue;
+            }
+
+            if let Some((ordered, item)) = list_item(trimmed) {
+                flush_paragraph(&mut blocks, &mut paragraph);
+
+                if list.as_ref().map_or(false, |&(kind, _)| kind != ordered) {
+                    flush_list(&mut blocks, &mut list);
+                }
+
+                list.get_or_insert_with(|| (ordered, Vec::new())).1.push(item.to_owned());
+                continue;
+            }
+
+            // Indented lines continue the previous list item.
+            if let Some((_, ref mut items)) = list {
+                if line.starts_with(char::is_whitespace) {
+                    if let Some(last) = items.last_mut() {
+                        last.push(' ');
+                        last.push_str(trimmed);
+                        continue;
+                    }
+                }
+
+                flush_list(&mut blocks, &mut list);
+            }
+
+            if !paragraph.is_empty() {
+                paragraph.push(' ');
+            }
+
+            paragraph.push_str(trimmed);
+        }
+
+        flush_paragraph(&mut blocks, &mut paragraph);
+        flush_list(&mut blocks, &mut list);
+
+        Self { blocks }
+    }
+
+    /// Returns `true` if the changelog has no content.
+    pub fn is_empty(&self) -> bool { self.blocks.is_empty() }
+}
+
+/// Renders the changelog as plain text, with bullets for list items.
+impl fmt::Display for Changelog {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn inlines(f: &mut fmt::Formatter, inlines: &[Inline]) -> fmt::Result {
+            for inline in inlines {
+                match inline {
+                    Inline::Link { text, url } if text != url => write!(f, "{} ({})", text, url)?,
+                    inline => f.write_str(inline.text())?,
+                }
+            }
+
+            Ok(())
+        }
+
+        for (id, block) in self.blocks.iter().enumerate() {
+            if id != 0 {
+                f.write_str("\n\n")?;
+            }
+
+            match block {
+                Block::Paragraph(paragraph) => inlines(f, paragraph)?,
+                Block::List { ordered, items } => {
+                    for (id, item) in items.iter().enumerate() {
+                        if id != 0 {
+                            f.write_char('\n')?;
+                        }
+
+                        if *ordered {
+                            write!(f, "{}. ", id + 1)?;
+                        } else {
+                            f.write_str("• ")?;
+                        }
+
+                        inlines(f, item)?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Tracks the state of the AppStream parser between tags.
+#[derive(Default)]
+struct AppStreamParser {
+    blocks: Vec<Block>,
+    paragraph: Vec<Inline>,
+    list: Option<(bool, Vec<Vec<Inline>>)>,
+    item: Option<Vec<Inline>>,
+    emphasis: usize,
+    strong: usize,
+    code: usize,
+    link: Option<Box<str>>,
+}
+
+impl AppStreamParser {
+    fn tag(&mut self, tag: &str) {
+        let tag = tag.trim();
+        let (closing, tag) = match tag.strip_prefix('/') {
+            Some(tag) => (true, tag.trim_start()),
+            None => (false, tag.trim_end_matches('/')),
+        };
+
+        let name_end = tag.find(char::is_whitespace).unwrap_or(tag.len());
+        let (name, attributes) = tag.split_at(name_end);
+
+        match (name.to_ascii_lowercase().as_str(), closing) {
+            ("p", _) => self.flush_paragraph(),
+            ("ul", false) | ("ol", false) => {
+                self.flush_paragraph();
+                self.flush_list();
+                self.list = Some((name.eq_ignore_ascii_case("ol"), Vec::new()));
+            }
+            ("ul", true) | ("ol", true) => self.flush_list(),
+            ("li", false) => {
+                self.flush_item();
+                if self.list.is_none() {
+                    self.flush_paragraph();
+                    self.list = Some((false, Vec::new()));
+                }
+
+                self.item = Some(Vec::new());
+            }
+            ("li", true) => self.flush_item(),
+            ("em", false) | ("i", false) => self.emphasis += 1,
+            ("em", true) | ("i", true) => self.emphasis = self.emphasis.saturating_sub(1),
+            ("strong", false) | ("b", false) => self.strong += 1,
+            ("strong", true) | ("b", true) => self.strong = self.strong.saturating_sub(1),
+            ("code", false) | ("tt", false) => self.code += 1,
+            ("code", true) | ("tt", true) => self.code = self.code.saturating_sub(1),
+            ("a", false) => self.link = attribute(attributes, "href").map(Box::from),
+            ("a", true) => self.link = None,
+            ("br", _) => self.text(" "),
+            _ => (),
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+
+        let in_list = self.list.is_some();
+        if in_list && self.item.is_none() {
+            // Whitespace between list items is insignificant.
+            if text.trim().is_empty() {
+                return;
+            }
+
+            self.item = Some(Vec::new());
+        }
+
+        let inline = if let Some(ref url) = self.link {
+            Inline::Link { text: "".into(), url: url.clone() }
+        } else if self.code != 0 {
+            Inline::Code("".into())
+        } else if self.strong != 0 {
+            Inline::Strong("".into())
+        } else if self.emphasis != 0 {
+            Inline::Emphasis("".into())
+        } else {
+            Inline::Text("".into())
+        };
+
+        let target = match self.item {
+            Some(ref mut item) => item,
+            None => &mut self.paragraph,
+        };
+
+        push_inline(target, inline, &collapse_whitespace(text));
+    }
+
+    fn flush_paragraph(&mut self) {
+        if let Some(paragraph) = finish_inlines(&mut self.paragraph) {
+            self.blocks.push(Block::Paragraph(paragraph));
+        }
+    }
+
+    fn flush_item(&mut self) {
+        if let Some(mut item) = self.item.take() {
+            if let (Some(item), Some((_, items))) = (finish_inlines(&mut item), &mut self.list) {
+                items.push(item);
+            }
+        }
+    }
+
+    fn flush_list(&mut self) {
+        self.flush_item();
+        if let Some((ordered, items)) = self.list.take() {
+            if !items.is_empty() {
+                self.blocks.push(Block::List { ordered, items });
+            }
+        }
+    }
+
+    fn finish(mut self) -> Changelog {
+        self.flush_list();
+        self.flush_paragraph();
+        Changelog { blocks: self.blocks }
+    }
+}
+
+/// Appends text to a sequence of inlines, merging it into the last inline if their styles match.
+fn push_inline(inlines: &mut Vec<Inline>, style: Inline, text: &str) {
+    let text = match inlines.last() {
+        // Whitespace only needs to be collapsed across inline boundaries.
+        Some(last) if last.text().ends_with(' ') => text.trim_start_matches(' '),
+        None => text.trim_start_matches(' '),
+        _ => text,
+    };
+
+    if text.is_empty() {
+        return;
+    }
+
+    match (inlines.last_mut(), style) {
+        (Some(Inline::Text(prev)), Inline::Text(_))
+        | (Some(Inline::Emphasis(prev)), Inline::Emphasis(_))
+        | (Some(Inline::Strong(prev)), Inline::Strong(_))
+        | (Some(Inline::Code(prev)), Inline::Code(_)) => {
+            *prev = [&**prev, text].concat().into();
+        }
+        (Some(Inline::Link { text: prev, url: prev_url }), Inline::Link { url, .. })
+            if *prev_url == url =>
+        {
+            *prev = [&**prev, text].concat().into();
+        }
+        (_, style) => inlines.push(with_text(style, text.into())),
+    }
+}
+
+/// Trims the trailing whitespace of a completed sequence of inlines.
+fn finish_inlines(inlines: &mut Vec<Inline>) -> Option<Vec<Inline>> {
+    let mut inlines = std::mem::take(inlines);
+
+    while let Some(last) = inlines.pop() {
+        let text = last.text().trim_end();
+        if !text.is_empty() {
+            inlines.push(with_text(last.clone(), text.into()));
+            break;
+        }
+    }
+
+    if inlines.is_empty() {
+        None
+    } else {
+        Some(inlines)
+    }
+}
+
+fn with_text(inline: Inline, text: Box<str>) -> Inline {
+    match inline {
+        Inline::Text(_) => Inline::Text(text),
+        Inline::Emphasis(_) => Inline::Emphasis(text),
+        Inline::Strong(_) => Inline::Strong(text),
+        Inline::Code(_) => Inline::Code(text),
+        Inline::Link { url, .. } => Inline::Link { text, url },
+    }
+}
+
+/// Replaces each run of whitespace with a single space, as HTML does.
+fn collapse_whitespace(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut whitespace = false;
+
+    for character in text.chars() {
+        if character.is_whitespace() {
+            whitespace = true;
+        } else {
+            if whitespace {
+                output.push(' ');
+                whitespace = false;
+            }
+
+            output.push(character);
+        }
+    }
+
+    if whitespace {
+        output.push(' ');
+    }
+
+    output
+}
+
+/// Decodes the named XML entities, along with numeric character references.
+fn decode_entities(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let decoded = rest.find(';').and_then(|end| {
+            let character = match &rest[1..end] {
+                "amp" => '&',
+                "lt" => '<',
+                "gt" => '>',
+                "quot" => '"',
+                "apos" => '\'',
+                "nbsp" => ' ',
+                entity => {
+                    let code = match entity.strip_prefix("#x").or_else(|| entity.strip_prefix("#X"))
+                    {
+                        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                        None => entity.strip_prefix('#')?.parse().ok()?,
+                    };
+
+                    char::from_u32(code)?
+                }
+            };
+
+            Some((character, end))
+        });
+
+        match decoded {
+            Some((character, end)) => {
+                output.push(character);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                output.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    output.push_str(rest);
+    output
+}
+
+/// Fetches the value of an attribute from the attributes of a tag.
+fn attribute<'a>(attributes: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = attributes.trim_start();
+
+    while !rest.is_empty() {
+        let name_end = rest.find(|c: char| c == '=' || c.is_whitespace()).unwrap_or(rest.len());
+        let (key, after) = rest.split_at(name_end);
+        let after = after.trim_start();
+
+        let (value, after) = match after.strip_prefix('=') {
+            Some(after) => {
+                let after = after.trim_start();
+                match after.chars().next() {
+                    Some(quote @ '"') | Some(quote @ '\'') => {
+                        let after = &after[1..];
+                        let end = after.find(quote).unwrap_or(after.len());
+                        (&after[..end], after.get(end + 1..).unwrap_or(""))
+                    }
+                    _ => {
+                        let end = after.find(char::is_whitespace).unwrap_or(after.len());
+                        after.split_at(end)
+                    }
+                }
+            }
+            None => ("", after),
+        };
+
+        if key.eq_ignore_ascii_case(name) {
+            return Some(value);
+        }
+
+        rest = after.trim_start();
+    }
+
+    None
+}
+
+/// Identifies a list item in plain text, returning whether it is numbered along with its text.
+fn list_item(line: &str) -> Option<(bool, &str)> {
+    for bullet in ["- ", "* ", "+ "] {
+        if let Some(item) = line.strip_prefix(bullet) {
+            return Some((false, item.trim_start()));
+        }
+    }
+
+    let digits = line.find(|c: char| !c.is_ascii_digit())?;
+    if digits == 0 {
+        return None;
+    }
+
+    let rest = &line[digits..];
+    rest.strip_prefix(". ")
+        .or_else(|| rest.strip_prefix(") "))
+        .map(|item| (true, item.trim_start()))
+}
+
+/// Parses the inline markup of plain text: backticks for code, along with Markdown links and
+/// bare URLs.
+fn parse_plain_inlines(text: &str) -> Vec<Inline> {
+    let mut inlines = Vec::new();
+    let mut plain = String::new();
+    let mut rest = text;
+
+    fn flush(inlines: &mut Vec<Inline>, plain: &mut String) {
+        if !plain.is_empty() {
+            inlines.push(Inline::Text(plain.as_str().into()));
+            plain.clear();
+        }
+    }
+
+    while let Some(character) = rest.chars().next() {
+        if character == '`' {
+            if let Some(end) = rest[1..].find('`') {
+                flush(&mut inlines, &mut plain);
+                inlines.push(Inline::Code(rest[1..end + 1].into()));
+                rest = &rest[end + 2..];
+                continue;
+            }
+        } else if character == '[' {
+            // The URL must follow the first closing bracket, so that brackets which are not links
+            // are left as they are.
+            let link = rest.find(']').filter(|&middle| rest[middle..].starts_with("](")).and_then(
+                |middle| {
+                    let end = middle + rest[middle..].find(')')?;
+                    Some((&rest[1..middle], &rest[middle + 2..end], end))
+                },
+            );
+
+            if let Some((text, url, end)) = link {
+                flush(&mut inlines, &mut plain);
+                inlines.push(Inline::Link { text: text.into(), url: url.into() });
+                rest = &rest[end + 1..];
+                continue;
+            }
+        } else if (rest.starts_with("https://") || rest.starts_with("http://"))
+            && plain.chars().last().map_or(true, |c| !c.is_alphanumeric())
+        {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            // Trailing punctuation belongs to the sentence rather than the URL.
+            let url = rest[..end].trim_end_matches(['.', ',', ';', ':', ')']);
+            flush(&mut inlines, &mut plain);
+            inlines.push(Inline::Link { text: url.into(), url: url.into() });
+            rest = &rest[url.len()..];
+            continue;
+        }
+
+        plain.push(character);
+        rest = &rest[character.len_utf8()..];
+    }
+
+    flush(&mut inlines, &mut plain);
+    inlines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(text: &str) -> Inline { Inline::Text(text.into()) }
+
+    #[test]
+    fn appstream_paragraphs_and_lists() {
+        let changelog = Changelog::from_appstream(
+            "<p>This release fixes:</p>\n<ul>\n  <li>A  <em>critical</em> bug</li>\n  \
+             <li>Another &amp; more</li>\n</ul><ol><li>First</li></ol>",
+        );
+
+        assert_eq!(
+            changelog.blocks,
+            vec![
+                Block::Paragraph(vec![text("This release fixes:")]),
+                Block::List {
+                    ordered: false,
+                    items: vec![
+                        vec![text("A "), Inline::Emphasis("critical".into()), text(" bug")],
+                        vec![text("Another & more")],
+                    ],
+                },
+                Block::List { ordered: true, items: vec![vec![text("First")]] },
+            ]
+        );
+    }
+
+    #[test]
+    fn appstream_links_and_code() {
+        let changelog = Changelog::from_appstream(
+            "<p>See <a href=\"https://example.com\">the advisory</a> for <code>CVE-1</code>.</p>",
+        );
+
+        assert_eq!(
+            changelog.blocks,
+            vec![Block::Paragraph(vec![
+                text("See "),
+                Inline::Link { text: "the advisory".into(), url: "https://example.com".into() },
+                text(" for "),
+                Inline::Code("CVE-1".into()),
+                text("."),
+            ])]
+        );
+    }
+
+    #[test]
+    fn appstream_plain_text() {
+        assert_eq!(
+            Changelog::from_appstream("  Just text  ").blocks,
+            vec![Block::Paragraph(vec![text("Just text")])]
+        );
+        assert!(Changelog::from_appstream("").is_empty());
+        assert!(Changelog::from_appstream("<p>  </p><ul></ul>").is_empty());
+    }
+
+    #[test]
+    fn entities() {
+        assert_eq!(decode_entities("a &lt;b&gt; &#65;&#x42; &unknown; &"), "a <b> AB &unknown; &");
+    }
+
+    #[test]
+    fn system76_lists() {
+        let changelog = Changelog::from_system76(
+            "Updated firmware:\n- Update EC to `2020-02-03`\n- Fix suspend\n  on battery\n\n\
+             1. See https://example.com/notes.\n2) [Article](https://example.com)",
+        );
+
+        assert_eq!(
+            changelog.blocks,
+            vec![
+                Block::Paragraph(vec![text("Updated firmware:")]),
+                Block::List {
+                    ordered: false,
+                    items: vec![
+                        vec![text("Update EC to "), Inline::Code("2020-02-03".into())],
+                        vec![text("Fix suspend on battery")],
+                    ],
+                },
+                Block::List {
+                    ordered: true,
+                    items: vec![
+                        vec![
+                            text("See "),
+                            Inline::Link {
+                                text: "https://example.com/notes".into(),
+                                url: "https://example.com/notes".into(),
+                            },
+                            text("."),
+                        ],
+                        vec![Inline::Link {
+                            text: "Article".into(),
+                            url: "https://example.com".into(),
+                        }],
+                    ],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn bracketed_text_before_link() {
+        assert_eq!(
+            parse_plain_inlines("[note] see [x](y)"),
+            vec![text("[note] see "), Inline::Link { text: "x".into(), url: "y".into() }]
+        );
+        assert_eq!(parse_plain_inlines("[a] (b)"), vec![text("[a] (b)")]);
+    }
+
+    #[test]
+    fn display() {
+        let changelog = Changelog::from_appstream(
+            "<p>Fixes:</p><ul><li>One</li><li><a href=\"u\">Two</a></li></ul>",
+        );
+        assert_eq!(changelog.to_string(), "Fixes:\n\n• One\n• Two (u)");
+    }
+}
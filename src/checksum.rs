@@ -0,0 +1,205 @@
+//! Verification of firmware payloads against the checksums in their release metadata.
+
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha512};
+use std::{
+    fmt, fs,
+    io::{self, Read},
+    path::Path,
+    str::FromStr,
+};
+
+/// The hash algorithm that a checksum was computed with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ChecksumKind {
+    /// SHA-1, which fwupd still lists for compatibility with older clients.
+    Sha1,
+    /// SHA-256
+    Sha256,
+    /// SHA-512
+    Sha512,
+}
+
+impl ChecksumKind {
+    /// The name of the algorithm, as it is written in the history.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ChecksumKind::Sha1 => "sha1",
+            ChecksumKind::Sha256 => "sha256",
+            ChecksumKind::Sha512 => "sha512",
+        }
+    }
+
+    /// Identifies the algorithm of a hex digest from its length.
+    fn from_hex_len(len: usize) -> Option<Self> {
+        match len {
+            40 => Some(ChecksumKind::Sha1),
+            64 => Some(ChecksumKind::Sha256),
+            128 => Some(ChecksumKind::Sha512),
+            _ => None,
+        }
+    }
+}
+
+/// A checksum of a firmware payload.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Checksum {
+    /// The algorithm which the checksum was computed with.
+    pub kind: ChecksumKind,
+    /// The digest, in lowercase hexadecimal.
+    pub digest: Box<str>,
+}
+
+impl Checksum {
+    /// Computes the checksum of a file with the given algorithm.
+    pub fn of_file(kind: ChecksumKind, path: &Path) -> io::Result<Self> {
+        let file = fs::File::open(path)?;
+        match kind {
+            ChecksumKind::Sha1 => hash_reader::<Sha1, _>(kind, file),
+            ChecksumKind::Sha256 => hash_reader::<Sha256, _>(kind, file),
+            ChecksumKind::Sha512 => hash_reader::<Sha512, _>(kind, file),
+        }
+    }
+}
+
+/// Parses a bare hex digest, as given by fwupd, or a digest prefixed with its algorithm.
+impl FromStr for Checksum {
+    type Err = ();
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let input = input.trim();
+        let digest = match input.split_once(':') {
+            Some((_, digest)) => digest,
+            None => input,
+        };
+
+        if !digest.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            return Err(());
+        }
+
+        let kind = ChecksumKind::from_hex_len(digest.len()).ok_or(())?;
+        Ok(Self { kind, digest: digest.to_ascii_lowercase().into() })
+    }
+}
+
+impl fmt::Display for Checksum {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.kind.as_str(), self.digest)
+    }
+}
+
+/// The outcome of verifying a downloaded firmware payload.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Verification {
+    /// The payload matched the checksum in the release metadata.
+    Verified(Checksum),
+    /// The release metadata did not contain a checksum to verify the payload against, so the
+    /// payload's SHA-256 checksum is given instead.
+    Unverified(Checksum),
+    /// The payload did not match the checksum in the release metadata.
+    Mismatch {
+        /// The checksum given by the release metadata.
+        expected: Checksum,
+        /// The checksum of the payload that was downloaded.
+        actual: Checksum,
+    },
+}
+
+impl Verification {
+    /// Verifies a payload against the strongest of the checksums given for its release.
+    pub fn of_file(path: &Path, expected: &[Box<str>]) -> io::Result<Self> {
+        let expected = expected
+            .iter()
+            .filter_map(|checksum| checksum.parse::<Checksum>().ok())
+            .max_by_key(|checksum| checksum.kind);
+
+        Ok(match expected {
+            Some(expected) => {
+                let actual = Checksum::of_file(expected.kind, path)?;
+                if actual == expected {
+                    Verification::Verified(actual)
+                } else {
+                    Verification::Mismatch { expected, actual }
+                }
+            }
+            None => Verification::Unverified(Checksum::of_file(ChecksumKind::Sha256, path)?),
+        })
+    }
+
+    /// The checksum of the payload that was downloaded.
+    pub fn checksum(&self) -> &Checksum {
+        match self {
+            Verification::Verified(checksum)
+            | Verification::Unverified(checksum)
+            | Verification::Mismatch { actual: checksum, .. } => checksum,
+        }
+    }
+}
+
+fn hash_reader<D: Digest, R: Read>(kind: ChecksumKind, mut reader: R) -> io::Result<Checksum> {
+    let mut hasher = D::new();
+    let mut buffer = [0u8; 8 * 1024];
+
+    loop {
+        match reader.read(&mut buffer)? {
+            0 => break,
+            read => hasher.update(&buffer[..read]),
+        }
+    }
+
+    let digest = hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect::<String>();
+
+    Ok(Checksum { kind, digest: digest.into() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHA1_ABC: &str = "a9993e364706816aba3e25717850c26c9cd0d89d";
+    const SHA256_ABC: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+    #[test]
+    fn parse() {
+        let checksum = SHA1_ABC.to_ascii_uppercase().parse::<Checksum>().unwrap();
+        assert_eq!(checksum.kind, ChecksumKind::Sha1);
+        assert_eq!(&*checksum.digest, SHA1_ABC);
+
+        let checksum = format!("sha256:{}", SHA256_ABC).parse::<Checksum>().unwrap();
+        assert_eq!(checksum.kind, ChecksumKind::Sha256);
+        assert_eq!(checksum.to_string(), format!("sha256:{}", SHA256_ABC));
+
+        assert!("abc".parse::<Checksum>().is_err());
+        assert!("z".repeat(40).parse::<Checksum>().is_err());
+    }
+
+    #[test]
+    fn hash() {
+        let checksum = hash_reader::<Sha256, _>(ChecksumKind::Sha256, &b"abc"[..]).unwrap();
+        assert_eq!(&*checksum.digest, SHA256_ABC);
+
+        let checksum = hash_reader::<Sha1, _>(ChecksumKind::Sha1, &b"abc"[..]).unwrap();
+        assert_eq!(&*checksum.digest, SHA1_ABC);
+    }
+
+    #[test]
+    fn verify() {
+        let path = std::env::temp_dir().join(format!("firmware-checksum-{}", std::process::id()));
+        fs::write(&path, b"abc").unwrap();
+
+        let verification = Verification::of_file(&path, &[SHA1_ABC.into(), SHA256_ABC.into()]);
+        assert_eq!(
+            verification.unwrap(),
+            Verification::Verified(SHA256_ABC.parse::<Checksum>().unwrap())
+        );
+
+        let verification = Verification::of_file(&path, &["0".repeat(64).into()]).unwrap();
+        assert!(matches!(verification, Verification::Mismatch { .. }));
+        assert_eq!(verification.checksum().digest.as_ref(), SHA256_ABC);
+
+        let verification = Verification::of_file(&path, &[]).unwrap();
+        assert_eq!(verification, Verification::Unverified(SHA256_ABC.parse().unwrap()));
+
+        let _ = fs::remove_file(&path);
+    }
+}
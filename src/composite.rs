@@ -0,0 +1,131 @@
+//! The relationships between the devices which make up a composite device, such as a dock.
+
+use crate::DeviceFlags;
+use std::mem;
+
+/// Orders the devices of composite devices in the order that fwupd requires them to be installed.
+///
+/// Each device is given by its ID, the ID of its parent, and its flags. A device is installed
+/// before its parent, unless the parent has the `INSTALL_PARENT_FIRST` flag. Devices whose parent
+/// is not among the given devices are ordered as though they had no parent.
+///
+/// Returns the indices of the devices in the order that they are to be installed.
+pub fn install_order(devices: &[(&str, Option<&str>, DeviceFlags)]) -> Vec<usize> {
+    let is_member = |id: &str| devices.iter().any(|&(other, ..)| other == id);
+
+    let mut order = Vec::with_capacity(devices.len());
+    let mut visited = vec![false; devices.len()];
+
+    for (index, &(_, parent, _)) in devices.iter().enumerate() {
+        if !parent.map_or(false, is_member) {
+            visit(devices, index, &mut visited, &mut order);
+        }
+    }
+
+    // Devices whose parents form a cycle have no root, and are installed last.
+    for index in 0..devices.len() {
+        visit(devices, index, &mut visited, &mut order);
+    }
+
+    order
+}
+
+/// The ID of the device at the root of the composite device which a device belongs to.
+///
+/// `parent` gives the ID of the parent of a device, if it has one. A device without a parent is
+/// its own root, and the search ends early if the parents form a cycle.
+pub(crate) fn composite_root<'a>(id: &'a str, parent: impl Fn(&str) -> Option<&'a str>) -> &'a str {
+    let mut visited = vec![id];
+
+    while let Some(next) = parent(visited[visited.len() - 1]) {
+        if visited.contains(&next) {
+            break;
+        }
+
+        visited.push(next);
+    }
+
+    visited[visited.len() - 1]
+}
+
+fn visit(
+    devices: &[(&str, Option<&str>, DeviceFlags)],
+    index: usize,
+    visited: &mut [bool],
+    order: &mut Vec<usize>,
+) {
+    if mem::replace(&mut visited[index], true) {
+        return;
+    }
+
+    let (id, _, flags) = devices[index];
+    let parent_first = flags.contains(DeviceFlags::INSTALL_PARENT_FIRST);
+
+    if parent_first {
+        order.push(index);
+    }
+
+    for child in 0..devices.len() {
+        if devices[child].1 == Some(id) {
+            visit(devices, child, visited, order);
+        }
+    }
+
+    if !parent_first {
+        order.push(index);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NONE: DeviceFlags = DeviceFlags::from_bits(0);
+
+    #[test]
+    fn children_first() {
+        let devices = [
+            ("dock", None, NONE),
+            ("hub", Some("dock"), NONE),
+            ("pd", Some("hub"), NONE),
+            ("mst", Some("dock"), NONE),
+        ];
+
+        assert_eq!(install_order(&devices), [2, 1, 3, 0]);
+    }
+
+    #[test]
+    fn parent_first() {
+        let devices = [
+            ("pd", Some("dock"), NONE),
+            ("dock", None, DeviceFlags::INSTALL_PARENT_FIRST),
+            ("unrelated", Some("missing"), NONE),
+        ];
+
+        assert_eq!(install_order(&devices), [1, 0, 2]);
+    }
+
+    #[test]
+    fn roots() {
+        let parent = |id: &str| match id {
+            "hub" => Some("dock"),
+            "pd" => Some("hub"),
+            "a" => Some("b"),
+            "b" => Some("a"),
+            _ => None,
+        };
+
+        assert_eq!(composite_root("pd", parent), "dock");
+        assert_eq!(composite_root("dock", parent), "dock");
+        assert_eq!(composite_root("mouse", parent), "mouse");
+        assert_eq!(composite_root("a", parent), "b");
+    }
+
+    #[test]
+    fn cycle() {
+        let devices = [("a", Some("b"), NONE), ("b", Some("a"), NONE)];
+        let mut order = install_order(&devices);
+        order.sort_unstable();
+        assert_eq!(order, [0, 1]);
+    }
+}
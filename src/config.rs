@@ -0,0 +1,185 @@
+//! User preferences which are shared between the firmware manager frontends.
+//!
+//! Preferences are stored as `key = value` lines in the XDG config directory.
+
+use std::{
+    fmt::Write,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+const ALLOW_UNVERIFIED: &str = "allow_unverified";
+const FIRMWARE_DIRECTORY: &str = "firmware_directory";
+const OFFLINE: &str = "offline";
+const SECURITY_ONLY_NOTIFICATIONS: &str = "security_only_notifications";
+
+/// An error that may occur when reading or writing the config file.
+#[derive(Debug, Error)]
+pub enum Error {
+    /// The XDG base directories could not be determined.
+    #[error("failed to get XDG base directory")]
+    BaseDirectory(#[from] xdg::BaseDirectoriesError),
+    /// The config directory could not be created.
+    #[error("failed to get config directory")]
+    Place(#[source] io::Error),
+    /// The config file exists but could not be read.
+    #[error("failed to read config file")]
+    Read(#[source] io::Error),
+    /// The config file could not be written.
+    #[error("failed to write config file")]
+    Write(#[source] io::Error),
+}
+
+/// Preferences which alter the behavior of the firmware manager.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Config {
+    /// Install firmware whose release metadata has no checksum to verify it against.
+    pub allow_unverified: bool,
+    /// A directory of firmware archives, which are installed instead of downloading them.
+    pub firmware_directory: Option<PathBuf>,
+    /// Never access the network, using only cached metadata and local firmware archives.
+    pub offline: bool,
+    /// Only notify the user of updates which fix security vulnerabilities.
+    pub security_only_notifications: bool,
+}
+
+impl Config {
+    /// Loads the config, falling back to the defaults if it could not be read.
+    pub fn load() -> Self {
+        Self::read().unwrap_or_else(|why| {
+            error!("failed to load config: {}", why);
+            Self::default()
+        })
+    }
+
+    /// Reads the config from the XDG config directory.
+    ///
+    /// Returns the defaults if the config file does not exist.
+    pub fn read() -> Result<Self, Error> {
+        let path = config_path()?;
+        match fs::read_to_string(&path) {
+            Ok(data) => Ok(Self::parse(&data)),
+            Err(why) if why.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(why) => Err(Error::Read(why)),
+        }
+    }
+
+    /// Writes the config to the XDG config directory.
+    pub fn save(&self) -> Result<(), Error> {
+        let path = config_path()?;
+        fs::write(&path, self.serialize()).map_err(Error::Write)
+    }
+
+    fn parse(data: &str) -> Self {
+        let mut config = Self::default();
+
+        for line in data.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => {
+                    warn!("ignoring malformed line in config: {}", line);
+                    continue;
+                }
+            };
+
+            match key {
+                ALLOW_UNVERIFIED => {
+                    config.allow_unverified = parse_bool(value).unwrap_or(config.allow_unverified)
+                }
+                FIRMWARE_DIRECTORY => {
+                    config.firmware_directory =
+                        Some(PathBuf::from(value)).filter(|path| !path.as_os_str().is_empty())
+                }
+                OFFLINE => config.offline = parse_bool(value).unwrap_or(config.offline),
+                SECURITY_ONLY_NOTIFICATIONS => {
+                    config.security_only_notifications =
+                        parse_bool(value).unwrap_or(config.security_only_notifications)
+                }
+                _ => warn!("ignoring unknown config key: {}", key),
+            }
+        }
+
+        config
+    }
+
+    fn serialize(&self) -> String {
+        let mut data = String::new();
+
+        if self.allow_unverified {
+            let _ = writeln!(data, "{} = {}", ALLOW_UNVERIFIED, self.allow_unverified);
+        }
+
+        if let Some(ref directory) = self.firmware_directory {
+            let _ = writeln!(data, "{} = {}", FIRMWARE_DIRECTORY, directory.display());
+        }
+
+        let _ = writeln!(data, "{} = {}", OFFLINE, self.offline);
+        let _ = writeln!(
+            data,
+            "{} = {}",
+            SECURITY_ONLY_NOTIFICATIONS, self.security_only_notifications
+        );
+        data
+    }
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value {
+        "true" | "yes" | "1" => Some(true),
+        "false" | "no" | "0" => Some(false),
+        _ => {
+            warn!("expected a boolean in config, found {}", value);
+            None
+        }
+    }
+}
+
+/// Convenience function for fetching the path to the config file.
+fn config_path() -> Result<PathBuf, Error> {
+    xdg::BaseDirectories::with_prefix("com.system76.FirmwareManager")
+        .map_err(Error::BaseDirectory)?
+        .place_config_file(Path::new("config"))
+        .map_err(Error::Place)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Config;
+
+    #[test]
+    fn parse() {
+        assert_eq!(Config::parse(""), Config::default());
+        assert!(Config::parse("security_only_notifications = true").security_only_notifications);
+        assert!(
+            Config::parse("# comment\nsecurity_only_notifications=yes\n")
+                .security_only_notifications
+        );
+        assert!(!Config::parse("security_only_notifications = maybe").security_only_notifications);
+        assert!(!Config::parse("unknown = true").security_only_notifications);
+    }
+
+    #[test]
+    fn parse_offline() {
+        let config = Config::parse("offline = true\nfirmware_directory = /srv/firmware\n");
+        assert!(config.offline);
+        assert_eq!(config.firmware_directory.as_deref(), Some("/srv/firmware".as_ref()));
+        assert_eq!(Config::parse("firmware_directory =").firmware_directory, None);
+        assert!(!config.allow_unverified);
+        assert!(Config::parse("allow_unverified = yes").allow_unverified);
+    }
+
+    #[test]
+    fn round_trip() {
+        let config = Config {
+            allow_unverified: true,
+            firmware_directory: Some("/srv/firmware".into()),
+            offline: true,
+            security_only_notifications: true,
+        };
+        assert_eq!(Config::parse(&config.serialize()), config);
+    }
+}
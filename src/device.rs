@@ -0,0 +1,134 @@
+//! Flags and problems which fwupd reports for a device.
+
+use serde::{Deserialize, Serialize};
+
+/// Flags which fwupd assigns to a device, mirroring `FwupdDeviceFlags`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct DeviceFlags(u64);
+
+impl DeviceFlags {
+    /// The device is internal to the system.
+    pub const INTERNAL: Self = Self(1 << 0);
+    /// The firmware of the device can be updated.
+    pub const UPDATABLE: Self = Self(1 << 1);
+    /// The device can only be updated while the system is offline.
+    pub const ONLY_OFFLINE: Self = Self(1 << 2);
+    /// The system must be connected to AC power to update the device.
+    pub const REQUIRE_AC: Self = Self(1 << 3);
+    /// The device is locked, and must be unlocked before it can be updated.
+    pub const LOCKED: Self = Self(1 << 4);
+    /// The device is supported by fwupd's metadata.
+    pub const SUPPORTED: Self = Self(1 << 5);
+    /// The device must be in bootloader mode to be updated.
+    pub const NEEDS_BOOTLOADER: Self = Self(1 << 6);
+    /// The system must be rebooted to apply an update.
+    pub const NEEDS_REBOOT: Self = Self(1 << 8);
+    /// The parent of a composite device is to be installed before its children.
+    pub const INSTALL_PARENT_FIRST: Self = Self(1 << 12);
+    /// The system must be shut down to apply an update.
+    pub const NEEDS_SHUTDOWN: Self = Self(1 << 17);
+    /// An update has been staged, and must be activated to be applied.
+    pub const NEEDS_ACTIVATION: Self = Self(1 << 20);
+    /// The firmware of the device can be verified against its checksum.
+    pub const CAN_VERIFY: Self = Self(1 << 25);
+    /// The firmware image of the device can be read back to be verified.
+    pub const CAN_VERIFY_IMAGE: Self = Self(1 << 26);
+
+    /// Creates the flags from their raw bits.
+    pub const fn from_bits(bits: u64) -> Self { Self(bits) }
+
+    /// The raw bits of these flags.
+    pub fn bits(self) -> u64 { self.0 }
+
+    /// Returns `true` if all of the flags in `other` are set.
+    pub fn contains(self, other: Self) -> bool { self.0 & other.0 == other.0 }
+}
+
+/// A condition which may prevent a device from being updated, mirroring `FwupdDeviceProblem`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceProblem {
+    /// The system's battery is too low.
+    SystemPowerTooLow,
+    /// The device cannot be reached.
+    Unreachable,
+    /// The device's battery is too low.
+    PowerTooLow,
+    /// An update is waiting to be installed.
+    UpdatePending,
+    /// The system must be connected to AC power.
+    RequireAcPower,
+    /// The lid of the system is closed.
+    LidIsClosed,
+    /// The device is emulated.
+    IsEmulated,
+    /// The license of the firmware is missing.
+    MissingLicense,
+    /// Updates are inhibited by another program.
+    SystemInhibit,
+    /// An update is already in progress.
+    UpdateInProgress,
+    /// The device is in use.
+    InUse,
+    /// A display must be connected.
+    DisplayRequired,
+    /// Another plugin with a higher priority manages the device.
+    LowerPriority,
+}
+
+impl DeviceProblem {
+    const ALL: [(u64, DeviceProblem); 13] = [
+        (1 << 0, DeviceProblem::SystemPowerTooLow),
+        (1 << 1, DeviceProblem::Unreachable),
+        (1 << 2, DeviceProblem::PowerTooLow),
+        (1 << 3, DeviceProblem::UpdatePending),
+        (1 << 4, DeviceProblem::RequireAcPower),
+        (1 << 5, DeviceProblem::LidIsClosed),
+        (1 << 6, DeviceProblem::IsEmulated),
+        (1 << 7, DeviceProblem::MissingLicense),
+        (1 << 8, DeviceProblem::SystemInhibit),
+        (1 << 9, DeviceProblem::UpdateInProgress),
+        (1 << 10, DeviceProblem::InUse),
+        (1 << 11, DeviceProblem::DisplayRequired),
+        (1 << 12, DeviceProblem::LowerPriority),
+    ];
+
+    /// The problems set in the raw bits that fwupd reports.
+    pub fn from_bits(bits: u64) -> Vec<Self> {
+        Self::ALL.iter().filter(|&&(bit, _)| bits & bit != 0).map(|&(_, problem)| problem).collect()
+    }
+
+    /// Whether the problem prevents the device from being updated.
+    pub fn blocks_update(self) -> bool {
+        !matches!(self, DeviceProblem::IsEmulated | DeviceProblem::LowerPriority)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn problems() {
+        let problems = DeviceProblem::from_bits(1 << 5 | 1 << 11 | 1 << 12 | 1 << 40);
+        assert_eq!(
+            problems,
+            [
+                DeviceProblem::LidIsClosed,
+                DeviceProblem::DisplayRequired,
+                DeviceProblem::LowerPriority
+            ]
+        );
+
+        assert!(problems[0].blocks_update());
+        assert!(!problems[2].blocks_update());
+    }
+
+    #[test]
+    fn flags() {
+        let bits = DeviceFlags::LOCKED.bits() | DeviceFlags::UPDATABLE.bits();
+        let flags = DeviceFlags::from_bits(bits);
+        assert!(flags.contains(DeviceFlags::LOCKED));
+        assert!(!flags.contains(DeviceFlags::NEEDS_ACTIVATION));
+    }
+}
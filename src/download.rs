@@ -0,0 +1,112 @@
+//! Downloads firmware payloads into the cache.
+
+use crate::cache;
+use std::{
+    fs,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+
+/// An error that may occur when downloading a firmware payload.
+#[derive(Debug, Error)]
+pub enum Error {
+    /// The location of the payload in the cache could not be determined.
+    #[error("cache error")]
+    Cache(#[from] cache::Error),
+    /// The release does not say where its payload can be downloaded from.
+    #[error("release does not have a download location")]
+    NoLocation,
+    /// The payload is not available locally, and downloads are disabled while offline.
+    #[error("{0} is not available offline")]
+    Offline(Box<str>),
+    /// The request for the payload failed.
+    #[error("failed to request firmware from {0}")]
+    Request(Box<str>, #[source] Box<ureq::Error>),
+    /// The payload could not be read from the server.
+    #[error("failed to read firmware from server")]
+    Read(#[source] io::Error),
+    /// The payload could not be written to the cache.
+    #[error("failed to write firmware to cache")]
+    Write(#[source] io::Error),
+}
+
+/// The path in the cache where a payload with the given file name is stored.
+pub fn cached_path(filename: &str) -> Result<PathBuf, Error> {
+    cache::cache(Path::new("firmware").join(filename)).map_err(Error::Cache)
+}
+
+/// The local path of a payload, if `uri` refers to a local file rather than a remote one.
+///
+/// fwupd directory remotes refer to their payloads in this way.
+pub fn local_path(uri: &str) -> Option<&Path> {
+    uri.strip_prefix("file://")
+        .or_else(|| Some(uri).filter(|uri| uri.starts_with('/')))
+        .map(Path::new)
+}
+
+/// The file name of the payload at `uri`.
+pub fn filename(uri: &str) -> Option<&str> {
+    let path = uri.split(['?', '#']).next()?;
+    path.rsplit('/').next().filter(|name| !name.is_empty())
+}
+
+/// Downloads the payload at `uri` to `path`.
+///
+/// `begin` is called with the size of the payload, if it is known, and `progress` with the
+/// number of bytes received since its last call. The payload is written to a temporary file which
+/// is renamed once the download completes, so an interrupted download never leaves a partial
+/// payload at `path`.
+pub fn download<B: FnOnce(u64), P: FnMut(usize)>(
+    uri: &str,
+    path: &Path,
+    begin: B,
+    mut progress: P,
+) -> Result<(), Error> {
+    info!("downloading {} to {}", uri, path.display());
+
+    let response =
+        ureq::get(uri).call().map_err(|why| Error::Request(uri.into(), Box::new(why)))?;
+
+    let size = response.header("Content-Length").and_then(|size| size.parse::<u64>().ok());
+    begin(size.unwrap_or(0));
+
+    let partial = path.with_extension("part");
+    let mut file = fs::File::create(&partial).map_err(Error::Write)?;
+    let mut reader = response.into_reader();
+    let mut buffer = [0u8; 16 * 1024];
+
+    loop {
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(why) if why.kind() == io::ErrorKind::Interrupted => continue,
+            Err(why) => return Err(Error::Read(why)),
+        };
+
+        file.write_all(&buffer[..read]).map_err(Error::Write)?;
+        progress(read);
+    }
+
+    file.sync_all().map_err(Error::Write)?;
+    fs::rename(&partial, path).map_err(Error::Write)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn filename() {
+        assert_eq!(
+            super::filename("https://fwupd.org/downloads/abc-firmware.cab"),
+            Some("abc-firmware.cab")
+        );
+        assert_eq!(super::filename("https://example.com/a.cab?token=1"), Some("a.cab"));
+        assert_eq!(super::filename("https://example.com/"), None);
+    }
+
+    #[test]
+    fn local_path() {
+        assert_eq!(super::local_path("file:///srv/a.cab"), Some("/srv/a.cab".as_ref()));
+        assert_eq!(super::local_path("/srv/a.cab"), Some("/srv/a.cab".as_ref()));
+        assert_eq!(super::local_path("https://example.com/a.cab"), None);
+    }
+}
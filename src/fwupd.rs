@@ -1,8 +1,29 @@
 //! Functions specific to working with fwupd firmware.
 
-use crate::{FirmwareInfo, FirmwareSignal};
+use crate::{
+    composite::composite_root,
+    download::{self, Error as DownloadError},
+    history::{self, HistoryEntry, Outcome},
+    version_sorting::Versioned,
+    install_order, Checksum, Config, DeviceFlags, DeviceProblem, Entity, Error, FirmwareEvent,
+    FirmwareInfo, FirmwareSignal, MetadataAge, MetadataSource, ReleaseInfo, Verification, Version,
+    VersionFormat,
+};
+use dbus::{
+    arg::{prop_cast, AppendAll, PropMap, ReadAll},
+    blocking::Connection,
+};
 use fwupd_dbus::{Client as FwupdClient, Device as FwupdDevice, Release as FwupdRelease};
-use std::{cmp::Ordering, sync::mpsc::Sender};
+use std::{
+    collections::HashMap,
+    fs,
+    path::PathBuf,
+    sync::{mpsc::Sender, Arc},
+    time::Duration,
+};
+
+/// Seconds to wait for fwupd, which may need to wait for the user to authenticate.
+const FWUPD_TIMEOUT: u64 = 120;
 
 /// A signal sent when a fwupd-compatible device has been discovered.
 #[derive(Debug)]
@@ -15,13 +36,40 @@ pub struct FwupdSignal {
     pub upgradeable: bool,
     /// All releases that were found for the firmware.
     pub releases: Vec<FwupdRelease>,
+    /// Flags which fwupd assigned to the device.
+    pub flags: DeviceFlags,
+    /// Conditions which fwupd reported for the device.
+    pub problems: Vec<DeviceProblem>,
+    /// The ID of the parent device, if the device belongs to a composite device.
+    pub parent: Option<Box<str>>,
+}
+
+impl FwupdSignal {
+    /// An update was staged, and must be activated to be applied.
+    pub fn needs_activation(&self) -> bool { self.flags.contains(DeviceFlags::NEEDS_ACTIVATION) }
+
+    /// The device must be unlocked before it can be updated.
+    pub fn is_locked(&self) -> bool { self.flags.contains(DeviceFlags::LOCKED) }
+
+    /// The problems which currently prevent the device from being updated.
+    pub fn blocking_problems(&self) -> impl Iterator<Item = DeviceProblem> + '_ {
+        self.problems.iter().copied().filter(|problem| problem.blocks_update())
+    }
+
+    /// Whether fwupd can verify the firmware which is installed on the device.
+    pub fn can_verify(&self) -> bool { can_verify(&self.device) }
+
+    /// Whether the device can be updated to its latest release right now.
+    pub fn can_update(&self) -> bool {
+        self.upgradeable && !self.is_locked() && self.blocking_problems().next().is_none()
+    }
 }
 
 /// Scan for supported devices from the fwupd DBus daemon.
 pub fn fwupd_scan(fwupd: &FwupdClient, sender: Sender<FirmwareSignal>) {
     info!("scanning fwupd devices");
 
-    let devices = match fwupd.devices() {
+    let mut devices = match fwupd.devices() {
         Ok(devices) => devices,
         Err(why) => {
             let _res = sender.send(FirmwareSignal::Error(None, why.into()));
@@ -29,18 +77,43 @@ pub fn fwupd_scan(fwupd: &FwupdClient, sender: Sender<FirmwareSignal>) {
         }
     };
 
+    let details = fwupd_details();
+
+    // Parents are sent before their children, so that frontends may group children under them.
+    devices.sort_by_cached_key(|device| {
+        let mut id: &str = device.device_id.as_ref();
+        let mut depth = 0;
+        while let Some(parent) = details.get(id).and_then(|details| details.parent.as_deref()) {
+            id = parent;
+            depth += 1;
+            if depth > details.len() {
+                break;
+            }
+        }
+
+        depth
+    });
+
     for device in devices {
-        if device.is_supported() {
+        let flags = DeviceFlags::from_bits(device.flags.bits());
+
+        // Locked devices, and devices with staged updates, are listed so that the user can act on
+        // them, even if they are not updatable yet.
+        let actionable = flags.contains(DeviceFlags::LOCKED)
+            || flags.contains(DeviceFlags::NEEDS_ACTIVATION);
+
+        if device.is_supported() || actionable {
+            let format = version_format(&device);
             let releases = match fwupd.releases(&device) {
                 Ok(mut releases) => {
-                    crate::sort_versions(&mut releases);
+                    crate::sort_versions(format, &mut releases);
                     releases
                 }
                 Err(why) => {
                     error!(
                         "failure to get fwupd releases for {}: {}",
                         device.name,
-                        super::format_error(why)
+                        super::format_error(&why)
                     );
 
                     Vec::new()
@@ -48,23 +121,35 @@ pub fn fwupd_scan(fwupd: &FwupdClient, sender: Sender<FirmwareSignal>) {
             };
 
             let latest = releases.iter().last();
-            let upgradeable = latest.map_or(false, |latest| {
-                is_newer(&device.version, &latest.version)
-            });
-            let install_duration = latest.map_or(0, |latest| {
-                latest.install_duration
-            });
+            let install_duration = latest.map_or(0, |latest| latest.install_duration);
+
+            let info = FirmwareInfo {
+                name: [&device.vendor, " ", &device.name].concat().into(),
+                current: Some(&device.version)
+                    .filter(|version| !version.is_empty())
+                    .map(|version| Version::new(version.clone(), format)),
+                latest: latest.map(|latest| latest.versioned(format)),
+                install_duration,
+                releases: releases
+                    .iter()
+                    .map(|release| ReleaseInfo::from_fwupd(release, format))
+                    .collect(),
+            };
+
+            let upgradeable = info.is_upgradeable();
+            let details = details.get::<str>(device.device_id.as_ref());
+            let problems =
+                details.map_or_else(Vec::new, |details| DeviceProblem::from_bits(details.problems));
+            let parent = details.and_then(|details| details.parent.as_deref()).map(Box::from);
 
             let _res = sender.send(FirmwareSignal::Fwupd(FwupdSignal {
-                info: FirmwareInfo {
-                    name: [&device.vendor, " ", &device.name].concat().into(),
-                    current: device.version.clone(),
-                    latest: latest.map(|latest| latest.version.clone()),
-                    install_duration,
-                },
+                info,
                 device,
                 upgradeable,
                 releases,
+                flags,
+                problems,
+                parent,
             }));
         }
     }
@@ -72,52 +157,501 @@ pub fn fwupd_scan(fwupd: &FwupdClient, sender: Sender<FirmwareSignal>) {
     info!("fwupd scanning complete");
 }
 
-/// Update the fwupd remotes
-pub fn fwupd_updates(client: &FwupdClient) -> Result<(), fwupd_dbus::Error> {
+/// Activates a staged update of a device, which fwupd installs on its next boot or replug.
+pub fn fwupd_activate(entity: Entity, device: &FwupdDevice, sender: &Sender<FirmwareSignal>) {
+    let device_id: &str = device.device_id.as_ref();
+    let _res = sender.send(match fwupd_method::<_, ()>("Activate", (device_id,)) {
+        Ok(()) => FirmwareSignal::DeviceActivated(entity),
+        Err(why) => FirmwareSignal::Error(Some(entity), Error::Activate(why)),
+    });
+}
+
+/// Unlocks a device so that its firmware can be updated.
+pub fn fwupd_unlock(entity: Entity, device: &FwupdDevice, sender: &Sender<FirmwareSignal>) {
+    let device_id: &str = device.device_id.as_ref();
+    let _res = sender.send(match fwupd_method::<_, ()>("Unlock", (device_id,)) {
+        Ok(()) => FirmwareSignal::DeviceUnlocked(entity),
+        Err(why) => FirmwareSignal::Error(Some(entity), Error::Unlock(why)),
+    });
+}
+
+/// The result of verifying the firmware which is installed on a device.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Integrity {
+    /// The firmware matches its known-good checksums.
+    Passed,
+    /// The firmware does not match its known-good checksums, and may have been tampered with.
+    Failed(Box<str>),
+    /// There are no known-good checksums to verify the firmware against.
+    NoBaseline,
+}
+
+impl Integrity {
+    /// Interprets the error that fwupd returned from `Verify`.
+    ///
+    /// fwupd reports a mismatch as an authentication failure, and a lack of checksums as a failure
+    /// to find them. Any other error is a failure to verify the device at all.
+    fn from_error(name: Option<&str>, message: Option<&str>) -> Option<Self> {
+        match name? {
+            "org.freedesktop.fwupd.AuthFailed" => {
+                Some(Integrity::Failed(message.unwrap_or_default().into()))
+            }
+            "org.freedesktop.fwupd.NotFound" => Some(Integrity::NoBaseline),
+            _ => None,
+        }
+    }
+}
+
+/// Whether fwupd can verify the firmware which is installed on the device.
+pub fn can_verify(device: &FwupdDevice) -> bool {
+    let flags = DeviceFlags::from_bits(device.flags.bits());
+    flags.contains(DeviceFlags::CAN_VERIFY) || flags.contains(DeviceFlags::CAN_VERIFY_IMAGE)
+}
+
+/// Verifies the firmware installed on a device against its known-good checksums.
+///
+/// Checksums are taken from the baseline recorded by `fwupd_verify_baseline`, or else from the
+/// release metadata. Passes and failures are recorded in the history.
+pub fn fwupd_verify(entity: Entity, device: &FwupdDevice, sender: &Sender<FirmwareSignal>) {
+    let _res = sender.send(FirmwareSignal::DeviceVerifying(entity));
+
+    let device_id: &str = device.device_id.as_ref();
+    let integrity = match fwupd_method::<_, ()>("Verify", (device_id,)) {
+        Ok(()) => Integrity::Passed,
+        Err(why) => match Integrity::from_error(why.name(), why.message()) {
+            Some(integrity) => integrity,
+            None => {
+                let _res = sender.send(FirmwareSignal::Error(Some(entity), Error::Integrity(why)));
+                return;
+            }
+        },
+    };
+
+    let outcome = match integrity {
+        Integrity::Passed => Some(Outcome::Verified),
+        Integrity::Failed(ref why) => {
+            warn!("firmware of {} failed verification: {}", device.name, why);
+            Some(Outcome::VerifyFailed)
+        }
+        Integrity::NoBaseline => None,
+    };
+
+    if let Some(outcome) = outcome {
+        let entry = HistoryEntry {
+            timestamp: crate::timestamp::current(),
+            device_id: Box::from(device_id),
+            device: device.name.clone(),
+            previous: device.version.clone(),
+            version: device.version.clone(),
+            checksum: None,
+            outcome,
+        };
+
+        if let Err(why) = history::record(&entry) {
+            error!("failed to record verification of {} in history: {}", device.name, why);
+        }
+    }
+
+    let _res = sender.send(FirmwareSignal::DeviceVerified(entity, integrity));
+}
+
+/// Records the checksums of the firmware installed on a device as its known-good baseline.
+pub fn fwupd_verify_baseline(
+    entity: Entity,
+    device: &FwupdDevice,
+    sender: &Sender<FirmwareSignal>,
+) {
+    let device_id: &str = device.device_id.as_ref();
+    let _res = sender.send(match fwupd_method::<_, ()>("VerifyUpdate", (device_id,)) {
+        Ok(()) => FirmwareSignal::BaselineRecorded(entity),
+        Err(why) => FirmwareSignal::Error(Some(entity), Error::Baseline(why)),
+    });
+}
+
+/// Details of a device which fwupd-dbus does not expose.
+struct DeviceDetails {
+    /// The problems of the device, which versions of fwupd that predate them do not report.
+    problems: u64,
+    /// The ID of the parent device, if the device belongs to a composite device.
+    parent: Option<String>,
+}
+
+/// The details of each device which fwupd-dbus does not expose, by device ID.
+fn fwupd_details() -> HashMap<String, DeviceDetails> {
+    let devices = match fwupd_method::<_, (Vec<PropMap>,)>("GetDevices", ()) {
+        Ok((devices,)) => devices,
+        Err(why) => {
+            warn!("failed to get details of fwupd devices: {}", why);
+            return HashMap::new();
+        }
+    };
+
+    devices
+        .iter()
+        .filter_map(|device| {
+            let id = prop_cast::<String>(device, "DeviceId")?;
+            let details = DeviceDetails {
+                problems: prop_cast::<u64>(device, "Problems").copied().unwrap_or(0),
+                parent: prop_cast::<String>(device, "ParentDeviceId").cloned(),
+            };
+
+            Some((id.clone(), details))
+        })
+        .collect()
+}
+
+/// Calls a method of the fwupd daemon which fwupd-dbus does not provide.
+fn fwupd_method<A: AppendAll, R: ReadAll>(method: &str, args: A) -> Result<R, dbus::Error> {
+    let connection = Connection::new_system()?;
+    let proxy =
+        connection.with_proxy("org.freedesktop.fwupd", "/", Duration::from_secs(FWUPD_TIMEOUT));
+    proxy.method_call("org.freedesktop.fwupd", method, args)
+}
+
+/// Update the fwupd remotes, if they were last refreshed more than a day ago.
+pub fn fwupd_updates(client: &FwupdClient) -> Result<(), Error> {
     const SECONDS_IN_DAY: u64 = 60 * 60 * 24;
 
     if crate::timestamp::exceeded(SECONDS_IN_DAY).ok().unwrap_or(true) {
-        info!("refreshing remotes");
+        fwupd_refresh(client)?;
+    }
+
+    Ok(())
+}
 
-        if let Err(why) = crate::timestamp::refresh() {
-            error!("failed to update timestamp: {}", why);
+/// Refreshes the metadata of every enabled download remote, regardless of when it was last
+/// refreshed.
+///
+/// Every remote is refreshed even if others fail, but an error is returned if any did.
+pub fn fwupd_refresh(client: &FwupdClient) -> Result<(), Error> {
+    info!("refreshing remotes");
+
+    if let Err(why) = crate::timestamp::refresh() {
+        error!("failed to update timestamp: {}", why);
+    }
+
+    let mut total = 0;
+    let mut failed = 0;
+    let mut first_failure = None;
+
+    // NOTE: This attribute is required due to a clippy bug.
+    #[allow(clippy::identity_conversion)]
+    for remote in client.remotes()? {
+        if !remote.enabled {
+            continue;
         }
 
-        // NOTE: This attribute is required due to a clippy bug.
-        #[allow(clippy::identity_conversion)]
-        for remote in client.remotes()? {
-            if !remote.enabled {
-                continue;
+        if let fwupd_dbus::RemoteKind::Download = remote.kind {
+            info!("Updating {:?} metadata from {:?}", remote.remote_id, remote.uri);
+            total += 1;
+            if let Err(why) = remote.update_metadata(client) {
+                error!(
+                    "failed to fetch updates from {}: {:?}",
+                    remote.filename_cache,
+                    super::format_error(&why)
+                );
+                failed += 1;
+                first_failure.get_or_insert(why);
             }
+        }
+    }
 
-            if let fwupd_dbus::RemoteKind::Download = remote.kind {
-                info!("Updating {:?} metadata from {:?}", remote.remote_id, remote.uri);
-                if let Err(why) = remote.update_metadata(client) {
-                    error!(
-                        "failed to fetch updates from {}: {:?}",
-                        remote.filename_cache,
-                        super::format_error(why)
-                    );
+    match first_failure {
+        None => Ok(()),
+        Some(source) => Err(Error::RemotesFailed { failed, total, source }),
+    }
+}
+
+/// Describes how current the metadata of the fwupd remotes is.
+///
+/// The remotes are refreshed at most once a day, so the time of the last refresh is read from the
+/// timestamp that `fwupd_updates` keeps.
+pub fn fwupd_metadata_age(offline: bool) -> MetadataAge {
+    MetadataAge {
+        source: MetadataSource::Fwupd,
+        refreshed: crate::timestamp::last().ok().filter(|&timestamp| timestamp != 0),
+        offline,
+    }
+}
+
+/// The events which update fwupd devices, grouping the devices of each composite device.
+///
+/// Devices which belong to different composite devices, or to none, are updated by separate
+/// events, so that the failure of one update does not skip the others.
+pub fn fwupd_update_events(
+    devices: Vec<(Entity, Arc<FwupdDevice>, Arc<FwupdRelease>)>,
+) -> Vec<FirmwareEvent> {
+    let details = fwupd_details();
+    let parent = |id: &str| details.get(id).and_then(|details| details.parent.as_deref());
+
+    let roots = devices
+        .iter()
+        .map(|(_, device, _)| composite_root(device.device_id.as_ref(), parent).to_owned())
+        .collect::<Vec<_>>();
+
+    let mut groups: Vec<(String, Vec<_>)> = Vec::new();
+    for (root, update) in roots.into_iter().zip(devices) {
+        match groups.iter_mut().find(|(other, _)| *other == root) {
+            Some((_, group)) => group.push(update),
+            None => groups.push((root, vec![update])),
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(|(_, mut group)| {
+            if group.len() == 1 {
+                let (entity, device, release) = group.remove(0);
+                FirmwareEvent::Fwupd(entity, device, release)
+            } else {
+                FirmwareEvent::FwupdComposite(group)
+            }
+        })
+        .collect()
+}
+
+/// Updates the devices of a composite device in the order that fwupd requires.
+///
+/// Once an update fails, the devices which remain are not updated, as they may depend on it.
+pub fn fwupd_update_composite(
+    client: &FwupdClient,
+    devices: &[(Entity, Arc<FwupdDevice>, Arc<FwupdRelease>)],
+    config: &Config,
+    sender: &Sender<FirmwareSignal>,
+) {
+    let details = fwupd_details();
+
+    let members = devices
+        .iter()
+        .map(|(_, device, _)| {
+            let id: &str = device.device_id.as_ref();
+            let parent = details.get(id).and_then(|details| details.parent.as_deref());
+            (id, parent, DeviceFlags::from_bits(device.flags.bits()))
+        })
+        .collect::<Vec<_>>();
+
+    let mut failed: Option<&str> = None;
+
+    for index in install_order(&members) {
+        let (entity, ref device, ref release) = devices[index];
+        match failed {
+            Some(failed) => {
+                let why = Error::CompositeSkipped(failed.into());
+                let _res = sender.send(FirmwareSignal::Error(Some(entity), why));
+            }
+            None => {
+                if !fwupd_update(client, entity, device, release, config, sender) {
+                    failed = Some(&device.name);
                 }
             }
         }
     }
+}
+
+/// Downloads, verifies, and installs a release of firmware for a fwupd device.
+///
+/// Progress and the result are sent as signals, and the attempt is recorded in the history.
+/// Returns whether the firmware was installed.
+pub fn fwupd_update(
+    client: &FwupdClient,
+    entity: Entity,
+    device: &FwupdDevice,
+    release: &FwupdRelease,
+    config: &Config,
+    sender: &Sender<FirmwareSignal>,
+) -> bool {
+    let mut checksum = None;
+    let result = fwupd_install(client, entity, device, release, config, sender, &mut checksum);
+
+    let outcome = match result {
+        Ok(()) => Outcome::Installed,
+        Err(Error::ChecksumMismatch { .. }) => Outcome::ChecksumMismatch,
+        Err(_) => Outcome::Failed,
+    };
+
+    let entry = HistoryEntry {
+        timestamp: crate::timestamp::current(),
+        device_id: Box::from(device.device_id.as_ref()),
+        device: device.name.clone(),
+        previous: device.version.clone(),
+        version: release.version.clone(),
+        checksum,
+        outcome,
+    };
+
+    if let Err(why) = history::record(&entry) {
+        error!("failed to record update of {} in history: {}", device.name, why);
+    }
+
+    let installed = result.is_ok();
+    let _res = sender.send(match result {
+        Ok(()) => FirmwareSignal::DeviceUpdated(entity),
+        Err(why) => FirmwareSignal::Error(Some(entity), why),
+    });
+
+    installed
+}
+
+fn fwupd_install(
+    client: &FwupdClient,
+    entity: Entity,
+    device: &FwupdDevice,
+    release: &FwupdRelease,
+    config: &Config,
+    sender: &Sender<FirmwareSignal>,
+    checksum: &mut Option<Checksum>,
+) -> Result<(), Error> {
+    let (path, cached) = fwupd_payload(entity, release, config, sender)?;
+
+    let _res = sender.send(FirmwareSignal::Verifying(entity));
+
+    let verification = Verification::of_file(&path, &release.checksums).map_err(Error::Verify)?;
+    *checksum = Some(verification.checksum().clone());
+    let _res = sender.send(FirmwareSignal::Verified(entity, verification.clone()));
+
+    if let Verification::Mismatch { expected, actual } = verification {
+        // A corrupted payload must not be reused by a later attempt, but payloads which the user
+        // provided are left for them to inspect.
+        if cached {
+            let _res = fs::remove_file(&path);
+        }
+
+        return Err(Error::ChecksumMismatch { expected, actual });
+    }
+
+    if let Verification::Unverified(actual) = verification {
+        if !config.allow_unverified {
+            return Err(Error::Unverified(actual));
+        }
+
+        warn!("installing firmware of {} which could not be verified: {}", device.name, actual);
+    }
+
+    let _res = sender.send(FirmwareSignal::DeviceFlashing(entity));
+    client.install(device, "system-update", &path, None, fwupd_dbus::InstallFlags::empty())?;
 
     Ok(())
 }
 
-// Returns `true` if the `latest` string is a newer version than the `current` string.
-fn is_newer(current: &str, latest: &str) -> bool {
-    human_sort::compare(current, latest) == Ordering::Less
+/// Locates the payload of a release, downloading it into the cache only if it is not available
+/// locally.
+///
+/// Payloads are looked for in the local remote that the release refers to, then in the configured
+/// firmware directory, and then in the cache. The returned flag is set if the payload is owned by
+/// the cache.
+fn fwupd_payload(
+    entity: Entity,
+    release: &FwupdRelease,
+    config: &Config,
+    sender: &Sender<FirmwareSignal>,
+) -> Result<(PathBuf, bool), Error> {
+    if let Some(path) = download::local_path(&release.uri) {
+        return Ok((path.to_owned(), false));
+    }
+
+    let filename = download::filename(&release.uri).ok_or(DownloadError::NoLocation)?;
+
+    if let Some(ref directory) = config.firmware_directory {
+        let path = directory.join(filename);
+        if path.exists() {
+            return Ok((path, false));
+        }
+    }
+
+    let path = download::cached_path(filename)?;
+
+    // A payload which was downloaded previously can be reinstalled, provided that it is intact.
+    let reusable = path.exists()
+        && Verification::of_file(&path, &release.checksums)
+            .map_or(false, |verification| !matches!(verification, Verification::Mismatch { .. }));
+
+    if reusable {
+        info!("using cached firmware at {}", path.display());
+    } else if config.offline {
+        return Err(DownloadError::Offline(filename.into()).into());
+    } else {
+        download::download(
+            &release.uri,
+            &path,
+            |size| {
+                let _res = sender.send(FirmwareSignal::DownloadBegin(entity, size));
+            },
+            |read| {
+                let _res = sender.send(FirmwareSignal::DownloadUpdate(entity, read));
+            },
+        )?;
+
+        let _res = sender.send(FirmwareSignal::DownloadComplete(entity));
+    }
+
+    Ok((path, true))
+}
+
+/// The format that fwupd reports for the versions of this device.
+pub fn version_format(device: &FwupdDevice) -> VersionFormat {
+    VersionFormat::from_fwupd(device.version_format as u32)
 }
 
 #[cfg(test)]
 mod tests {
+    use super::Integrity;
+    use crate::{FirmwareInfo, Version, VersionFormat};
+
+    fn is_newer(current: &str, latest: &str, format: VersionFormat) -> bool {
+        let info = FirmwareInfo {
+            name: "device".into(),
+            current: Some(Version::new(current, format)),
+            latest: Some(Version::new(latest, format)),
+            install_duration: 0,
+            releases: Vec::new(),
+        };
+
+        info.is_upgradeable()
+    }
+
+    #[test]
+    pub fn is_newer_test() {
+        for &format in &[VersionFormat::Plain, VersionFormat::Triplet] {
+            assert!(is_newer("0.2.8", "0.2.11", format));
+            assert!(!is_newer("0.2.11", "0.2.8", format));
+            assert!(is_newer("0.2.7", "0.2.8", format));
+            assert!(!is_newer("0.2.8", "0.2.7", format));
+        }
+
+        assert!(is_newer("0x0000000f", "0x00000010", VersionFormat::Hex));
+        assert!(!is_newer("1.0", "1.0.0", VersionFormat::Triplet));
+    }
+
     #[test]
-    pub fn is_newer() {
-        assert!(super::is_newer("0.2.8", "0.2.11"));
-        assert!(!super::is_newer("0.2.11", "0.2.8"));
-        assert!(super::is_newer("0.2.7", "0.2.8"));
-        assert!(!super::is_newer("0.2.8", "0.2.7"));
+    fn unknown_version_is_upgradeable() {
+        let mut info = FirmwareInfo {
+            name: "device".into(),
+            current: None,
+            latest: Some(Version::plain("0.1")),
+            install_duration: 0,
+            releases: Vec::new(),
+        };
+
+        assert!(info.is_upgradeable());
+        assert_eq!(info.current_label(), "N/A");
+
+        info.latest = None;
+        assert!(!info.is_upgradeable());
+    }
+
+    #[test]
+    fn integrity() {
+        let failed = Integrity::from_error(
+            Some("org.freedesktop.fwupd.AuthFailed"),
+            Some("expected abc, got def"),
+        );
+
+        assert_eq!(failed, Some(Integrity::Failed("expected abc, got def".into())));
+
+        let none = Integrity::from_error(Some("org.freedesktop.fwupd.NotFound"), None);
+        assert_eq!(none, Some(Integrity::NoBaseline));
+
+        assert_eq!(Integrity::from_error(Some("org.freedesktop.fwupd.Internal"), None), None);
+        assert_eq!(Integrity::from_error(None, None), None);
     }
 }
@@ -0,0 +1,210 @@
+//! A record of the firmware updates and verifications which have been attempted on this system.
+//!
+//! Each entry is stored as a line of tab-separated fields in the XDG data directory.
+
+use crate::Checksum;
+use std::{
+    fmt, fs,
+    io::{self, Write},
+    path::PathBuf,
+    str::FromStr,
+};
+
+/// An error that may occur when reading or writing the history.
+#[derive(Debug, Error)]
+pub enum Error {
+    /// The XDG base directories could not be determined.
+    #[error("failed to get XDG base directory")]
+    BaseDirectory(#[from] xdg::BaseDirectoriesError),
+    /// The data directory could not be created.
+    #[error("failed to get data directory")]
+    Place(#[source] io::Error),
+    /// The history exists but could not be read.
+    #[error("failed to read history")]
+    Read(#[source] io::Error),
+    /// An entry could not be appended to the history.
+    #[error("failed to write history")]
+    Write(#[source] io::Error),
+}
+
+/// The outcome of an attempted firmware update.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Outcome {
+    /// The firmware was installed.
+    Installed,
+    /// The downloaded payload did not match the checksum in the release metadata.
+    ChecksumMismatch,
+    /// The update failed for another reason.
+    Failed,
+    /// The installed firmware matched its known-good checksums.
+    Verified,
+    /// The installed firmware did not match its known-good checksums.
+    VerifyFailed,
+}
+
+impl Outcome {
+    fn as_str(self) -> &'static str {
+        match self {
+            Outcome::Installed => "installed",
+            Outcome::ChecksumMismatch => "checksum-mismatch",
+            Outcome::Failed => "failed",
+            Outcome::Verified => "verified",
+            Outcome::VerifyFailed => "verify-failed",
+        }
+    }
+}
+
+impl FromStr for Outcome {
+    type Err = ();
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "installed" => Ok(Outcome::Installed),
+            "checksum-mismatch" => Ok(Outcome::ChecksumMismatch),
+            "failed" => Ok(Outcome::Failed),
+            "verified" => Ok(Outcome::Verified),
+            "verify-failed" => Ok(Outcome::VerifyFailed),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { f.write_str(self.as_str()) }
+}
+
+/// A record of an attempted firmware update, or of a verification of the installed firmware.
+///
+/// Verifications record the installed version as both the previous and the new version.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HistoryEntry {
+    /// When the update was attempted, in seconds since the UNIX Epoch.
+    pub timestamp: u64,
+    /// The identifier of the device that the service assigned.
+    pub device_id: Box<str>,
+    /// The name of the device.
+    pub device: Box<str>,
+    /// The version which was installed before the update.
+    pub previous: Box<str>,
+    /// The version which the update installs.
+    pub version: Box<str>,
+    /// The checksum of the payload which was installed, if it was downloaded.
+    pub checksum: Option<Checksum>,
+    /// Whether the update succeeded.
+    pub outcome: Outcome,
+}
+
+impl HistoryEntry {
+    fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.split('\t');
+        let mut next = || fields.next().map(Box::<str>::from);
+
+        let timestamp = next()?.parse().ok()?;
+        let device_id = next()?;
+        let device = next()?;
+        let previous = next()?;
+        let version = next()?;
+        let checksum = next()?;
+        let outcome = next()?.parse().ok()?;
+
+        Some(Self {
+            timestamp,
+            device_id,
+            device,
+            previous,
+            version,
+            checksum: checksum.parse().ok(),
+            outcome,
+        })
+    }
+}
+
+/// Writes the entry as a single line, without a trailing newline.
+impl fmt::Display for HistoryEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let checksum = self.checksum.as_ref().map_or_else(|| "-".to_owned(), Checksum::to_string);
+
+        write!(
+            f,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            self.timestamp,
+            escape(&self.device_id),
+            escape(&self.device),
+            escape(&self.previous),
+            escape(&self.version),
+            checksum,
+            self.outcome
+        )
+    }
+}
+
+/// Appends an entry to the history.
+pub fn record(entry: &HistoryEntry) -> Result<(), Error> {
+    let path = history_path()?;
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(Error::Write)?;
+
+    writeln!(file, "{}", entry).map_err(Error::Write)
+}
+
+/// Reads every entry in the history, from oldest to newest.
+///
+/// Lines which could not be parsed are skipped.
+pub fn history() -> Result<Vec<HistoryEntry>, Error> {
+    let path = history_path()?;
+    match fs::read_to_string(&path) {
+        Ok(data) => Ok(data.lines().filter_map(HistoryEntry::parse).collect()),
+        Err(why) if why.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(why) => Err(Error::Read(why)),
+    }
+}
+
+/// Tabs and newlines separate fields and entries, so they are replaced with spaces.
+fn escape(field: &str) -> String { field.replace(['\t', '\n', '\r'], " ") }
+
+/// Convenience function for fetching the path to the history file.
+fn history_path() -> Result<PathBuf, Error> {
+    xdg::BaseDirectories::with_prefix("com.system76.FirmwareManager")
+        .map_err(Error::BaseDirectory)?
+        .place_data_file("history")
+        .map_err(Error::Place)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let entry = HistoryEntry {
+            timestamp: 1_580_688_000,
+            device_id: "0123456789abcdef".into(),
+            device: "Thunderbolt\tController".into(),
+            previous: "1.0".into(),
+            version: "1.1".into(),
+            checksum: "a9993e364706816aba3e25717850c26c9cd0d89d".parse().ok(),
+            outcome: Outcome::Installed,
+        };
+
+        let line = entry.to_string();
+        let parsed = HistoryEntry::parse(&line).unwrap();
+        assert_eq!(&*parsed.device, "Thunderbolt Controller");
+        assert_eq!(parsed, HistoryEntry { device: parsed.device.clone(), ..entry.clone() });
+
+        let entry = HistoryEntry { checksum: None, outcome: Outcome::Failed, ..parsed };
+        assert_eq!(HistoryEntry::parse(&entry.to_string()).unwrap(), entry);
+
+        let entry = HistoryEntry { outcome: Outcome::VerifyFailed, ..entry };
+        assert_eq!(HistoryEntry::parse(&entry.to_string()).unwrap(), entry);
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert!(HistoryEntry::parse("").is_none());
+        assert!(HistoryEntry::parse("abc\tid\tname\t1\t2\t-\tinstalled").is_none());
+        assert!(HistoryEntry::parse("0\tid\tname\t1\t2\t-\tunknown").is_none());
+    }
+}
@@ -0,0 +1,220 @@
+//! The identity of this system, as its firmware describes it in the DMI/SMBIOS tables.
+
+use serde::{Deserialize, Serialize};
+use std::{fmt, fs, path::Path};
+
+/// Where sysfs is mounted on a running system.
+pub const SYSFS_ROOT: &str = "/sys";
+
+/// The vendor that System76 systems report in their DMI tables.
+const SYSTEM76_VENDOR: &str = "System76";
+
+/// How the system firmware booted the system.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FirmwareMode {
+    /// Booted through UEFI, which may install capsule updates.
+    Uefi,
+    /// Booted through a legacy BIOS, or compatibility support module.
+    Legacy,
+}
+
+impl FirmwareMode {
+    /// The name of the mode, as it appears in reports.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FirmwareMode::Uefi => "uefi",
+            FirmwareMode::Legacy => "legacy",
+        }
+    }
+}
+
+impl fmt::Display for FirmwareMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { f.write_str(self.as_str()) }
+}
+
+/// The system, its board, chassis, and firmware, as they are described by DMI.
+///
+/// Each field is `None` if the firmware does not describe it, or it could not be read. Serial
+/// numbers are deliberately omitted, so that the identity may be shared in reports.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SystemIdentity {
+    /// The vendor of the system, such as `System76`.
+    pub sys_vendor:      Option<Box<str>>,
+    /// The name of the product, such as `Oryx Pro`.
+    pub product_name:    Option<Box<str>>,
+    /// The version of the product, which System76 uses for the model, such as `oryp6`.
+    pub product_version: Option<Box<str>>,
+    /// The vendor of the system firmware.
+    pub bios_vendor:     Option<Box<str>>,
+    /// The version of the system firmware.
+    pub bios_version:    Option<Box<str>>,
+    /// The release date of the system firmware, as the firmware formats it.
+    pub bios_date:       Option<Box<str>>,
+    /// The vendor of the board.
+    pub board_vendor:    Option<Box<str>>,
+    /// The name of the board.
+    pub board_name:      Option<Box<str>>,
+    /// The version of the board.
+    pub board_version:   Option<Box<str>>,
+    /// The vendor of the chassis.
+    pub chassis_vendor:  Option<Box<str>>,
+    /// The SMBIOS chassis type, such as 9 for a laptop or 3 for a desktop.
+    pub chassis_type:    Option<u8>,
+    /// The version of the chassis.
+    pub chassis_version: Option<Box<str>>,
+    /// How the system firmware booted the system.
+    pub firmware_mode:   FirmwareMode,
+}
+
+impl SystemIdentity {
+    /// Reads the identity of this system.
+    pub fn read() -> Self { Self::read_from(Path::new(SYSFS_ROOT)) }
+
+    /// Reads the identity from a sysfs tree at `root`, which tests may use in place of `/sys`.
+    pub fn read_from(root: &Path) -> Self {
+        let dmi = root.join("class/dmi/id");
+        let field = |name: &str| read_field(&dmi.join(name));
+
+        Self {
+            sys_vendor:      field("sys_vendor"),
+            product_name:    field("product_name"),
+            product_version: field("product_version"),
+            bios_vendor:     field("bios_vendor"),
+            bios_version:    field("bios_version"),
+            bios_date:       field("bios_date"),
+            board_vendor:    field("board_vendor"),
+            board_name:      field("board_name"),
+            board_version:   field("board_version"),
+            chassis_vendor:  field("chassis_vendor"),
+            chassis_type:    field("chassis_type").and_then(|kind| kind.parse().ok()),
+            chassis_version: field("chassis_version"),
+            firmware_mode:   if root.join("firmware/efi").is_dir() {
+                FirmwareMode::Uefi
+            } else {
+                FirmwareMode::Legacy
+            },
+        }
+    }
+
+    /// A name for the system, from its vendor, product name, and product version.
+    ///
+    /// `None` if the vendor or product name are unknown.
+    pub fn name(&self) -> Option<String> {
+        let vendor = self.sys_vendor.as_deref()?;
+        let product = self.product_name.as_deref()?;
+
+        Some(match self.product_version.as_deref() {
+            Some(version) => [vendor, " ", product, " (", version, ")"].concat(),
+            None => [vendor, " ", product].concat(),
+        })
+    }
+
+    /// Whether the system was made by System76.
+    pub fn is_system76(&self) -> bool { self.sys_vendor.as_deref() == Some(SYSTEM76_VENDOR) }
+
+    /// The System76 model of the system, such as `oryp6`, if it was made by System76.
+    pub fn system76_model(&self) -> Option<&str> {
+        self.product_version.as_deref().filter(|_| self.is_system76())
+    }
+
+    /// Whether the System76 model of this system is `model`.
+    pub fn matches_system76_model(&self, model: &str) -> bool {
+        self.system76_model().map_or(false, |known| known.eq_ignore_ascii_case(model.trim()))
+    }
+}
+
+/// The serial numbers of the system, board, and chassis, which are not part of its identity.
+pub(crate) fn serials(root: &Path) -> Vec<String> {
+    let dmi = root.join("class/dmi/id");
+    ["product_serial", "board_serial", "chassis_serial"]
+        .iter()
+        .filter_map(|name| read_field(&dmi.join(name)))
+        .map(String::from)
+        .collect()
+}
+
+/// Reads a DMI field, which is absent if it is empty or unreadable.
+///
+/// Most fields are readable by any user, whereas serials are only readable by root.
+fn read_field(path: &Path) -> Option<Box<str>> {
+    let value = fs::read_to_string(path).ok()?;
+    let value = value.trim();
+    if value.is_empty() {
+        None
+    } else {
+        Some(value.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn fake_sysfs(name: &str, fields: &[(&str, &str)], efi: bool) -> PathBuf {
+        let root = std::env::temp_dir().join(format!(
+            "firmware-manager-sysfs-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let dmi = root.join("class/dmi/id");
+        fs::create_dir_all(&dmi).unwrap();
+        for (field, value) in fields {
+            fs::write(dmi.join(field), [value, "\n"].concat()).unwrap();
+        }
+
+        if efi {
+            fs::create_dir_all(root.join("firmware/efi")).unwrap();
+        }
+
+        root
+    }
+
+    #[test]
+    fn system76_identity() {
+        let root = fake_sysfs(
+            "system76",
+            &[
+                ("sys_vendor", "System76"),
+                ("product_name", "Oryx Pro"),
+                ("product_version", "oryp6"),
+                ("bios_vendor", "coreboot"),
+                ("bios_version", "2020-11-18_c3e5b1a"),
+                ("bios_date", "11/18/2020"),
+                ("board_name", "Oryx Pro"),
+                ("chassis_type", "10"),
+                ("chassis_version", ""),
+                ("product_serial", "A1B2C3D4E5"),
+            ],
+            true,
+        );
+
+        let identity = SystemIdentity::read_from(&root);
+        assert_eq!(identity.name().as_deref(), Some("System76 Oryx Pro (oryp6)"));
+        assert_eq!(identity.bios_version.as_deref(), Some("2020-11-18_c3e5b1a"));
+        assert_eq!(identity.chassis_type, Some(10));
+        assert_eq!(identity.chassis_version, None);
+        assert_eq!(identity.board_vendor, None);
+        assert_eq!(identity.firmware_mode, FirmwareMode::Uefi);
+        assert!(identity.matches_system76_model("oryp6\n"));
+        assert!(!identity.matches_system76_model("galp5"));
+        assert_eq!(serials(&root), vec!["A1B2C3D4E5".to_owned()]);
+
+        let _res = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn other_identity() {
+        let root =
+            fake_sysfs("other", &[("sys_vendor", "LENOVO"), ("product_name", "20XW")], false);
+
+        let identity = SystemIdentity::read_from(&root);
+        assert_eq!(identity.name().as_deref(), Some("LENOVO 20XW"));
+        assert_eq!(identity.system76_model(), None);
+        assert_eq!(identity.firmware_mode, FirmwareMode::Legacy);
+
+        let _res = fs::remove_dir_all(&root);
+        assert_eq!(SystemIdentity::read_from(&root).name(), None);
+    }
+}
@@ -12,10 +12,30 @@ extern crate log;
 #[macro_use]
 extern crate shrinkwraprs;
 
+mod authorization;
 mod cache;
+mod changelog;
+mod checksum;
+mod composite;
+mod config;
+mod device;
+mod download;
+mod history;
+mod identity;
+mod lock;
+mod metadata;
+mod recording;
+mod release;
+mod report;
+mod request;
+mod schema;
+mod security;
+mod service;
+mod session;
 mod timestamp;
 mod udev;
 mod users;
+mod version;
 mod version_sorting;
 
 mod fwupd;
@@ -33,13 +53,60 @@ pub use system76_firmware_daemon::{
 };
 
 use self::version_sorting::sort_versions;
-pub use self::{fwupd::*, system76::*, udev::usb_hotplug_event_loop};
+pub use self::{
+    authorization::{authorize, authorize_client, check_authorization, Action, Authorization},
+    changelog::{Block, Changelog, Inline},
+    checksum::{Checksum, ChecksumKind, Verification},
+    composite::install_order,
+    config::Config,
+    device::{DeviceFlags, DeviceProblem},
+    download::Error as DownloadError,
+    fwupd::*,
+    history::{history, Error as HistoryError, HistoryEntry, Outcome},
+    identity::{FirmwareMode, SystemIdentity, SYSFS_ROOT},
+    lock::{
+        lock_holder, lock_holder_at, Error as LockError, LockHolder, LockOperation,
+        OperationLock, LOCK_PATH,
+    },
+    metadata::{MetadataAge, MetadataSource},
+    recording::{
+        record_event_loop, replay_event_loop, Entry, Error as RecordingError, Recorder, Recording,
+        RECORD_VARIABLE, REDACTED, REPLAY_VARIABLE,
+    },
+    release::{ReleaseDate, ReleaseFlags, ReleaseInfo, ReleaseRange, Severity, Urgency},
+    report::{DeviceReport, Report, ReportFormat},
+    request::{DeviceRequest, RequestKind, WellKnownRequest},
+    schema::{
+        BoardTarget, DeviceTarget, EntityId, EventData, FwupdData, InfoData, IntegrityData,
+        Record, ReleaseData, RequestData, SignalData, ThelioIoData, UpdateTarget,
+        VerificationData, SCHEMA_VERSION,
+    },
+    security::{
+        host_security, Error as SecurityError, HostSecurity, Remediation, SecurityAttribute,
+        SecurityChange, SecurityResult, SecuritySnapshot,
+    },
+    service::{
+        decode_record, encode_record, service_event_loop, Bus, DeviceArg, EntityArg, HistoryArg,
+        Operation, OperationArg, ServiceClient, ServiceDevice, ServiceRequest, ServiceSignal,
+        Stage, ERROR_BUSY, ERROR_NOT_FOUND, SERVICE_INTERFACE, SERVICE_NAME, SERVICE_PATH,
+    },
+    session::{session_event_loop, SessionEvent, SESSION_SETTLE},
+    system76::*,
+    udev::{
+        debounce_hotplug, hotplug_event_loop, hotplug_event_loop_with, hotplug_events,
+        usb_hotplug_event_loop, Error as HotplugError, HotplugAction, HotplugConfig, HotplugDevice,
+        HotplugFilter,
+    },
+    version::{Version, VersionFormat},
+};
 pub use slotmap::DefaultKey as Entity;
 use slotmap::{SlotMap, SparseSecondaryMap};
 use std::{
+    collections::HashMap,
     io,
+    path::Path,
     process::Command,
-    sync::{mpsc::{Receiver, Sender}, Arc, atomic::{AtomicBool, Ordering}},
+    sync::{mpsc::{Receiver, Sender}, Arc, Mutex, atomic::{AtomicBool, Ordering}},
 };
 pub use system76_firmware_daemon::Client as System76Client;
 
@@ -49,17 +116,91 @@ pub enum Error {
     /// Errors specific to fwupd devices.
     #[error("error in fwupd client")]
     Fwupd(#[from] fwupd_dbus::Error),
+    /// The metadata of some fwupd remotes could not be refreshed.
+    #[error("{failed} of {total} fwupd remotes could not be refreshed")]
+    RemotesFailed {
+        /// The number of remotes which could not be refreshed.
+        failed: usize,
+        /// The number of remotes which were to be refreshed.
+        total: usize,
+        /// Why the first of them could not be refreshed.
+        #[source]
+        source: fwupd_dbus::Error,
+    },
     /// Errors specific to system76 devices.
     #[error("error in system76-firmware client")]
     System76(#[from] System76Error),
+    /// Firmware could not be downloaded.
+    #[error("failed to download firmware")]
+    Download(#[from] DownloadError),
+    /// The checksum of downloaded firmware could not be computed.
+    #[error("failed to verify downloaded firmware")]
+    Verify(#[source] io::Error),
+    /// Downloaded firmware did not match the checksum in its release metadata.
+    #[error("checksum of downloaded firmware did not match: expected {expected}, found {actual}")]
+    ChecksumMismatch {
+        /// The checksum given by the release metadata.
+        expected: Checksum,
+        /// The checksum of the firmware that was downloaded.
+        actual: Checksum,
+    },
+    /// The release metadata had no checksum to verify downloaded firmware against, and installing
+    /// unverified firmware was not allowed by the config.
+    #[error("firmware was not installed, as its release has no checksum to verify it against")]
+    Unverified(Checksum),
+    /// fwupd failed to activate a staged update.
+    #[error("failed to activate device")]
+    Activate(#[source] dbus::Error),
+    /// fwupd failed to verify the firmware installed on a device.
+    #[error("failed to verify firmware of device")]
+    Integrity(#[source] dbus::Error),
+    /// fwupd failed to record the known-good checksums of a device.
+    #[error("failed to record known-good firmware of device")]
+    Baseline(#[source] dbus::Error),
+    /// fwupd failed to unlock a device.
+    #[error("failed to unlock device")]
+    Unlock(#[source] dbus::Error),
+    /// A device of a composite device was not updated, because an update it depends upon failed.
+    #[error("not updated, as the update of {0} failed")]
+    CompositeSkipped(Box<str>),
+    /// A Thelio I/O board was not updated to the latest revision.
+    #[error("Thelio I/O board {0} was not updated")]
+    ThelioIoBoard(Box<str>),
+    /// Only some of the Thelio I/O boards were updated.
+    #[error("{updated} of {total} Thelio I/O boards were updated")]
+    ThelioIoPartial {
+        /// The number of boards which were updated.
+        updated: usize,
+        /// The number of boards which were to be updated.
+        total: usize,
+    },
+    /// Another process is updating firmware, or refreshing metadata.
+    #[error(transparent)]
+    Locked(#[from] LockError),
+    /// A recording could not be replayed.
+    #[error("failed to replay recording")]
+    Recording(#[from] RecordingError),
+    /// An error which was recorded in a previous session, and is being replayed.
+    #[error("{0}")]
+    Replayed(Box<str>),
+    /// The firmware manager service could not be reached, or refused an event.
+    #[error("the firmware manager service failed: {0}")]
+    Service(String),
 }
 
 /// A request for the background event loop to perform.
 #[derive(Debug)]
 pub enum FirmwareEvent {
+    /// Activate the staged update of a fwupd device.
+    Activate(Entity, Arc<FwupdDevice>),
+
     /// Upgrade the firmware of a fwupd-compatible device.
     Fwupd(Entity, Arc<FwupdDevice>, Arc<FwupdRelease>),
 
+    /// Upgrade the firmware of the devices of a composite device, in the order that fwupd
+    /// requires.
+    FwupdComposite(Vec<(Entity, Arc<FwupdDevice>, Arc<FwupdRelease>)>),
+
     /// Stop processing events.
     Stop,
 
@@ -69,24 +210,111 @@ pub enum FirmwareEvent {
     /// Search for available firmware devices.
     Scan,
 
-    /// Upgrade the firmware of Thelio I/O boarods.
-    ThelioIo(Entity, System76Digest),
+    /// Unlock a locked fwupd device.
+    Unlock(Entity, Arc<FwupdDevice>),
+
+    /// Verify the firmware installed on fwupd devices against their known-good checksums.
+    ///
+    /// Devices which do not support verification are skipped.
+    Verify(Vec<(Entity, Arc<FwupdDevice>)>),
+
+    /// Record the firmware installed on fwupd devices as their known-good baseline.
+    ///
+    /// Devices which do not support verification are skipped.
+    VerifyBaseline(Vec<(Entity, Arc<FwupdDevice>)>),
+
+    /// Upgrade the firmware of Thelio I/O boards to the given revision.
+    ///
+    /// All boards are flashed together, so every board's entity and identifier is given in order
+    /// to report the outcome of each board.
+    ThelioIo(System76Digest, Version, Vec<(Entity, Box<str>)>),
 }
 
+impl FirmwareEvent {
+    /// The fwupd devices which the event acts upon.
+    fn fwupd_devices(&self) -> Vec<(Entity, &FwupdDevice)> {
+        match self {
+            FirmwareEvent::Activate(entity, device)
+            | FirmwareEvent::Fwupd(entity, device, _)
+            | FirmwareEvent::Unlock(entity, device) => vec![(*entity, device)],
+            FirmwareEvent::FwupdComposite(devices) => {
+                devices.iter().map(|(entity, device, _)| (*entity, &**device)).collect()
+            }
+            FirmwareEvent::Verify(devices) | FirmwareEvent::VerifyBaseline(devices) => {
+                devices.iter().map(|(entity, device)| (*entity, &**device)).collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// How an installed version which a device does not report is displayed.
+pub const UNKNOWN_VERSION: &str = "N/A";
+
 /// Information about a device and its current and latest firmware.
 #[derive(Debug)]
 pub struct FirmwareInfo {
     /// The name of this device.
     pub name: Box<str>,
 
-    /// The currently-installed version.
-    pub current: Box<str>,
+    /// The currently-installed version, or `None` if the device does not report it.
+    pub current: Option<Version>,
 
     /// The latest version of firmware for this device.
-    pub latest: Option<Box<str>>,
+    pub latest: Option<Version>,
 
     /// The time required for this firmware to be flashed, in seconds.
     pub install_duration: u32,
+
+    /// Metadata of every release known for this firmware, from oldest to newest.
+    pub releases: Vec<ReleaseInfo>,
+}
+
+impl FirmwareInfo {
+    /// Returns `true` if the latest version is newer than the currently-installed version.
+    ///
+    /// Devices which do not report their installed version may always be updated, as it cannot be
+    /// known whether the latest version is already installed.
+    pub fn is_upgradeable(&self) -> bool {
+        match (&self.current, &self.latest) {
+            (Some(current), Some(latest)) => current < latest,
+            (None, Some(_)) => true,
+            (_, None) => false,
+        }
+    }
+
+    /// The currently-installed version as it is displayed, which is `N/A` if it is unknown.
+    pub fn current_label(&self) -> &str {
+        self.current.as_ref().map_or(UNKNOWN_VERSION, Version::as_str)
+    }
+
+    /// The metadata of the latest release, if it is known.
+    pub fn latest_release(&self) -> Option<&ReleaseInfo> { self.releases.last() }
+
+    /// Releases which are newer than the currently-installed version, from oldest to newest.
+    ///
+    /// Every release is pending if the installed version is unknown.
+    pub fn pending_releases(&self) -> impl Iterator<Item = &ReleaseInfo> {
+        self.releases.iter().filter(move |release| {
+            self.current.as_ref().map_or(true, |current| *current < release.version)
+        })
+    }
+
+    /// Divides the known releases by their relation to the installed version and `target`.
+    pub fn release_range(&self, target: &Version) -> ReleaseRange<'_> {
+        ReleaseRange::new(&self.releases, self.current.as_ref(), target)
+    }
+
+    /// The severity of updating to the latest version, if an update is available.
+    ///
+    /// An update is as severe as the most severe of the releases that it installs.
+    pub fn severity(&self) -> Option<Severity> {
+        if !self.is_upgradeable() {
+            return None;
+        }
+
+        Some(self.pending_releases().map(ReleaseInfo::severity).max().unwrap_or(Severity::Routine))
+    }
 }
 
 /// A collection of all firmware device entities that a frontend is managing.
@@ -136,14 +364,29 @@ impl Entities {
 #[derive(Debug)]
 #[allow(clippy::large_enum_variant)]
 pub enum FirmwareSignal {
+    /// The known-good checksums of a device's firmware were recorded.
+    BaselineRecorded(Entity),
+
+    /// The staged update of a device was activated.
+    DeviceActivated(Entity),
+
     /// A device has initiated the flashing process.
     DeviceFlashing(Entity),
 
+    /// A device was unlocked.
+    DeviceUnlocked(Entity),
+
     /// A device was updated
     DeviceUpdated(Entity),
 
+    /// The firmware installed on a device is being verified.
+    DeviceVerifying(Entity),
+
+    /// The result of verifying the firmware installed on a device.
+    DeviceVerified(Entity, Integrity),
+
     /// A device has a request for user interaction.
-    DeviceRequest(String),
+    DeviceRequest(DeviceRequest),
 
     /// Signals that the entity's firmware is being downloaded.
     DownloadBegin(Entity, u64),
@@ -160,9 +403,15 @@ pub enum FirmwareSignal {
     /// Fwupd firmware was discovered.
     Fwupd(FwupdSignal),
 
+    /// Describes how current the metadata of a firmware service is.
+    Metadata(MetadataAge),
+
     /// Devices are being scanned
     Scanning,
 
+    /// The security attributes of the host, and the results of the last run, if there was one.
+    Security(HostSecurity, Option<SecuritySnapshot>),
+
     /// Signals when scanning has completed.
     ScanningComplete,
 
@@ -170,19 +419,63 @@ pub enum FirmwareSignal {
     SystemScheduled,
 
     /// System76 system firmware was discovered.
-    S76System(FirmwareInfo, Option<(System76Digest, System76Changelog)>),
+    S76System(FirmwareInfo, Option<System76Digest>),
+
+    /// A Thelio I/O board was discovered.
+    ThelioIo(ThelioIoSignal),
 
-    /// Thelio I/O firmware was discovered.
-    ThelioIo(FirmwareInfo, Option<System76Digest>),
+    /// The downloaded firmware of an entity is being verified.
+    Verifying(Entity),
+
+    /// The result of verifying the downloaded firmware of an entity.
+    Verified(Entity, Verification),
 }
 
 /// An event loop that should be run in the background, as this function will block until
 /// the stop signal is received.
+///
+/// If `FIRMWARE_MANAGER_REPLAY` names a recording, that recording is replayed instead of
+/// contacting the firmware services. Otherwise, if `FIRMWARE_MANAGER_RECORD` names a file, the
+/// session is recorded to it.
 pub fn event_loop(receiver: Receiver<FirmwareEvent>, sender: Sender<FirmwareSignal>) {
+    if let Some(path) = std::env::var_os(REPLAY_VARIABLE) {
+        match Recording::load(Path::new(&path)) {
+            Ok(recording) => replay_event_loop(recording, receiver, sender),
+            Err(why) => {
+                let _res = sender.send(FirmwareSignal::Error(None, why.into()));
+                while let Ok(event) = receiver.recv() {
+                    if let FirmwareEvent::Stop = event {
+                        break;
+                    }
+                }
+            }
+        }
+
+        return;
+    }
+
+    if let Some(path) = std::env::var_os(RECORD_VARIABLE) {
+        match Recorder::create(Path::new(&path)) {
+            Ok(recorder) => return record_event_loop(recorder, receiver, sender),
+            Err(why) => error!("session will not be recorded: {}", format_error(&why)),
+        }
+    }
+
+    backend_event_loop(receiver, sender);
+}
+
+/// The event loop which acts upon the firmware services.
+pub(crate) fn backend_event_loop(
+    receiver: Receiver<FirmwareEvent>,
+    sender: Sender<FirmwareSignal>,
+) {
     let cancellable = Arc::new(AtomicBool::new(true));
 
     let s76 = get_client("system76", s76_firmware_is_active, System76Client::new);
 
+    // Requests from fwupd are attributed to the devices which were acted upon, by device ID.
+    let targets: Arc<Mutex<HashMap<Box<str>, Entity>>> = Arc::default();
+
     let fwupd = {
         // Use Ping() to wake up fwupd, and to check if it exists.
         let fwupd_connect = || {
@@ -194,13 +487,14 @@ pub fn event_loop(receiver: Receiver<FirmwareEvent>, sender: Sender<FirmwareSign
             std::thread::spawn({
                 let sender = sender.clone();
                 let cancellable = cancellable.clone();
+                let targets = targets.clone();
                 move || {
                     if let Ok(client) = FwupdClient::new() {
                         if let Ok(signals) = client.listen_signals(cancellable) {
                             for signal in signals {
-                                if let fwupd_dbus::Signal::DeviceRequest(request) = dbg!(signal) {
-                                    let message = FirmwareSignal::DeviceRequest(request.update_message);
-                                    let _res = sender.send(message);
+                                trace!("fwupd signal: {:?}", signal);
+                                if let fwupd_dbus::Signal::DeviceRequest(request) = signal {
+                                    let _res = sender.send(device_request(request, &targets));
                                 }
                             }
                         }
@@ -214,78 +508,140 @@ pub fn event_loop(receiver: Receiver<FirmwareEvent>, sender: Sender<FirmwareSign
         get_client::<_, _, fwupd_dbus::Error>("fwupd", || true, fwupd_connect)
     };
 
+    let mut config = Config::load();
+
+    // Security changes are shown relative to the previous run of the application.
+    let previous_security = match SecuritySnapshot::load() {
+        Ok(snapshot) => snapshot,
+        Err(why) => {
+            error!("failed to read previous security results: {}", why);
+            None
+        }
+    };
+
     while let Ok(event) = receiver.recv() {
         trace!("event loop received firmware event: {:?}", event);
+
+        if let Ok(mut targets) = targets.lock() {
+            for (entity, device) in event.fwupd_devices() {
+                targets.insert(Box::from(device.device_id.as_ref()), entity);
+            }
+        }
         match event {
             FirmwareEvent::Scan => {
                 let sender = &sender;
                 let _res = sender.send(FirmwareSignal::Scanning);
 
+                // The configuration may be changed between scans.
+                config = Config::load();
+
                 if let Some(ref client) = s76 {
-                    s76_scan(client, sender.clone());
+                    s76_scan(client, &config, sender.clone());
                 }
 
                 if let Some(ref client) = fwupd {
-                    if let Err(why) = fwupd_updates(client) {
-                        eprintln!("failed to update fwupd remotes: {}", why);
+                    if config.offline {
+                        info!("offline, so fwupd remotes are not refreshed");
+                    } else if !check_authorization(Action::ModifyRemotes).is_authorized() {
+                        info!("not authorized to refresh fwupd remotes");
+                    } else {
+                        match OperationLock::acquire(LockOperation::Refresh, None) {
+                            Ok(_lock) => {
+                                // The scan goes on with the metadata that is already present.
+                                if let Err(why) = fwupd_updates(client) {
+                                    error!(
+                                        "failed to update fwupd remotes: {}",
+                                        format_error(&why)
+                                    );
+                                    let _res = sender.send(FirmwareSignal::Error(None, why));
+                                }
+                            }
+                            Err(why @ LockError::Busy(_)) => {
+                                info!("fwupd remotes are not refreshed: {}", why)
+                            }
+                            Err(why) => {
+                                error!("fwupd remotes are not refreshed: {}", format_error(&why))
+                            }
+                        }
                     }
 
+                    let age = fwupd_metadata_age(config.offline);
+                    let _res = sender.send(FirmwareSignal::Metadata(age));
+
                     fwupd_scan(client, sender.clone());
+
+                    // Older versions of fwupd do not support host security attributes.
+                    match host_security() {
+                        Ok(security) => {
+                            if let Err(why) = security.snapshot().store() {
+                                error!("failed to store security results: {}", why);
+                            }
+
+                            let previous = previous_security.clone();
+                            let _res = sender.send(FirmwareSignal::Security(security, previous));
+                        }
+                        Err(why) => warn!("host security attributes unavailable: {}", why),
+                    }
                 }
 
                 let _ = sender.send(FirmwareSignal::ScanningComplete);
             }
-            FirmwareEvent::Fwupd(entity, device, release) => {
-                let flags = fwupd_dbus::InstallFlags::empty();
-                let event = match fwupd.as_ref().map(|fwupd| {
-                    fwupd.update_device_with_release(
-                        &device,
-                        &release,
-                        flags,
-                        Some(|download_event| {
-                            use fwupd_dbus::FlashEvent::*;
-                            let event = match download_event {
-                                DownloadUpdate(progress) => {
-                                    FirmwareSignal::DownloadUpdate(entity, progress)
-                                }
-                                DownloadInitiate(size) => {
-                                    FirmwareSignal::DownloadBegin(entity, size)
-                                }
-                                DownloadComplete => FirmwareSignal::DownloadComplete(entity),
-                                FlashInProgress => FirmwareSignal::DeviceFlashing(entity),
-                                VerifyingChecksum => return,
-                            };
-
-                            let _res = sender.send(event);
-                        }),
-                    )
-                }) {
-                    Some(Ok(_)) => FirmwareSignal::DeviceUpdated(entity),
-                    Some(Err(why)) => FirmwareSignal::Error(Some(entity), why.into()),
-                    None => panic!("fwupd event assigned to non-fwupd button"),
-                };
-
-                let _res = sender.send(event);
+            FirmwareEvent::Activate(entity, device) => {
+                let lock = (LockOperation::Update, Some(&*device.name));
+                locked(lock, &[entity], &sender, |sender| fwupd_activate(entity, &device, sender));
             }
-            FirmwareEvent::S76System(entity, digest) => {
-                let signal = match s76.as_ref().map(|client| client.schedule(&digest)) {
-                    Some(Ok(_)) => FirmwareSignal::SystemScheduled,
-                    Some(Err(why)) => FirmwareSignal::Error(Some(entity), why.into()),
-                    None => panic!("thelio event assigned to non-thelio button"),
-                };
-
-                let _res = sender.send(signal);
+            FirmwareEvent::Unlock(entity, device) => fwupd_unlock(entity, &device, &sender),
+            FirmwareEvent::Verify(devices) => {
+                for (entity, device) in devices.iter().filter(|(_, device)| can_verify(device)) {
+                    fwupd_verify(*entity, device, &sender);
+                }
             }
-            FirmwareEvent::ThelioIo(entity, digest) => {
-                let _res = sender.send(FirmwareSignal::DeviceFlashing(entity));
-                let event = match s76.as_ref().map(|client| client.thelio_io_update(&digest)) {
-                    Some(Ok(_)) => FirmwareSignal::DeviceUpdated(entity),
-                    Some(Err(why)) => FirmwareSignal::Error(Some(entity), why.into()),
-                    None => panic!("thelio event assigned to non-thelio button"),
-                };
-
-                let _res = sender.send(event);
+            FirmwareEvent::VerifyBaseline(devices) => {
+                for (entity, device) in devices.iter().filter(|(_, device)| can_verify(device)) {
+                    fwupd_verify_baseline(*entity, device, &sender);
+                }
             }
+            FirmwareEvent::Fwupd(entity, device, release) => match fwupd.as_ref() {
+                Some(client) => {
+                    let lock = (LockOperation::Update, Some(&*device.name));
+                    locked(lock, &[entity], &sender, |sender| {
+                        fwupd_update(client, entity, &device, &release, &config, sender);
+                    });
+                }
+                None => panic!("fwupd event assigned to non-fwupd button"),
+            },
+            FirmwareEvent::FwupdComposite(devices) => match fwupd.as_ref() {
+                Some(client) => {
+                    // The composite device is named by the first of its devices.
+                    let name = devices.first().map(|(_, device, _)| &*device.name);
+                    let entities = devices.iter().map(|(entity, ..)| *entity).collect::<Vec<_>>();
+                    locked((LockOperation::Update, name), &entities, &sender, |sender| {
+                        fwupd_update_composite(client, &devices, &config, sender)
+                    });
+                }
+                None => panic!("fwupd event assigned to non-fwupd button"),
+            },
+            FirmwareEvent::S76System(entity, digest) => {
+                locked((LockOperation::Schedule, None), &[entity], &sender, |sender| {
+                    let signal = match s76.as_ref().map(|client| client.schedule(&digest)) {
+                        Some(Ok(_)) => FirmwareSignal::SystemScheduled,
+                        Some(Err(why)) => FirmwareSignal::Error(Some(entity), why.into()),
+                        None => panic!("thelio event assigned to non-thelio button"),
+                    };
+
+                    let _res = sender.send(signal);
+                });
+            }
+            FirmwareEvent::ThelioIo(digest, latest, boards) => match s76.as_ref() {
+                Some(client) => {
+                    let lock = (LockOperation::Update, Some("Thelio I/O"));
+                    let entities = boards.iter().map(|(entity, _)| *entity).collect::<Vec<_>>();
+                    locked(lock, &entities, &sender, |sender| {
+                        thelio_io_update(client, &digest, &latest, &boards, sender)
+                    });
+                }
+                None => panic!("thelio event assigned to non-thelio button"),
+            },
             FirmwareEvent::Stop => {
                 trace!("received quit signal");
                 break;
@@ -296,31 +652,57 @@ pub fn event_loop(receiver: Receiver<FirmwareEvent>, sender: Sender<FirmwareSign
     cancellable.store(false, Ordering::SeqCst);
 }
 
-/// Function for getting a timmed string from a file.
-fn read_trimmed(path: &str) -> io::Result<String> {
-    let mut vendor = std::fs::read_to_string(path)?;
-    vendor.truncate(vendor.trim_end().len());
-    Ok(vendor)
-}
+/// Runs a firmware operation while holding the lock which serializes them across processes.
+///
+/// The lock follows the progress of the operation from its signals, so that other processes may
+/// watch it. If another process holds the lock, the frontend is told so for every entity that
+/// the operation targets, and nothing is done.
+fn locked<F: FnOnce(&Sender<FirmwareSignal>)>(
+    (operation, device): (LockOperation, Option<&str>),
+    entities: &[Entity],
+    sender: &Sender<FirmwareSignal>,
+    func: F,
+) {
+    let mut lock = match OperationLock::acquire(operation, device) {
+        Ok(lock) => lock,
+        Err(why) => {
+            if entities.is_empty() {
+                let _res = sender.send(FirmwareSignal::Error(None, why.clone().into()));
+            }
 
-/// Convenience function for reading the product name from the DMI ID information.
-fn product_name() -> io::Result<String> {
-    read_trimmed("/sys/class/dmi/id/product_name")
-}
+            for &entity in entities {
+                let _res = sender.send(FirmwareSignal::Error(Some(entity), why.clone().into()));
+            }
 
-/// Convenience function for reading the product version from the DMI ID information.
-fn product_version() -> io::Result<String> {
-    read_trimmed("/sys/class/dmi/id/product_version")
-}
+            return;
+        }
+    };
+
+    let (tx, rx) = std::sync::mpsc::channel();
+    std::thread::scope(|scope| {
+        let lock = &mut lock;
+        scope.spawn(move || {
+            for signal in rx {
+                lock.observe(&signal);
+                if sender.send(signal).is_err() {
+                    break;
+                }
+            }
+        });
 
-/// Convenience function for reading the system vendor from the DMI ID information.
-fn sys_vendor() -> io::Result<String> {
-    read_trimmed("/sys/class/dmi/id/sys_vendor")
+        func(&tx);
+        drop(tx);
+    });
 }
 
-/// Creates a string identifying system firmware by the board vendor and name.
-pub(crate) fn system_board_identity() -> io::Result<String> {
-    Ok([&*sys_vendor()?, " ", &*product_name()?, " (", &*product_version()?, ")"].concat())
+/// Attributes a request from fwupd to the device it concerns, if that device was acted upon.
+fn device_request(
+    request: fwupd_dbus::Request,
+    targets: &Mutex<HashMap<Box<str>, Entity>>,
+) -> FirmwareSignal {
+    let entity = targets.lock().ok().and_then(|targets| targets.get(&request.device_id).copied());
+    let offline = Config::load().offline;
+    FirmwareSignal::DeviceRequest(DeviceRequest::from_fwupd(request, entity, offline))
 }
 
 /// Generic function for attaining a DBus client connection to a firmware service.
@@ -346,25 +728,8 @@ fn systemd_service_is_active(name: &str) -> bool {
         .map_or(false, |status| status.success())
 }
 
-/// Finds the lowest revision from anything that is or may become an `Iterator` of strings.
-fn lowest_revision<'a, I: IntoIterator<Item = &'a str>>(list: I) -> &'a str {
-    use std::cmp::Ordering;
-    let mut list = list.into_iter();
-    match list.next() {
-        Some(mut lowest_revision) => {
-            for rev in list {
-                if human_sort::compare(lowest_revision, rev) == Ordering::Greater {
-                    lowest_revision = rev;
-                }
-            }
-            lowest_revision
-        }
-        None => "",
-    }
-}
-
-/// Helper for formatting errors for logs.
-fn format_error<E: std::error::Error>(why: E) -> String {
+/// Formats an error, followed by each of its causes.
+pub fn format_error(why: &dyn std::error::Error) -> String {
     let mut error_message = format!("{}", why);
     let mut cause = why.source();
     while let Some(error) = cause {
@@ -374,21 +739,3 @@ fn format_error<E: std::error::Error>(why: E) -> String {
 
     error_message
 }
-
-#[cfg(test)]
-mod tests {
-    #[test]
-    fn lowest_revision() {
-        let input = vec!["", "F10", "F5"];
-        let rev = super::lowest_revision(input.iter().cloned());
-        assert_eq!(rev, "");
-
-        let input = vec!["F3", "F10", "F5"];
-        let rev = super::lowest_revision(input.iter().cloned());
-        assert_eq!(rev, "F3");
-
-        let input = vec!["F10", "F3", "F5"];
-        let rev = super::lowest_revision(input.iter().cloned());
-        assert_eq!(rev, "F3");
-    }
-}
@@ -0,0 +1,387 @@
+//! An advisory lock which serializes firmware operations across every process on the system.
+//!
+//! Any frontend may flash firmware, schedule system firmware, or refresh metadata, and several
+//! may run at once. Each operation holds the lock for as long as it runs, and describes itself and
+//! its progress in a file beside the lock, so that other processes may say what is in progress and
+//! follow it until it ends.
+//!
+//! The lock file is created by systemd-tmpfiles in a directory owned by root, and is readable by
+//! every user, so that every frontend which polkit authorizes may lock it through a read-only
+//! descriptor, whatever groups its user belongs to. Operations are refused if it cannot be locked.
+
+use crate::{FirmwareSignal, Stage};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    fs::{File, OpenOptions},
+    io::{self, Read},
+    os::unix::{
+        fs::{FileExt, OpenOptionsExt},
+        io::AsRawFd,
+    },
+    path::{Path, PathBuf},
+    sync::Arc,
+    thread,
+    time::Duration,
+};
+
+/// The lock file, which every user may open for reading, and so may lock.
+pub const LOCK_PATH: &str = "/run/firmware-manager/operation.lock";
+
+/// An error that may occur when acquiring the lock.
+#[derive(Clone, Debug, Error)]
+pub enum Error {
+    /// Another process holds the lock, which it describes if it has written its details yet.
+    #[error("another firmware operation is in progress{}", describe(.0))]
+    Busy(Option<LockHolder>),
+    /// The lock file could not be opened or locked, so the operation was not begun.
+    #[error("failed to take the firmware operation lock at {}", .0.display())]
+    Unavailable(PathBuf, #[source] Arc<io::Error>),
+}
+
+fn describe(holder: &Option<LockHolder>) -> String {
+    holder.as_ref().map_or_else(String::new, |holder| format!(": {}", holder))
+}
+
+/// The kind of operation that holds the lock.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LockOperation {
+    /// Firmware is being installed on a device.
+    Update,
+    /// System firmware is being scheduled for installation on the next boot.
+    Schedule,
+    /// The metadata of the firmware services is being refreshed.
+    Refresh,
+}
+
+/// The process which holds the lock, and how far its operation has progressed.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockHolder {
+    /// The ID of the process.
+    pub pid:       u32,
+    /// The name of the program which the process runs.
+    pub program:   Box<str>,
+    /// What the process is doing.
+    pub operation: LockOperation,
+    /// The device which the operation acts upon, if it acts upon one.
+    pub device:    Option<Box<str>>,
+    /// When the operation began, in seconds since the UNIX Epoch.
+    pub started:   u64,
+    /// How far the update of the device has progressed, if it has begun.
+    pub stage:     Option<Stage>,
+    /// The percentage of the firmware which was downloaded, if it is being downloaded.
+    pub percent:   Option<u8>,
+}
+
+impl fmt::Display for LockHolder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let action = match self.operation {
+            LockOperation::Update => "updating",
+            LockOperation::Schedule => "scheduling",
+            LockOperation::Refresh => "refreshing",
+        };
+
+        let target = self.device.as_deref().unwrap_or(match self.operation {
+            LockOperation::Refresh => "firmware metadata",
+            LockOperation::Schedule => "system firmware",
+            LockOperation::Update => "firmware",
+        });
+
+        write!(f, "{} ({}) is {} {}", self.program, self.pid, action, target)?;
+
+        match (self.stage, self.percent) {
+            (Some(Stage::Downloading), Some(percent)) => write!(f, ", downloading {}%", percent),
+            (Some(stage), _) => write!(f, ", {}", stage.as_str()),
+            (None, _) => Ok(()),
+        }
+    }
+}
+
+/// A held lock, which is released when it is dropped.
+pub struct OperationLock {
+    /// The lock file, which is locked for as long as it is open.
+    _file:      File,
+    /// The file which describes the operation, if it could be opened for writing.
+    details:    Option<File>,
+    holder:     LockHolder,
+    downloaded: u64,
+    total:      u64,
+}
+
+impl OperationLock {
+    /// Acquires the lock for an operation, unless another process holds it.
+    pub fn acquire(operation: LockOperation, device: Option<&str>) -> Result<Self, Error> {
+        Self::acquire_at(Path::new(LOCK_PATH), operation, device)
+    }
+
+    /// Acquires the lock at `path`, which tests may use in place of the system's lock.
+    pub fn acquire_at(
+        path: &Path,
+        operation: LockOperation,
+        device: Option<&str>,
+    ) -> Result<Self, Error> {
+        let holder = LockHolder {
+            pid: std::process::id(),
+            program: program_name(),
+            operation,
+            device: device.map(Box::from),
+            started: crate::timestamp::current(),
+            stage: None,
+            percent: None,
+        };
+
+        let unavailable = |why| Error::Unavailable(path.to_path_buf(), Arc::new(why));
+        let file = open(path).map_err(unavailable)?;
+
+        let mut attempts = 0;
+        while let Err(why) = flock(&file, libc::LOCK_EX | libc::LOCK_NB) {
+            if why.raw_os_error() != Some(libc::EWOULDBLOCK) {
+                return Err(unavailable(why));
+            }
+
+            // Processes which probe for the holder share the lock for a moment, before a holder
+            // which has the lock to itself has described itself.
+            let holder = read_holder(&details_path(path));
+            if holder.is_some() || attempts == PROBE_RETRIES {
+                return Err(Error::Busy(holder));
+            }
+
+            attempts += 1;
+            thread::sleep(PROBE_INTERVAL);
+        }
+
+        let details = match open_details(&details_path(path)) {
+            Ok(details) => Some(details),
+            Err(why) => {
+                warn!("failed to open the details of the operation lock: {}", why);
+                None
+            }
+        };
+
+        let lock = Self { _file: file, details, holder, downloaded: 0, total: 0 };
+        lock.store();
+        Ok(lock)
+    }
+
+    /// The details which the lock was acquired with.
+    pub fn holder(&self) -> &LockHolder { &self.holder }
+
+    /// Follows the progress of the operation from a signal of the event loop.
+    pub fn observe(&mut self, signal: &FirmwareSignal) {
+        let before = (self.holder.stage, self.holder.percent);
+
+        match *signal {
+            FirmwareSignal::DownloadBegin(_, total) => {
+                self.downloaded = 0;
+                self.total = total;
+                self.holder.stage = Some(Stage::Downloading);
+                self.holder.percent = Some(0);
+            }
+            FirmwareSignal::DownloadUpdate(_, downloaded) => {
+                self.downloaded += downloaded as u64;
+                let percent = self.downloaded * 100 / self.total.max(1);
+                self.holder.percent = Some(percent.min(100) as u8);
+            }
+            FirmwareSignal::Verifying(_) => {
+                self.holder.stage = Some(Stage::Verifying);
+                self.holder.percent = None;
+            }
+            FirmwareSignal::DeviceFlashing(_) => {
+                self.holder.stage = Some(Stage::Flashing);
+                self.holder.percent = None;
+            }
+            _ => return,
+        }
+
+        if (self.holder.stage, self.holder.percent) != before {
+            self.store();
+        }
+    }
+
+    /// Describes the operation beside the lock file, for other processes to read.
+    fn store(&self) {
+        let Some(file) = self.details.as_ref() else {
+            return;
+        };
+
+        let result =
+            serde_json::to_vec(&self.holder).map_err(io::Error::from).and_then(|details| {
+                file.set_len(0)?;
+                file.write_all_at(&details, 0)
+            });
+
+        if let Err(why) = result {
+            warn!("failed to describe the operation beside its lock file: {}", why);
+        }
+    }
+}
+
+impl Drop for OperationLock {
+    fn drop(&mut self) {
+        // The lock is released when the file is closed, after which the details are stale.
+        if let Some(file) = self.details.as_ref() {
+            let _res = file.set_len(0);
+        }
+    }
+}
+
+/// The process which holds the lock, if the lock is held.
+pub fn lock_holder() -> Option<LockHolder> { lock_holder_at(Path::new(LOCK_PATH)) }
+
+/// The process which holds the lock at `path`, if the lock is held.
+///
+/// `None` is also returned if the holder has not written its details yet.
+pub fn lock_holder_at(path: &Path) -> Option<LockHolder> {
+    let file = OpenOptions::new().read(true).custom_flags(OPEN_FLAGS).open(path).ok()?;
+
+    // The lock is free if it can be shared, and this probe releases it when the file is closed.
+    match flock(&file, libc::LOCK_SH | libc::LOCK_NB) {
+        Ok(()) => None,
+        Err(_) => read_holder(&details_path(path)),
+    }
+}
+
+/// Links are never followed, so that a file elsewhere cannot be substituted for the lock.
+const OPEN_FLAGS: libc::c_int = libc::O_NOFOLLOW | libc::O_CLOEXEC;
+
+/// How many times a busy lock is retried while its holder has not described itself.
+const PROBE_RETRIES: u32 = 5;
+
+/// How long to wait for a probe to release the lock before retrying.
+const PROBE_INTERVAL: Duration = Duration::from_millis(20);
+
+/// The file which describes the operation that holds the lock at `path`.
+fn details_path(path: &Path) -> PathBuf { path.with_extension("json") }
+
+/// Opens the lock file for locking, which only needs it to be readable.
+///
+/// The file is created if it is missing, which only root may do in the directory of the lock.
+fn open(path: &Path) -> io::Result<File> {
+    OpenOptions::new().read(true).mode(0o644).custom_flags(OPEN_FLAGS | libc::O_CREAT).open(path)
+}
+
+/// Opens the details of the lock for writing, which only the holder of the lock does.
+///
+/// systemd-tmpfiles creates the file writable by every user, so that any holder may describe
+/// itself. The details are only informative, and are ignored while the lock is free.
+fn open_details(path: &Path) -> io::Result<File> {
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o666)
+        .custom_flags(OPEN_FLAGS)
+        .open(path)
+}
+
+fn flock(file: &File, operation: libc::c_int) -> io::Result<()> {
+    match unsafe { libc::flock(file.as_raw_fd(), operation) } {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+fn read_holder(path: &Path) -> Option<LockHolder> {
+    let mut file = OpenOptions::new().read(true).custom_flags(OPEN_FLAGS).open(path).ok()?;
+    let mut details = String::new();
+    file.read_to_string(&mut details).ok()?;
+    serde_json::from_str(&details).ok()
+}
+
+fn program_name() -> Box<str> {
+    std::env::args_os()
+        .next()
+        .as_deref()
+        .map(Path::new)
+        .and_then(Path::file_name)
+        .map_or_else(|| "unknown".into(), |name| name.to_string_lossy().into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Entities;
+    use std::{fs, os::unix::fs::symlink};
+
+    fn lock_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("firmware-manager-{}-{}.lock", name, std::process::id()))
+    }
+
+    fn remove(path: &Path) {
+        let _res = fs::remove_file(path);
+        let _res = fs::remove_file(details_path(path));
+    }
+
+    #[test]
+    fn second_holder_is_refused() {
+        let path = lock_path("refused");
+
+        let lock = OperationLock::acquire_at(&path, LockOperation::Update, Some("Dock")).unwrap();
+        assert_eq!(lock_holder_at(&path).as_ref(), Some(lock.holder()));
+
+        match OperationLock::acquire_at(&path, LockOperation::Refresh, None) {
+            Err(Error::Busy(Some(holder))) => assert_eq!(holder.device.as_deref(), Some("Dock")),
+            other => panic!("expected the lock to be busy: {:?}", other.map(|_| ())),
+        }
+
+        drop(lock);
+        assert_eq!(lock_holder_at(&path), None);
+        assert!(OperationLock::acquire_at(&path, LockOperation::Refresh, None).is_ok());
+
+        remove(&path);
+    }
+
+    #[test]
+    fn progress_is_shared() {
+        let path = lock_path("progress");
+        let entity = Entities::default().create();
+
+        let mut lock = OperationLock::acquire_at(&path, LockOperation::Update, None).unwrap();
+        lock.observe(&FirmwareSignal::DownloadBegin(entity, 200));
+        lock.observe(&FirmwareSignal::DownloadUpdate(entity, 50));
+
+        let holder = lock_holder_at(&path).unwrap();
+        assert_eq!(holder.stage, Some(Stage::Downloading));
+        assert_eq!(holder.percent, Some(25));
+
+        lock.observe(&FirmwareSignal::DeviceFlashing(entity));
+        assert_eq!(lock_holder_at(&path).unwrap().stage, Some(Stage::Flashing));
+
+        drop(lock);
+        remove(&path);
+    }
+
+    #[test]
+    fn probes_do_not_refuse_holders() {
+        let path = lock_path("probe");
+
+        // A probe which shares the lock while the operation begins.
+        let probe = open(&path).unwrap();
+        flock(&probe, libc::LOCK_SH | libc::LOCK_NB).unwrap();
+        let release = thread::spawn(move || {
+            thread::sleep(PROBE_INTERVAL);
+            drop(probe);
+        });
+
+        assert!(OperationLock::acquire_at(&path, LockOperation::Refresh, None).is_ok());
+        release.join().unwrap();
+        remove(&path);
+    }
+
+    #[test]
+    fn links_are_not_followed() {
+        let target = lock_path("target");
+        let path = lock_path("link");
+        fs::write(&target, "contents").unwrap();
+        remove(&path);
+        symlink(&target, &path).unwrap();
+
+        let result = OperationLock::acquire_at(&path, LockOperation::Refresh, None);
+        assert!(matches!(result, Err(Error::Unavailable(..))));
+        assert_eq!(fs::read_to_string(&target).unwrap(), "contents");
+
+        remove(&path);
+        let _res = fs::remove_file(&target);
+    }
+}
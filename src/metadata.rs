@@ -0,0 +1,45 @@
+//! Describes how current the metadata of each firmware service is.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// The service that firmware metadata was obtained from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MetadataSource {
+    /// The metadata of the remotes configured in fwupd.
+    Fwupd,
+    /// The changelog and digest of System76 system firmware.
+    System76,
+    /// The digest of Thelio I/O firmware.
+    ThelioIo,
+}
+
+impl fmt::Display for MetadataSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            MetadataSource::Fwupd => "fwupd",
+            MetadataSource::System76 => "System76",
+            MetadataSource::ThelioIo => "Thelio I/O",
+        })
+    }
+}
+
+/// How current the metadata that a scan used is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MetadataAge {
+    /// The service that the metadata was obtained from.
+    pub source: MetadataSource,
+    /// When the metadata was last refreshed, in seconds since the UNIX Epoch, if it is known.
+    pub refreshed: Option<u64>,
+    /// The metadata was not refreshed from the network during this scan.
+    pub offline: bool,
+}
+
+impl MetadataAge {
+    /// The number of whole days since the metadata was refreshed, if it is known.
+    pub fn days(&self) -> Option<u64> {
+        let now = crate::timestamp::current();
+        self.refreshed.map(|refreshed| now.saturating_sub(refreshed) / (60 * 60 * 24))
+    }
+}
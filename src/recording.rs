@@ -0,0 +1,578 @@
+//! Recordings of firmware sessions, which are replayed to reproduce what a user saw.
+//!
+//! Every event that the event loop receives, and every signal that it sends in response, is
+//! appended to the recording as a line of JSON in the format of the `schema` module. Serial numbers
+//! are redacted before anything is written, so that recordings may be attached to bug reports.
+
+use crate::{
+    Changelog, Entity, EntityId, EventData, FirmwareEvent, FirmwareInfo, FirmwareSignal, FwupdData,
+    FwupdDevice, FwupdRelease, FwupdSignal, InfoData, Integrity, IntegrityData, Record,
+    ReleaseData, ReleaseFlags, ReleaseInfo, RequestData, SignalData, System76Digest, ThelioIoData,
+    ThelioIoSignal, Verification, VerificationData, Version,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufRead, BufReader, Write},
+    iter::Peekable,
+    mem,
+    path::Path,
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+/// The environment variable which names the file that the event loop records its session to.
+pub const RECORD_VARIABLE: &str = "FIRMWARE_MANAGER_RECORD";
+
+/// The environment variable which names a recording that the event loop replays, instead of
+/// contacting the firmware services.
+pub const REPLAY_VARIABLE: &str = "FIRMWARE_MANAGER_REPLAY";
+
+/// What serial numbers are replaced with in recordings.
+pub const REDACTED: &str = "<redacted>";
+
+/// Serial numbers shorter than this are not redacted, as they would match unrelated text.
+const MIN_SERIAL_LEN: usize = 4;
+
+/// An error that may occur when writing or reading a recording.
+#[derive(Debug, Error)]
+pub enum Error {
+    /// The recording could not be created.
+    #[error("failed to create recording")]
+    Create(#[source] io::Error),
+    /// The recording could not be opened.
+    #[error("failed to open recording")]
+    Open(#[source] io::Error),
+    /// The recording could not be read.
+    #[error("failed to read recording")]
+    Read(#[source] io::Error),
+    /// An entry could not be appended to the recording.
+    #[error("failed to write recording")]
+    Write(#[source] io::Error),
+    /// An entry could not be serialized.
+    #[error("failed to serialize entry of recording")]
+    Serialize(#[source] serde_json::Error),
+    /// A line of the recording is not a valid entry.
+    #[error("line {line} of recording is invalid")]
+    Parse {
+        /// The number of the line, counting from one.
+        line:   usize,
+        /// Why the line could not be parsed.
+        source: serde_json::Error,
+    },
+    /// A line of the recording was written with an unsupported version of the schema.
+    #[error("line {line} of recording has unsupported schema version {version}")]
+    Version {
+        /// The number of the line, counting from one.
+        line:    usize,
+        /// The version of the schema that the line was written with.
+        version: u32,
+    },
+}
+
+/// An entry of a recording, in the order that the event loop observed it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "entry", rename_all = "snake_case")]
+pub enum Entry {
+    /// An event which the event loop received from the frontend.
+    Event {
+        /// The event which was received.
+        event: EventData,
+    },
+    /// A signal which the event loop sent to the frontend.
+    Signal {
+        /// The signal which was sent.
+        signal: SignalData,
+    },
+}
+
+/// Appends the events and signals of a session to a recording, redacting serial numbers.
+pub struct Recorder<W = File> {
+    writer:  W,
+    serials: Vec<Box<str>>,
+}
+
+impl Recorder<File> {
+    /// Creates a recording at `path`, replacing any existing file.
+    pub fn create(path: &Path) -> Result<Self, Error> {
+        File::create(path).map(Self::new).map_err(Error::Create)
+    }
+}
+
+impl<W: Write> Recorder<W> {
+    /// Records to `writer`, redacting the serial numbers of the system.
+    pub fn new(writer: W) -> Self {
+        let mut recorder = Self { writer, serials: Vec::new() };
+        for serial in crate::identity::serials(Path::new(crate::SYSFS_ROOT)) {
+            recorder.redact(&serial);
+        }
+
+        recorder
+    }
+
+    /// Redacts every later occurrence of `serial` from the recording.
+    pub fn redact(&mut self, serial: &str) {
+        let serial = serial.trim();
+        if serial.len() >= MIN_SERIAL_LEN && !self.serials.iter().any(|known| &**known == serial) {
+            self.serials.push(serial.into());
+        }
+    }
+
+    /// Records an event which the event loop received.
+    pub fn event(&mut self, event: &FirmwareEvent) -> Result<(), Error> {
+        self.write(&Entry::Event { event: EventData::from(event) })
+    }
+
+    /// Records a signal which the event loop sent.
+    ///
+    /// The serial numbers of fwupd devices are redacted from the signal which reports the device.
+    pub fn signal(&mut self, signal: &FirmwareSignal) -> Result<(), Error> {
+        if let FirmwareSignal::Fwupd(signal) = signal {
+            if let Some(serial) = signal.device.serial.as_deref() {
+                self.redact(serial);
+            }
+        }
+
+        self.write(&Entry::Signal { signal: SignalData::from(signal) })
+    }
+
+    fn write(&mut self, entry: &Entry) -> Result<(), Error> {
+        let mut line = serde_json::to_string(&Record::new(entry)).map_err(Error::Serialize)?;
+        for serial in &self.serials {
+            if line.contains(&**serial) {
+                line = line.replace(&**serial, REDACTED);
+            }
+        }
+
+        line.push('\n');
+        self.writer.write_all(line.as_bytes()).map_err(Error::Write)
+    }
+}
+
+/// The entries of a recording which was read back.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Recording {
+    /// The entries, in the order that they were recorded.
+    pub entries: Vec<Entry>,
+}
+
+impl Recording {
+    /// Opens the recording at `path`.
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let file = File::open(path).map_err(Error::Open)?;
+        Self::read(BufReader::new(file))
+    }
+
+    /// Reads a recording, one entry per line.
+    pub fn read<R: BufRead>(reader: R) -> Result<Self, Error> {
+        let mut entries = Vec::new();
+
+        for (number, line) in reader.lines().enumerate() {
+            let line = line.map_err(Error::Read)?;
+            let number = number + 1;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            // The version is checked first, as newer entries may not parse.
+            let parse = |source| Error::Parse { line: number, source };
+            let record: Record<serde_json::Value> = serde_json::from_str(&line).map_err(parse)?;
+            if !record.is_supported() {
+                return Err(Error::Version { line: number, version: record.version });
+            }
+
+            entries.push(serde_json::from_value(record.data).map_err(parse)?);
+        }
+
+        Ok(Self { entries })
+    }
+}
+
+/// Runs the event loop, recording its session.
+///
+/// The recording is best-effort: a failure to write it is logged, and the session carries on.
+pub fn record_event_loop<W: Write + Send + 'static>(
+    recorder: Recorder<W>,
+    receiver: Receiver<FirmwareEvent>,
+    sender: Sender<FirmwareSignal>,
+) {
+    let recorder = Arc::new(Mutex::new(recorder));
+    let (event_tx, event_rx) = mpsc::channel();
+    let (signal_tx, signal_rx) = mpsc::channel();
+
+    let events = thread::spawn({
+        let recorder = recorder.clone();
+        move || {
+            for event in receiver {
+                record(&recorder, |recorder| recorder.event(&event));
+                let stop = matches!(event, FirmwareEvent::Stop);
+                if event_tx.send(event).is_err() || stop {
+                    break;
+                }
+            }
+        }
+    });
+
+    // Signals are forwarded until every sender of the event loop is dropped, which threads that
+    // listen to fwupd may outlive the event loop by, so this thread is not joined.
+    thread::spawn(move || {
+        for signal in signal_rx {
+            record(&recorder, |recorder| recorder.signal(&signal));
+            if sender.send(signal).is_err() {
+                break;
+            }
+        }
+    });
+
+    crate::backend_event_loop(event_rx, signal_tx);
+    let _res = events.join();
+}
+
+fn record<W, F>(recorder: &Mutex<Recorder<W>>, func: F)
+where
+    F: FnOnce(&mut Recorder<W>) -> Result<(), Error>,
+{
+    let mut recorder = recorder.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Err(why) = func(&mut recorder) {
+        error!("{}", crate::format_error(&why));
+    }
+}
+
+/// Replays a recording in place of the event loop.
+///
+/// Each event that is received steps through the recording to the next recorded event, and sends
+/// the signals that were recorded after it. The entities of the recording are associated with those
+/// of the events received, so that the frontend recognizes the devices that signals refer to.
+pub fn replay_event_loop(
+    recording: Recording,
+    receiver: Receiver<FirmwareEvent>,
+    sender: Sender<FirmwareSignal>,
+) {
+    let mut entries = recording.entries.into_iter().peekable();
+    let mut entities = HashMap::new();
+
+    // Signals which were sent before the first event are replayed right away.
+    if !replay_signals(&mut entries, &entities, &sender) {
+        return;
+    }
+
+    while let Ok(event) = receiver.recv() {
+        if let FirmwareEvent::Stop = event {
+            break;
+        }
+
+        let live = EventData::from(&event);
+        match entries.next() {
+            Some(Entry::Event { event: recorded }) => {
+                if !associate(&recorded, &live, &mut entities) {
+                    warn!("replay diverged: expected {:?}, received {:?}", recorded, live);
+                }
+            }
+            _ => {
+                info!("recording ended before {:?}", live);
+                continue;
+            }
+        }
+
+        if !replay_signals(&mut entries, &entities, &sender) {
+            break;
+        }
+    }
+}
+
+/// Sends the signals up to the next recorded event, returning `false` if the frontend is gone.
+fn replay_signals(
+    entries: &mut Peekable<impl Iterator<Item = Entry>>,
+    entities: &HashMap<EntityId, Entity>,
+    sender: &Sender<FirmwareSignal>,
+) -> bool {
+    let entity = |id: EntityId| entities.get(&id).copied().unwrap_or_else(|| Entity::from(id));
+
+    while let Some(Entry::Signal { signal }) =
+        entries.next_if(|entry| matches!(entry, Entry::Signal { .. }))
+    {
+        if sender.send(replayed_signal(signal, entity)).is_err() {
+            return false;
+        }
+    }
+
+    true
+}
+
+/// Associates the entities of a recorded event with those of the event that was received.
+///
+/// Targets are matched by their device IDs, so the order of devices may differ. Returns `false`
+/// if the events differ.
+fn associate(
+    recorded: &EventData,
+    live: &EventData,
+    entities: &mut HashMap<EntityId, Entity>,
+) -> bool {
+    if mem::discriminant(recorded) != mem::discriminant(live) {
+        return false;
+    }
+
+    let live = live.targets();
+    let mut matched = true;
+    for (id, key) in recorded.targets() {
+        match live.iter().find(|(_, live_key)| *live_key == key) {
+            Some((live_id, _)) => {
+                entities.insert(id, Entity::from(*live_id));
+            }
+            None => matched = false,
+        }
+    }
+
+    matched
+}
+
+/// Recreates a signal from its recording.
+pub(crate) fn replayed_signal(
+    signal: SignalData,
+    entity: impl Fn(EntityId) -> Entity,
+) -> FirmwareSignal {
+    match signal {
+        SignalData::BaselineRecorded { entity: id } => FirmwareSignal::BaselineRecorded(entity(id)),
+        SignalData::DeviceActivated { entity: id } => FirmwareSignal::DeviceActivated(entity(id)),
+        SignalData::DeviceFlashing { entity: id } => FirmwareSignal::DeviceFlashing(entity(id)),
+        SignalData::DeviceUnlocked { entity: id } => FirmwareSignal::DeviceUnlocked(entity(id)),
+        SignalData::DeviceUpdated { entity: id } => FirmwareSignal::DeviceUpdated(entity(id)),
+        SignalData::DeviceVerifying { entity: id } => FirmwareSignal::DeviceVerifying(entity(id)),
+        SignalData::DeviceVerified { entity: id, integrity } => {
+            let integrity = match integrity {
+                IntegrityData::Passed => Integrity::Passed,
+                IntegrityData::Failed { reason } => Integrity::Failed(reason),
+                IntegrityData::NoBaseline => Integrity::NoBaseline,
+            };
+
+            FirmwareSignal::DeviceVerified(entity(id), integrity)
+        }
+        SignalData::DeviceRequest { request } => {
+            let RequestData { id, kind, entity: id_of_entity, device_id, message, image } = request;
+            FirmwareSignal::DeviceRequest(crate::DeviceRequest {
+                id,
+                kind,
+                entity: id_of_entity.map(&entity),
+                device_id,
+                message,
+                image,
+            })
+        }
+        SignalData::DownloadBegin { entity: id, size } => {
+            FirmwareSignal::DownloadBegin(entity(id), size)
+        }
+        SignalData::DownloadComplete { entity: id } => FirmwareSignal::DownloadComplete(entity(id)),
+        SignalData::DownloadUpdate { entity: id, downloaded } => {
+            FirmwareSignal::DownloadUpdate(entity(id), downloaded as usize)
+        }
+        SignalData::Error { entity: id, message } => {
+            FirmwareSignal::Error(id.map(&entity), crate::Error::Replayed(message.into()))
+        }
+        SignalData::Fwupd { device } => FirmwareSignal::Fwupd(replayed_fwupd(device)),
+        SignalData::Metadata { age } => FirmwareSignal::Metadata(age),
+        SignalData::Scanning => FirmwareSignal::Scanning,
+        SignalData::Security { security, previous } => FirmwareSignal::Security(security, previous),
+        SignalData::ScanningComplete => FirmwareSignal::ScanningComplete,
+        SignalData::SystemScheduled => FirmwareSignal::SystemScheduled,
+        SignalData::S76System { info, digest } => FirmwareSignal::S76System(
+            replayed_info(info, Changelog::from_system76),
+            digest.map(|digest| System76Digest(digest.into())),
+        ),
+        SignalData::ThelioIo { board } => {
+            let ThelioIoData { board, info, digest } = board;
+            FirmwareSignal::ThelioIo(ThelioIoSignal {
+                board,
+                info: replayed_info(info, Changelog::from_system76),
+                digest: digest.map(|digest| System76Digest(digest.into())),
+            })
+        }
+        SignalData::Verifying { entity: id } => FirmwareSignal::Verifying(entity(id)),
+        SignalData::Verified { entity: id, verification } => {
+            match replayed_verification(verification) {
+                Some(verification) => FirmwareSignal::Verified(entity(id), verification),
+                None => {
+                    let why = crate::Error::Replayed("recorded checksum is malformed".into());
+                    FirmwareSignal::Error(Some(entity(id)), why)
+                }
+            }
+        }
+    }
+}
+
+fn replayed_info(info: InfoData, changelog: fn(&str) -> Changelog) -> FirmwareInfo {
+    FirmwareInfo {
+        name:             info.name,
+        current:          info.current,
+        latest:           info.latest,
+        install_duration: info.install_duration,
+        releases:         info
+            .releases
+            .into_iter()
+            .map(|release| ReleaseInfo {
+                changelog:   changelog(&release.description),
+                version:     release.version,
+                date:        release.date,
+                description: release.description,
+                urgency:     release.urgency,
+                size:        release.size,
+                license:     release.license,
+                vendor:      release.vendor,
+                checksums:   release.checksums,
+                issues:      release.issues,
+                flags:       ReleaseFlags::from_bits(release.flags),
+            })
+            .collect(),
+    }
+}
+
+/// Recreates the fwupd device and releases that a recorded signal described.
+///
+/// Only the details which the recording kept are restored, which are those that frontends use.
+fn replayed_fwupd(data: FwupdData) -> FwupdSignal {
+    let device = FwupdDevice {
+        device_id: data.device_id.into(),
+        name: data.info.name.clone(),
+        version: data.info.current.as_ref().map_or("", Version::as_str).into(),
+        flags: fwupd_dbus::DeviceFlags::from_bits_truncate(data.flags),
+        ..FwupdDevice::default()
+    };
+
+    let releases = data.info.releases.iter().map(replayed_release).collect();
+
+    FwupdSignal {
+        info: replayed_info(data.info, Changelog::from_appstream),
+        device,
+        upgradeable: data.upgradeable,
+        releases,
+        flags: crate::DeviceFlags::from_bits(data.flags),
+        problems: data.problems,
+        parent: data.parent,
+    }
+}
+
+fn replayed_release(release: &ReleaseData) -> FwupdRelease {
+    FwupdRelease {
+        version: release.version.as_str().into(),
+        description: release.description.clone(),
+        size: release.size.unwrap_or(0),
+        license: release.license.clone().unwrap_or_default(),
+        vendor: release.vendor.clone().unwrap_or_default(),
+        checksums: release.checksums.clone(),
+        issues: release.issues.clone(),
+        flags: fwupd_dbus::ReleaseFlags::from_bits_truncate(release.flags),
+        ..FwupdRelease::default()
+    }
+}
+
+fn replayed_verification(verification: VerificationData) -> Option<Verification> {
+    let verification = match verification {
+        VerificationData::Verified { checksum } => Verification::Verified(checksum.parse().ok()?),
+        VerificationData::Unverified { checksum } => {
+            Verification::Unverified(checksum.parse().ok()?)
+        }
+        VerificationData::Mismatch { expected, actual } => Verification::Mismatch {
+            expected: expected.parse().ok()?,
+            actual:   actual.parse().ok()?,
+        },
+    };
+
+    Some(verification)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Entities;
+
+    #[test]
+    fn serials_are_redacted() {
+        let mut recorder = Recorder::new(Vec::new());
+        recorder.redact("SN0123456789");
+
+        let why = crate::Error::ThelioIoBoard("io SN0123456789".into());
+        recorder.signal(&FirmwareSignal::Error(None, why)).unwrap();
+
+        let recording = String::from_utf8(recorder.writer).unwrap();
+        assert!(!recording.contains("SN0123456789"));
+        assert!(recording.contains(REDACTED));
+    }
+
+    #[test]
+    fn recordings_are_read_back() {
+        let mut entities = Entities::default();
+        let entity = entities.create();
+
+        let mut recorder = Recorder::new(Vec::new());
+        recorder.event(&FirmwareEvent::Scan).unwrap();
+        recorder.signal(&FirmwareSignal::Scanning).unwrap();
+        recorder.signal(&FirmwareSignal::DownloadBegin(entity, 42)).unwrap();
+
+        let recording = Recording::read(&recorder.writer[..]).unwrap();
+        assert_eq!(
+            recording.entries,
+            [
+                Entry::Event { event: EventData::Scan },
+                Entry::Signal { signal: SignalData::Scanning },
+                Entry::Signal {
+                    signal: SignalData::DownloadBegin { entity: entity.into(), size: 42 },
+                },
+            ]
+        );
+
+        let future = r#"{"version":999,"data":{"entry":"unknown"}}"#;
+        assert!(matches!(
+            Recording::read(future.as_bytes()),
+            Err(Error::Version { line: 1, version: 999 })
+        ));
+    }
+
+    #[test]
+    fn replays_follow_the_entities_of_the_frontend() {
+        let mut recorded = Entities::default();
+        let _other = recorded.create();
+        let recorded = recorded.create();
+
+        let mut live = Entities::default();
+        let live = live.create();
+
+        let recording = Recording {
+            entries: vec![
+                Entry::Event { event: EventData::Scan },
+                Entry::Signal { signal: SignalData::ScanningComplete },
+                Entry::Event {
+                    event: EventData::S76System { entity: recorded.into(), digest: "a".into() },
+                },
+                Entry::Signal {
+                    signal: SignalData::Error {
+                        entity:  Some(recorded.into()),
+                        message: "failed".into(),
+                    },
+                },
+            ],
+        };
+
+        let (event_tx, event_rx) = mpsc::channel();
+        let (signal_tx, signal_rx) = mpsc::channel();
+        let replay = thread::spawn(move || replay_event_loop(recording, event_rx, signal_tx));
+
+        event_tx.send(FirmwareEvent::Scan).unwrap();
+        assert!(matches!(signal_rx.recv(), Ok(FirmwareSignal::ScanningComplete)));
+
+        let digest = System76Digest("b".into());
+        event_tx.send(FirmwareEvent::S76System(live, digest)).unwrap();
+        match signal_rx.recv() {
+            Ok(FirmwareSignal::Error(Some(entity), why)) => {
+                assert_eq!(entity, live);
+                assert_eq!(why.to_string(), "failed");
+            }
+            other => panic!("unexpected signal: {:?}", other),
+        }
+
+        event_tx.send(FirmwareEvent::Stop).unwrap();
+        replay.join().unwrap();
+    }
+}
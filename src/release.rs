@@ -0,0 +1,377 @@
+//! Release metadata which is shared between all firmware services.
+
+use crate::{Changelog, Version, VersionFormat};
+use fwupd_dbus::Release as FwupdRelease;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// How urgently a release should be installed, as reported by the vendor.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Urgency {
+    /// The vendor did not specify an urgency.
+    #[default]
+    Unknown,
+    /// The release may be installed at any time.
+    Low,
+    /// The release should be installed when convenient.
+    Medium,
+    /// The release should be installed soon.
+    High,
+    /// The release should be installed immediately.
+    Critical,
+}
+
+impl Urgency {
+    /// Converts the raw `FwupdReleaseUrgency` value reported by fwupd.
+    pub fn from_fwupd(raw: u32) -> Self {
+        match raw {
+            1 => Urgency::Low,
+            2 => Urgency::Medium,
+            3 => Urgency::High,
+            4 => Urgency::Critical,
+            _ => Urgency::Unknown,
+        }
+    }
+}
+
+/// How important it is to install an update, derived from the metadata of its releases.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// An update which carries no particular urgency.
+    Routine,
+    /// The vendor considers the update to be of high urgency.
+    Important,
+    /// The update fixes a security vulnerability.
+    Security,
+    /// The update fixes a security vulnerability, and is of high or critical urgency.
+    Critical,
+}
+
+impl Severity {
+    /// Returns `true` if the update fixes a security vulnerability.
+    pub fn is_security(self) -> bool { self >= Severity::Security }
+}
+
+/// Flags which fwupd assigns to a release, mirroring `FwupdReleaseFlags`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ReleaseFlags(u64);
+
+impl ReleaseFlags {
+    /// The release requires approval from an administrator.
+    pub const BLOCKED_APPROVAL: Self = Self(1 << 5);
+    /// The installation of this version has been blocked.
+    pub const BLOCKED_VERSION: Self = Self(1 << 4);
+    /// The release is on an alternate branch of firmware.
+    pub const IS_ALTERNATE_BRANCH: Self = Self(1 << 6);
+    /// The release was published by the community rather than the vendor.
+    pub const IS_COMMUNITY: Self = Self(1 << 7);
+    /// The release is older than the installed version.
+    pub const IS_DOWNGRADE: Self = Self(1 << 3);
+    /// The release is newer than the installed version.
+    pub const IS_UPGRADE: Self = Self(1 << 2);
+    /// The metadata was signed by a trusted key.
+    pub const TRUSTED_METADATA: Self = Self(1 << 1);
+    /// The payload was signed by a trusted key.
+    pub const TRUSTED_PAYLOAD: Self = Self(1 << 0);
+
+    /// Creates the flags from their raw bits.
+    pub fn from_bits(bits: u64) -> Self { Self(bits) }
+
+    /// The raw bits of these flags.
+    pub fn bits(self) -> u64 { self.0 }
+
+    /// Returns `true` if all of the flags in `other` are set.
+    pub fn contains(self, other: Self) -> bool { self.0 & other.0 == other.0 }
+}
+
+/// The date on which a release was published.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct ReleaseDate {
+    /// The year, such as 2020.
+    pub year:  u16,
+    /// The month, from 1 to 12.
+    pub month: u8,
+    /// The day of the month, from 1 to 31.
+    pub day:   u8,
+}
+
+impl ReleaseDate {
+    /// Creates a date if the fields describe a plausible date.
+    pub fn new(year: u16, month: u8, day: u8) -> Option<Self> {
+        if (1970..=9999).contains(&year) && (1..=12).contains(&month) && (1..=31).contains(&day) {
+            Some(Self { year, month, day })
+        } else {
+            None
+        }
+    }
+
+    /// Converts seconds since the UNIX Epoch into a date, in UTC.
+    ///
+    /// A timestamp of zero is treated as missing, as fwupd reports zero for unknown dates.
+    pub fn from_timestamp(seconds: u64) -> Option<Self> {
+        if seconds == 0 {
+            return None;
+        }
+
+        // Converts days since the epoch into a civil date, from Howard Hinnant's algorithm.
+        let days = (seconds / 86400) as i64 + 719_468;
+        let era = days / 146_097;
+        let day_of_era = days - era * 146_097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let mp = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+        Self::new(year as u16, month as u8, day as u8)
+    }
+
+    /// Parses the date formats which are found in firmware changelogs.
+    ///
+    /// Supports `YYYY-MM-DD`, `YYYY/MM/DD`, `YYYYMMDD`, `MM/DD/YYYY`, and ISO 8601 timestamps.
+    pub fn parse(input: &str) -> Option<Self> {
+        let input = input.trim();
+        let date = input.split(['T', ' ', '_']).next()?;
+
+        if date.len() == 8 && date.bytes().all(|b| b.is_ascii_digit()) {
+            return Self::new(
+                date[..4].parse().ok()?,
+                date[4..6].parse().ok()?,
+                date[6..].parse().ok()?,
+            );
+        }
+
+        let fields = date.split(['-', '/', '.']).collect::<Vec<_>>();
+        match fields.as_slice() {
+            [year, month, day] if year.len() == 4 => {
+                Self::new(year.parse().ok()?, month.parse().ok()?, day.parse().ok()?)
+            }
+            [month, day, year] if year.len() == 4 => {
+                Self::new(year.parse().ok()?, month.parse().ok()?, day.parse().ok()?)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ReleaseDate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+/// Metadata about a release of firmware, regardless of the service which provides it.
+#[derive(Clone, Debug, Default)]
+pub struct ReleaseInfo {
+    /// The version of firmware that this release installs.
+    pub version:     Version,
+    /// When the release was published.
+    pub date:        Option<ReleaseDate>,
+    /// The changelog of the release, as given by the firmware service.
+    pub description: Box<str>,
+    /// The changelog of the release, parsed into a structured document.
+    pub changelog:   Changelog,
+    /// How urgently the release should be installed.
+    pub urgency:     Urgency,
+    /// The size of the download, in bytes.
+    pub size:        Option<u64>,
+    /// The license that the firmware is distributed under.
+    pub license:     Option<Box<str>>,
+    /// The vendor which published the release.
+    pub vendor:      Option<Box<str>>,
+    /// Checksums of the firmware payload.
+    pub checksums:   Vec<Box<str>>,
+    /// Issues fixed by the release, such as CVE identifiers.
+    pub issues:      Vec<Box<str>>,
+    /// Flags which fwupd assigned to the release.
+    pub flags:       ReleaseFlags,
+}
+
+impl ReleaseInfo {
+    /// Collects the metadata of a release from fwupd.
+    pub fn from_fwupd(release: &FwupdRelease, format: VersionFormat) -> Self {
+        Self {
+            version:     Version::new(release.version.clone(), format),
+            date:        ReleaseDate::from_timestamp(release.created),
+            description: release.description.clone(),
+            changelog:   Changelog::from_appstream(&release.description),
+            urgency:     Urgency::from_fwupd(release.urgency as u32),
+            size:        Some(release.size).filter(|&size| size != 0),
+            license:     non_empty(&release.license),
+            vendor:      non_empty(&release.vendor),
+            checksums:   release.checksums.iter().map(|sum| Box::from(sum.as_ref())).collect(),
+            issues:      release.issues.iter().map(|issue| Box::from(issue.as_ref())).collect(),
+            flags:       ReleaseFlags::from_bits(release.flags.bits()),
+        }
+    }
+
+    /// Collects the metadata of a version in a System76 changelog.
+    pub fn from_system76(bios: &str, date: &str, description: &str) -> Self {
+        Self {
+            version: Version::new(bios, VersionFormat::System76),
+            date: ReleaseDate::parse(date),
+            description: description.into(),
+            changelog: Changelog::from_system76(description),
+            vendor: Some("System76".into()),
+            ..Self::default()
+        }
+    }
+
+    /// Returns `true` if the release addresses a security vulnerability.
+    ///
+    /// fwupd lists the CVEs and advisories that a release fixes as issues, whereas System76
+    /// changelogs only mention CVE identifiers in their descriptions.
+    pub fn is_security_fix(&self) -> bool {
+        !self.issues.is_empty() || self.description.contains("CVE-")
+    }
+
+    /// Classifies how important it is to install this release.
+    pub fn severity(&self) -> Severity {
+        let urgent = self.urgency >= Urgency::High;
+        match (self.is_security_fix(), urgent) {
+            (true, true) => Severity::Critical,
+            (true, false) => Severity::Security,
+            (false, true) => Severity::Important,
+            (false, false) => Severity::Routine,
+        }
+    }
+}
+
+/// Releases of a device, divided by their relation to the installed version and the target
+/// version of an update.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ReleaseRange<'a> {
+    /// Releases which have already been installed, from oldest to newest.
+    pub installed: &'a [ReleaseInfo],
+    /// Releases which the update will install, from oldest to newest.
+    pub pending:   &'a [ReleaseInfo],
+}
+
+impl<'a> ReleaseRange<'a> {
+    /// Divides releases, ordered from oldest to newest, into those which are no newer than
+    /// `current`, and those which are newer than `current` but no newer than `target`.
+    ///
+    /// Releases which are newer than `target` are excluded from both. If `current` is unknown,
+    /// no release is known to have been installed.
+    pub fn new(releases: &'a [ReleaseInfo], current: Option<&Version>, target: &Version) -> Self {
+        let start = current
+            .map_or(0, |current| releases.partition_point(|release| release.version <= *current));
+        let end = releases.partition_point(|release| release.version <= *target).max(start);
+
+        Self { installed: &releases[..start], pending: &releases[start..end] }
+    }
+}
+
+fn non_empty(input: &str) -> Option<Box<str>> {
+    Some(input.trim()).filter(|input| !input.is_empty()).map(Box::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn date_from_timestamp() {
+        assert_eq!(ReleaseDate::from_timestamp(0), None);
+        assert_eq!(ReleaseDate::from_timestamp(86400), ReleaseDate::new(1970, 1, 2));
+        assert_eq!(ReleaseDate::from_timestamp(951_782_400), ReleaseDate::new(2000, 2, 29));
+        assert_eq!(ReleaseDate::from_timestamp(1_580_688_000), ReleaseDate::new(2020, 2, 3));
+    }
+
+    #[test]
+    fn date_parse() {
+        let expected = ReleaseDate::new(2020, 2, 3);
+        assert_eq!(ReleaseDate::parse("2020-02-03"), expected);
+        assert_eq!(ReleaseDate::parse("2020/02/03"), expected);
+        assert_eq!(ReleaseDate::parse("20200203"), expected);
+        assert_eq!(ReleaseDate::parse("02/03/2020"), expected);
+        assert_eq!(ReleaseDate::parse("2020-02-03T12:00:00Z"), expected);
+        assert_eq!(ReleaseDate::parse("2020-02-03_35b3d61"), expected);
+        assert_eq!(ReleaseDate::parse(""), None);
+        assert_eq!(ReleaseDate::parse("2020-13-03"), None);
+        assert_eq!(ReleaseDate::parse("yesterday"), None);
+    }
+
+    #[test]
+    fn date_display() {
+        assert_eq!(ReleaseDate::new(2020, 2, 3).unwrap().to_string(), "2020-02-03");
+    }
+
+    #[test]
+    fn severity() {
+        let release = |urgency, issues: &[&str], description: &str| ReleaseInfo {
+            urgency,
+            issues: issues.iter().map(|&issue| Box::from(issue)).collect(),
+            description: description.into(),
+            ..ReleaseInfo::default()
+        };
+
+        assert_eq!(release(Urgency::Unknown, &[], "").severity(), Severity::Routine);
+        assert_eq!(release(Urgency::Medium, &[], "").severity(), Severity::Routine);
+        assert_eq!(release(Urgency::High, &[], "").severity(), Severity::Important);
+        assert_eq!(release(Urgency::Low, &["CVE-2020-0001"], "").severity(), Severity::Security);
+        assert_eq!(
+            release(Urgency::Unknown, &[], "Fixes CVE-2020-0001").severity(),
+            Severity::Security
+        );
+        assert_eq!(
+            release(Urgency::Critical, &["CVE-2020-0001"], "").severity(),
+            Severity::Critical
+        );
+        assert!(Severity::Critical.is_security());
+        assert!(!Severity::Important.is_security());
+    }
+
+    #[test]
+    fn range() {
+        let releases = ["1.0", "1.1", "1.2", "1.3"]
+            .iter()
+            .map(|&version| ReleaseInfo {
+                version: Version::new(version, VersionFormat::Pair),
+                ..ReleaseInfo::default()
+            })
+            .collect::<Vec<_>>();
+
+        let versions = |releases: &[ReleaseInfo]| {
+            releases.iter().map(|release| release.version.as_str().to_owned()).collect::<Vec<_>>()
+        };
+
+        let version = |version| Version::new(version, VersionFormat::Pair);
+
+        let range = ReleaseRange::new(&releases, Some(&version("1.1")), &version("1.2"));
+        assert_eq!(versions(range.installed), ["1.0", "1.1"]);
+        assert_eq!(versions(range.pending), ["1.2"]);
+
+        let range = ReleaseRange::new(&releases, Some(&version("1.1")), &version("1.3"));
+        assert_eq!(versions(range.pending), ["1.2", "1.3"]);
+
+        let range = ReleaseRange::new(&releases, Some(&version("0.9")), &version("1.3"));
+        assert!(range.installed.is_empty());
+        assert_eq!(versions(range.pending), ["1.0", "1.1", "1.2", "1.3"]);
+
+        let range = ReleaseRange::new(&releases, Some(&version("1.3")), &version("1.3"));
+        assert_eq!(versions(range.installed), ["1.0", "1.1", "1.2", "1.3"]);
+        assert!(range.pending.is_empty());
+
+        let range = ReleaseRange::new(&releases, Some(&version("1.3")), &version("1.0"));
+        assert!(range.pending.is_empty());
+
+        let range = ReleaseRange::new(&releases, None, &version("1.2"));
+        assert!(range.installed.is_empty());
+        assert_eq!(versions(range.pending), ["1.0", "1.1", "1.2"]);
+    }
+
+    #[test]
+    fn flags() {
+        let flags = ReleaseFlags::from_bits(0b101);
+        assert!(flags.contains(ReleaseFlags::TRUSTED_PAYLOAD));
+        assert!(flags.contains(ReleaseFlags::IS_UPGRADE));
+        assert!(!flags.contains(ReleaseFlags::TRUSTED_METADATA));
+    }
+}
@@ -0,0 +1,349 @@
+//! A machine-readable inventory of the firmware on this system.
+//!
+//! A report is assembled from the signals of a scan, and may be written as JSON or CSV.
+
+use crate::{
+    FirmwareSignal, FwupdSignal, MetadataAge, MetadataSource, SystemIdentity, ThelioIoSignal,
+    Version,
+};
+use serde::{Serialize, Serializer};
+use std::{fmt, fs, io, path::Path};
+
+/// The formats that a report may be written in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// A JSON document describing the system, its metadata, and its devices.
+    Json,
+    /// A table with a row for each device.
+    Csv,
+}
+
+impl ReportFormat {
+    /// Determines the format from the extension of a file name.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?;
+        if extension.eq_ignore_ascii_case("json") {
+            Some(ReportFormat::Json)
+        } else if extension.eq_ignore_ascii_case("csv") {
+            Some(ReportFormat::Csv)
+        } else {
+            None
+        }
+    }
+}
+
+/// The firmware of a device, as it is listed in a report.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct DeviceReport {
+    /// The name of the device.
+    pub name: Box<str>,
+    /// The service which manages the firmware of the device.
+    #[serde(serialize_with = "display")]
+    pub backend: MetadataSource,
+    /// The identifier that the service assigned to the device, if it has one.
+    pub id: Option<Box<str>>,
+    /// The remote that the latest release was obtained from.
+    pub remote: Option<Box<str>>,
+    /// The currently-installed version, or `None` if the device does not report it.
+    #[serde(serialize_with = "version")]
+    pub current: Option<Version>,
+    /// The latest version available, if one is known.
+    #[serde(serialize_with = "version")]
+    pub latest: Option<Version>,
+    /// Whether the latest version is newer than the current version.
+    pub upgradeable: bool,
+    /// Whether an update has been scheduled to be installed on the next reboot.
+    pub scheduled: bool,
+}
+
+/// An inventory of the firmware on this system.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Report {
+    /// The name of the system, if it could be read.
+    pub system: Option<Box<str>>,
+    /// The DMI identity of the system, including its firmware, if it was read.
+    pub identity: Option<SystemIdentity>,
+    /// When the report was generated, in seconds since the UNIX Epoch.
+    pub generated: u64,
+    /// How current the metadata of each service was when it was scanned.
+    #[serde(serialize_with = "metadata")]
+    pub metadata: Vec<MetadataAge>,
+    /// Every device that was discovered, in the order that they were discovered.
+    pub devices: Vec<DeviceReport>,
+}
+
+impl Report {
+    /// Creates an empty report for this system.
+    pub fn new() -> Self {
+        let identity = SystemIdentity::read();
+        Self {
+            system: identity.name().map(Box::from),
+            identity: Some(identity),
+            generated: crate::timestamp::current(),
+            ..Self::default()
+        }
+    }
+
+    /// Adds the information in a signal from a scan to the report.
+    ///
+    /// A new scan replaces the devices and metadata of the previous scan.
+    pub fn add(&mut self, signal: &FirmwareSignal) {
+        match signal {
+            FirmwareSignal::Scanning => {
+                self.generated = crate::timestamp::current();
+                self.metadata.clear();
+                self.devices.clear();
+            }
+            FirmwareSignal::Metadata(age) => {
+                self.metadata.retain(|metadata| metadata.source != age.source);
+                self.metadata.push(*age);
+            }
+            FirmwareSignal::Fwupd(FwupdSignal { info, device, upgradeable, releases, .. }) => {
+                self.devices.push(DeviceReport {
+                    name: info.name.clone(),
+                    backend: MetadataSource::Fwupd,
+                    id: Some(Box::from(device.device_id.as_ref())),
+                    remote: releases
+                        .last()
+                        .map(|release| release.remote_id.clone())
+                        .filter(|remote| !remote.is_empty()),
+                    current: info.current.clone(),
+                    latest: info.latest.clone(),
+                    upgradeable: *upgradeable,
+                    scheduled: device.needs_reboot(),
+                });
+            }
+            FirmwareSignal::S76System(info, _) => {
+                self.devices.push(DeviceReport {
+                    name: info.name.clone(),
+                    backend: MetadataSource::System76,
+                    id: None,
+                    remote: None,
+                    current: info.current.clone(),
+                    latest: info.latest.clone(),
+                    upgradeable: info.is_upgradeable(),
+                    scheduled: crate::s76_system_scheduled(),
+                });
+            }
+            FirmwareSignal::ThelioIo(ThelioIoSignal { board, info, .. }) => {
+                self.devices.push(DeviceReport {
+                    name: info.name.clone(),
+                    backend: MetadataSource::ThelioIo,
+                    id: Some(board.clone()),
+                    remote: None,
+                    current: info.current.clone(),
+                    latest: info.latest.clone(),
+                    upgradeable: info.is_upgradeable(),
+                    // Thelio I/O boards are flashed immediately.
+                    scheduled: false,
+                });
+            }
+            _ => (),
+        }
+    }
+
+    /// The devices with updates that are waiting to be installed on the next reboot.
+    pub fn scheduled(&self) -> impl Iterator<Item = &DeviceReport> {
+        self.devices.iter().filter(|device| device.scheduled)
+    }
+
+    /// The age of the metadata of the service which manages a device.
+    pub fn metadata_of(&self, device: &DeviceReport) -> Option<&MetadataAge> {
+        self.metadata.iter().find(|metadata| metadata.source == device.backend)
+    }
+
+    /// Renders the report in the given format.
+    pub fn render(&self, format: ReportFormat) -> String {
+        match format {
+            ReportFormat::Json => self.to_json(),
+            ReportFormat::Csv => self.to_csv(),
+        }
+    }
+
+    /// Writes the report to a file in the given format.
+    pub fn write(&self, path: &Path, format: ReportFormat) -> io::Result<()> {
+        fs::write(path, self.render(format))
+    }
+
+    /// Renders the report as a JSON document.
+    ///
+    /// The names of the devices with scheduled updates follow the devices, under `scheduled`.
+    pub fn to_json(&self) -> String {
+        let document = JsonReport {
+            report:    self,
+            scheduled: self.scheduled().map(|device| &*device.name).collect(),
+        };
+
+        let mut out = serde_json::to_string_pretty(&document)
+            .expect("serializing a report to a string cannot fail");
+        out.push('\n');
+        out
+    }
+
+    /// Renders the report as CSV, with a header row followed by a row for each device.
+    ///
+    /// The system identity and the age of each device's metadata are repeated on every row, so
+    /// that the reports of many systems can be concatenated into a single table.
+    pub fn to_csv(&self) -> String {
+        let mut out = String::from(
+            "system,backend,device,id,remote,current,latest,upgradeable,scheduled,\
+             metadata_refreshed,metadata_offline\r\n",
+        );
+
+        let system = self.system.as_deref().unwrap_or("");
+
+        for device in &self.devices {
+            let metadata = self.metadata_of(device);
+            let refreshed = metadata
+                .and_then(|metadata| metadata.refreshed)
+                .map_or_else(String::new, |refreshed| refreshed.to_string());
+            let offline =
+                metadata.map_or_else(String::new, |metadata| metadata.offline.to_string());
+
+            let fields = [
+                csv_field(system),
+                csv_field(&device.backend.to_string()),
+                csv_field(&device.name),
+                csv_field(device.id.as_deref().unwrap_or("")),
+                csv_field(device.remote.as_deref().unwrap_or("")),
+                csv_field(device.current.as_ref().map_or("", Version::as_str)),
+                csv_field(device.latest.as_ref().map_or("", Version::as_str)),
+                device.upgradeable.to_string(),
+                device.scheduled.to_string(),
+                refreshed,
+                offline,
+            ];
+
+            out.push_str(&fields.join(","));
+            out.push_str("\r\n");
+        }
+
+        out
+    }
+}
+
+/// The JSON document of a report, which also lists the devices with scheduled updates.
+#[derive(Serialize)]
+struct JsonReport<'a> {
+    #[serde(flatten)]
+    report:    &'a Report,
+    scheduled: Vec<&'a str>,
+}
+
+/// The age of metadata, as it is listed in a report.
+#[derive(Serialize)]
+struct MetadataReport {
+    #[serde(serialize_with = "display")]
+    source:    MetadataSource,
+    refreshed: Option<u64>,
+    age_days:  Option<u64>,
+    offline:   bool,
+}
+
+impl From<&MetadataAge> for MetadataReport {
+    fn from(age: &MetadataAge) -> Self {
+        Self {
+            source:    age.source,
+            refreshed: age.refreshed,
+            age_days:  age.days(),
+            offline:   age.offline,
+        }
+    }
+}
+
+/// Writes a value as it is displayed to the user.
+fn display<T: fmt::Display, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(value)
+}
+
+/// Writes a version as the string that the device reported.
+fn version<S: Serializer>(value: &Option<Version>, serializer: S) -> Result<S::Ok, S::Error> {
+    value.as_ref().map(Version::as_str).serialize(serializer)
+}
+
+/// Writes the age of metadata along with the number of days since it was refreshed.
+fn metadata<S: Serializer>(value: &[MetadataAge], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(value.iter().map(MetadataReport::from))
+}
+
+/// Quotes a field if it contains a delimiter, a quote, or a line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        ["\"", &value.replace('"', "\"\""), "\""].concat()
+    } else {
+        value.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::VersionFormat;
+
+    fn report() -> Report {
+        Report {
+            system: Some("System76 Thelio (thelio-r1)".into()),
+            identity: None,
+            generated: 1_580_688_000,
+            metadata: vec![MetadataAge {
+                source: MetadataSource::ThelioIo,
+                refreshed: Some(1_580_601_600),
+                offline: true,
+            }],
+            devices: vec![DeviceReport {
+                name: "Thelio I/O (\"front\", 1)".into(),
+                backend: MetadataSource::ThelioIo,
+                id: Some("front".into()),
+                remote: None,
+                current: Some(Version::new("0.1", VersionFormat::Plain)),
+                latest: Some(Version::new("0.2", VersionFormat::Plain)),
+                upgradeable: true,
+                scheduled: false,
+            }],
+        }
+    }
+
+    #[test]
+    fn format_from_path() {
+        assert_eq!(ReportFormat::from_path("a/report.JSON".as_ref()), Some(ReportFormat::Json));
+        assert_eq!(ReportFormat::from_path("report.csv".as_ref()), Some(ReportFormat::Csv));
+        assert_eq!(ReportFormat::from_path("report".as_ref()), None);
+    }
+
+    #[test]
+    fn csv() {
+        let csv = report().to_csv();
+        let mut lines = csv.split("\r\n");
+        assert!(lines.next().unwrap().starts_with("system,backend,device,"));
+        assert_eq!(
+            lines.next().unwrap(),
+            "System76 Thelio (thelio-r1),Thelio I/O,\"Thelio I/O (\"\"front\"\", 1)\",front,,0.1,\
+             0.2,true,false,1580601600,true"
+        );
+        assert_eq!(lines.next(), Some(""));
+    }
+
+    #[test]
+    fn json() {
+        let json: serde_json::Value = serde_json::from_str(&report().to_json()).unwrap();
+        assert_eq!(json["system"], "System76 Thelio (thelio-r1)");
+        assert!(json["identity"].is_null());
+        assert_eq!(json["generated"], 1_580_688_000);
+        assert_eq!(json["metadata"][0]["source"], "Thelio I/O");
+        assert_eq!(json["metadata"][0]["refreshed"], 1_580_601_600);
+        assert_eq!(json["metadata"][0]["offline"], true);
+
+        let device = &json["devices"][0];
+        assert_eq!(device["name"], "Thelio I/O (\"front\", 1)");
+        assert_eq!(device["backend"], "Thelio I/O");
+        assert!(device["remote"].is_null());
+        assert_eq!(device["current"], "0.1");
+        assert_eq!(device["latest"], "0.2");
+        assert_eq!(device["upgradeable"], true);
+        assert_eq!(json["scheduled"], serde_json::json!([]));
+
+        let empty: serde_json::Value = serde_json::from_str(&Report::default().to_json()).unwrap();
+        assert_eq!(empty["metadata"], serde_json::json!([]));
+        assert_eq!(empty["devices"], serde_json::json!([]));
+    }
+}
@@ -0,0 +1,143 @@
+//! Requests for the user to interact with a device while its firmware is being updated.
+
+use crate::{cache, download, Entity};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// When a request is to be acted upon.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RequestKind {
+    /// The request must be acted upon now, for the update to continue.
+    Immediate,
+    /// The request is to be acted upon once the update has completed.
+    Post,
+}
+
+/// Requests which fwupd identifies, so that their instructions may be localized.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum WellKnownRequest {
+    /// The device must not be powered off until the update has completed.
+    DoNotPowerOff,
+    /// The USB cable of the device must be inserted.
+    InsertUsbCable,
+    /// The unlock button of the device must be pressed.
+    PressUnlock,
+    /// The device must be removed, and then inserted again.
+    RemoveReplug,
+    /// The USB cable of the device must be removed.
+    RemoveUsbCable,
+    /// The device must be removed, and then inserted again, to install the update.
+    ReplugInstall,
+    /// The power cable of the device must be removed, and then inserted again.
+    ReplugPower,
+}
+
+impl WellKnownRequest {
+    /// The well-known request with the ID that fwupd assigned to it.
+    pub fn from_id(id: &str) -> Option<Self> {
+        let request = match id {
+            "org.freedesktop.fwupd.request.do-not-power-off" => WellKnownRequest::DoNotPowerOff,
+            "org.freedesktop.fwupd.request.insert-usb-cable" => WellKnownRequest::InsertUsbCable,
+            "org.freedesktop.fwupd.request.press-unlock" => WellKnownRequest::PressUnlock,
+            "org.freedesktop.fwupd.request.remove-replug" => WellKnownRequest::RemoveReplug,
+            "org.freedesktop.fwupd.request.remove-usb-cable" => WellKnownRequest::RemoveUsbCable,
+            "org.freedesktop.fwupd.replug-install" => WellKnownRequest::ReplugInstall,
+            "org.freedesktop.fwupd.request.replug-power" => WellKnownRequest::ReplugPower,
+            _ => return None,
+        };
+
+        Some(request)
+    }
+}
+
+/// A request from fwupd for the user to interact with a device.
+#[derive(Clone, Debug)]
+pub struct DeviceRequest {
+    /// The ID of the request, which identifies well-known requests.
+    pub id: Box<str>,
+    /// When the request is to be acted upon.
+    pub kind: RequestKind,
+    /// The entity of the device, if the request concerns a device that was acted upon.
+    pub entity: Option<Entity>,
+    /// The ID of the device that fwupd assigned.
+    pub device_id: Box<str>,
+    /// The instructions which fwupd gave, in English.
+    pub message: String,
+    /// The local path of an image which illustrates the request, if one was given.
+    pub image: Option<PathBuf>,
+}
+
+impl DeviceRequest {
+    /// Creates the request from a fwupd request, fetching its image if it is a remote one.
+    pub(crate) fn from_fwupd(
+        request: fwupd_dbus::Request,
+        entity: Option<Entity>,
+        offline: bool,
+    ) -> Self {
+        let kind = match request.kind {
+            fwupd_dbus::RequestKind::Post => RequestKind::Post,
+            // Requests of an unknown kind are shown right away, in case they must be.
+            _ => RequestKind::Immediate,
+        };
+
+        let image = request.image.as_deref().and_then(|uri| image(uri, offline));
+
+        Self {
+            id: request.id,
+            kind,
+            entity,
+            device_id: request.device_id,
+            message: request.update_message,
+            image,
+        }
+    }
+
+    /// The request, if it is one which fwupd identifies.
+    pub fn well_known(&self) -> Option<WellKnownRequest> { WellKnownRequest::from_id(&self.id) }
+}
+
+/// The local path of the image at `uri`, which is downloaded into the cache if it is remote.
+fn image(uri: &str, offline: bool) -> Option<PathBuf> {
+    if let Some(path) = download::local_path(uri) {
+        return Some(path.to_owned());
+    }
+
+    let path = cache::cache(["requests/", download::filename(uri)?].concat()).ok()?;
+
+    if path.exists() {
+        return Some(path);
+    }
+
+    if offline {
+        return None;
+    }
+
+    match download::download(uri, &path, |_| (), |_| ()) {
+        Ok(()) => Some(path),
+        Err(why) => {
+            warn!("failed to download image of device request: {}", why);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn well_known() {
+        assert_eq!(
+            WellKnownRequest::from_id("org.freedesktop.fwupd.request.remove-replug"),
+            Some(WellKnownRequest::RemoveReplug)
+        );
+
+        assert_eq!(
+            WellKnownRequest::from_id("org.freedesktop.fwupd.replug-install"),
+            Some(WellKnownRequest::ReplugInstall)
+        );
+
+        assert_eq!(WellKnownRequest::from_id("com.example.request.custom"), None);
+    }
+}
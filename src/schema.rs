@@ -0,0 +1,724 @@
+//! Serializable mirrors of the events and signals of the core.
+//!
+//! The backend types hold fwupd and system76-firmware handles which cannot be serialized, so
+//! processes which drive the core from outside, and tests which inspect its output, exchange these
+//! types instead. Every record carries the version of the schema that it was written with.
+
+use crate::{
+    format_error, DeviceProblem, Entity, FirmwareEvent, FirmwareInfo, FirmwareSignal, FwupdSignal,
+    HostSecurity, Integrity, MetadataAge, ReleaseDate, ReleaseInfo, RequestKind, SecuritySnapshot,
+    Severity, ThelioIoSignal, Urgency, Verification, Version,
+};
+use serde::{Deserialize, Serialize};
+use slotmap::{Key, KeyData};
+use std::path::PathBuf;
+
+/// The version of the schema, which is incremented whenever a change would break older readers.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Data paired with the version of the schema that it was written with.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Record<T> {
+    /// The version of the schema.
+    pub version: u32,
+    /// The data of the record.
+    pub data:    T,
+}
+
+impl<T> Record<T> {
+    /// Wraps data with the current version of the schema.
+    pub fn new(data: T) -> Self { Self { version: SCHEMA_VERSION, data } }
+
+    /// Whether the record was written with a version of the schema that this build can read.
+    pub fn is_supported(&self) -> bool { self.version == SCHEMA_VERSION }
+}
+
+/// An entity, as an integer which is stable for as long as the entity exists.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct EntityId(pub u64);
+
+impl From<Entity> for EntityId {
+    fn from(entity: Entity) -> Self { Self(entity.data().as_ffi()) }
+}
+
+impl From<EntityId> for Entity {
+    fn from(id: EntityId) -> Self { KeyData::from_ffi(id.0).into() }
+}
+
+/// Information about a device and its current and latest firmware.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct InfoData {
+    /// The name of the device.
+    pub name:             Box<str>,
+    /// The currently-installed version, or `None` if the device does not report it.
+    pub current:          Option<Version>,
+    /// The latest version of firmware for the device.
+    pub latest:           Option<Version>,
+    /// The time required for the firmware to be flashed, in seconds.
+    pub install_duration: u32,
+    /// The severity of updating to the latest version, if an update is available.
+    pub severity:         Option<Severity>,
+    /// Every release known for the firmware, from oldest to newest.
+    pub releases:         Vec<ReleaseData>,
+}
+
+impl From<&FirmwareInfo> for InfoData {
+    fn from(info: &FirmwareInfo) -> Self {
+        Self {
+            name:             info.name.clone(),
+            current:          info.current.clone(),
+            latest:           info.latest.clone(),
+            install_duration: info.install_duration,
+            severity:         info.severity(),
+            releases:         info.releases.iter().map(ReleaseData::from).collect(),
+        }
+    }
+}
+
+/// The metadata of a release of firmware.
+///
+/// The structured changelog is omitted, as it is parsed from the description.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReleaseData {
+    /// The version of firmware that the release installs.
+    pub version:     Version,
+    /// When the release was published.
+    pub date:        Option<ReleaseDate>,
+    /// The changelog of the release, as given by the firmware service.
+    pub description: Box<str>,
+    /// How urgently the release should be installed.
+    pub urgency:     Urgency,
+    /// How important it is to install the release.
+    pub severity:    Severity,
+    /// The size of the download, in bytes.
+    pub size:        Option<u64>,
+    /// The license that the firmware is distributed under.
+    pub license:     Option<Box<str>>,
+    /// The vendor which published the release.
+    pub vendor:      Option<Box<str>>,
+    /// Checksums of the firmware payload.
+    pub checksums:   Vec<Box<str>>,
+    /// Issues fixed by the release, such as CVE identifiers.
+    pub issues:      Vec<Box<str>>,
+    /// The bits of the flags which fwupd assigned to the release.
+    pub flags:       u64,
+}
+
+impl From<&ReleaseInfo> for ReleaseData {
+    fn from(release: &ReleaseInfo) -> Self {
+        Self {
+            version:     release.version.clone(),
+            date:        release.date,
+            description: release.description.clone(),
+            urgency:     release.urgency,
+            severity:    release.severity(),
+            size:        release.size,
+            license:     release.license.clone(),
+            vendor:      release.vendor.clone(),
+            checksums:   release.checksums.clone(),
+            issues:      release.issues.clone(),
+            flags:       release.flags.bits(),
+        }
+    }
+}
+
+/// A device which fwupd manages.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FwupdData {
+    /// Information about the device and its firmware.
+    pub info:        InfoData,
+    /// The ID of the device that fwupd assigned.
+    pub device_id:   Box<str>,
+    /// Whether the firmware is upgradeable.
+    pub upgradeable: bool,
+    /// The bits of the flags which fwupd assigned to the device.
+    pub flags:       u64,
+    /// Conditions which fwupd reported for the device.
+    pub problems:    Vec<DeviceProblem>,
+    /// The ID of the parent device, if the device belongs to a composite device.
+    pub parent:      Option<Box<str>>,
+}
+
+impl From<&FwupdSignal> for FwupdData {
+    fn from(signal: &FwupdSignal) -> Self {
+        Self {
+            info:        InfoData::from(&signal.info),
+            device_id:   Box::from(signal.device.device_id.as_ref()),
+            upgradeable: signal.upgradeable,
+            flags:       signal.flags.bits(),
+            problems:    signal.problems.clone(),
+            parent:      signal.parent.clone(),
+        }
+    }
+}
+
+/// A Thelio I/O board.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ThelioIoData {
+    /// The identifier of the board, as reported by system76-firmware.
+    pub board:  Box<str>,
+    /// Information about the board and its firmware.
+    pub info:   InfoData,
+    /// The digest of the latest firmware, if it could be downloaded.
+    pub digest: Option<Box<str>>,
+}
+
+impl From<&ThelioIoSignal> for ThelioIoData {
+    fn from(signal: &ThelioIoSignal) -> Self {
+        Self {
+            board:  signal.board.clone(),
+            info:   InfoData::from(&signal.info),
+            digest: signal.digest.as_ref().map(|digest| digest.0.as_str().into()),
+        }
+    }
+}
+
+/// A request for user interaction which a device made.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RequestData {
+    /// The ID of the request, which identifies well-known requests.
+    pub id:        Box<str>,
+    /// When the request is to be acted upon.
+    pub kind:      RequestKind,
+    /// The entity of the device, if the request concerns a device that was acted upon.
+    pub entity:    Option<EntityId>,
+    /// The ID of the device that fwupd assigned.
+    pub device_id: Box<str>,
+    /// The instructions which fwupd gave, in English.
+    pub message:   String,
+    /// The local path of an image which illustrates the request, if one was given.
+    pub image:     Option<PathBuf>,
+}
+
+/// The result of verifying the firmware which is installed on a device.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum IntegrityData {
+    /// The firmware matches its known-good checksums.
+    Passed,
+    /// The firmware does not match its known-good checksums.
+    Failed {
+        /// Why fwupd considered the firmware to be changed.
+        reason: Box<str>,
+    },
+    /// There are no known-good checksums to verify the firmware against.
+    NoBaseline,
+}
+
+impl From<&Integrity> for IntegrityData {
+    fn from(integrity: &Integrity) -> Self {
+        match integrity {
+            Integrity::Passed => IntegrityData::Passed,
+            Integrity::Failed(reason) => IntegrityData::Failed { reason: reason.clone() },
+            Integrity::NoBaseline => IntegrityData::NoBaseline,
+        }
+    }
+}
+
+/// The outcome of verifying a downloaded firmware payload.
+///
+/// Checksums are written as `kind:digest`, which `Checksum` parses.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum VerificationData {
+    /// The payload matched the checksum in the release metadata.
+    Verified {
+        /// The checksum which the payload matched.
+        checksum: String,
+    },
+    /// The release metadata had no checksum, so the payload's own checksum is given instead.
+    Unverified {
+        /// The SHA-256 checksum of the payload.
+        checksum: String,
+    },
+    /// The payload did not match the checksum in the release metadata.
+    Mismatch {
+        /// The checksum given by the release metadata.
+        expected: String,
+        /// The checksum of the payload that was downloaded.
+        actual:   String,
+    },
+}
+
+impl From<&Verification> for VerificationData {
+    fn from(verification: &Verification) -> Self {
+        match verification {
+            Verification::Verified(checksum) => {
+                VerificationData::Verified { checksum: checksum.to_string() }
+            }
+            Verification::Unverified(checksum) => {
+                VerificationData::Unverified { checksum: checksum.to_string() }
+            }
+            Verification::Mismatch { expected, actual } => VerificationData::Mismatch {
+                expected: expected.to_string(),
+                actual:   actual.to_string(),
+            },
+        }
+    }
+}
+
+/// A serializable mirror of `FirmwareSignal`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "signal", rename_all = "snake_case")]
+pub enum SignalData {
+    /// The known-good checksums of a device's firmware were recorded.
+    BaselineRecorded {
+        /// The entity of the device.
+        entity: EntityId,
+    },
+    /// The staged update of a device was activated.
+    DeviceActivated {
+        /// The entity of the device.
+        entity: EntityId,
+    },
+    /// A device has initiated the flashing process.
+    DeviceFlashing {
+        /// The entity of the device.
+        entity: EntityId,
+    },
+    /// A device was unlocked.
+    DeviceUnlocked {
+        /// The entity of the device.
+        entity: EntityId,
+    },
+    /// A device was updated.
+    DeviceUpdated {
+        /// The entity of the device.
+        entity: EntityId,
+    },
+    /// The firmware installed on a device is being verified.
+    DeviceVerifying {
+        /// The entity of the device.
+        entity: EntityId,
+    },
+    /// The result of verifying the firmware installed on a device.
+    DeviceVerified {
+        /// The entity of the device.
+        entity:    EntityId,
+        /// The result of the verification.
+        integrity: IntegrityData,
+    },
+    /// A device has a request for user interaction.
+    DeviceRequest {
+        /// The request of the device.
+        request: RequestData,
+    },
+    /// The firmware of an entity is being downloaded.
+    DownloadBegin {
+        /// The entity of the device.
+        entity: EntityId,
+        /// The size of the download, in bytes.
+        size:   u64,
+    },
+    /// The firmware of an entity was downloaded.
+    DownloadComplete {
+        /// The entity of the device.
+        entity: EntityId,
+    },
+    /// More of the firmware of an entity was downloaded.
+    DownloadUpdate {
+        /// The entity of the device.
+        entity:     EntityId,
+        /// The number of bytes downloaded since the last update.
+        downloaded: u64,
+    },
+    /// An error occurred.
+    Error {
+        /// The entity of the device, if the error concerns one.
+        entity:  Option<EntityId>,
+        /// The error, followed by each of its causes.
+        message: String,
+    },
+    /// Fwupd firmware was discovered.
+    Fwupd {
+        /// The device which was discovered.
+        device: FwupdData,
+    },
+    /// Describes how current the metadata of a firmware service is.
+    Metadata {
+        /// The age of the metadata.
+        age: MetadataAge,
+    },
+    /// Devices are being scanned.
+    Scanning,
+    /// The security attributes of the host, and the results of the last run, if there was one.
+    Security {
+        /// The current security attributes of the host.
+        security: HostSecurity,
+        /// The results of the last run.
+        previous: Option<SecuritySnapshot>,
+    },
+    /// Scanning has completed.
+    ScanningComplete,
+    /// System firmware was scheduled for installation.
+    SystemScheduled,
+    /// System76 system firmware was discovered.
+    S76System {
+        /// Information about the system firmware.
+        info:   InfoData,
+        /// The digest of the latest firmware, if it could be downloaded.
+        digest: Option<Box<str>>,
+    },
+    /// A Thelio I/O board was discovered.
+    ThelioIo {
+        /// The board which was discovered.
+        board: ThelioIoData,
+    },
+    /// The downloaded firmware of an entity is being verified.
+    Verifying {
+        /// The entity of the device.
+        entity: EntityId,
+    },
+    /// The result of verifying the downloaded firmware of an entity.
+    Verified {
+        /// The entity of the device.
+        entity:       EntityId,
+        /// The result of the verification.
+        verification: VerificationData,
+    },
+}
+
+impl From<&FirmwareSignal> for SignalData {
+    fn from(signal: &FirmwareSignal) -> Self {
+        let id = |entity: &Entity| EntityId::from(*entity);
+
+        match signal {
+            FirmwareSignal::BaselineRecorded(entity) => {
+                SignalData::BaselineRecorded { entity: id(entity) }
+            }
+            FirmwareSignal::DeviceActivated(entity) => {
+                SignalData::DeviceActivated { entity: id(entity) }
+            }
+            FirmwareSignal::DeviceFlashing(entity) => {
+                SignalData::DeviceFlashing { entity: id(entity) }
+            }
+            FirmwareSignal::DeviceUnlocked(entity) => {
+                SignalData::DeviceUnlocked { entity: id(entity) }
+            }
+            FirmwareSignal::DeviceUpdated(entity) => {
+                SignalData::DeviceUpdated { entity: id(entity) }
+            }
+            FirmwareSignal::DeviceVerifying(entity) => {
+                SignalData::DeviceVerifying { entity: id(entity) }
+            }
+            FirmwareSignal::DeviceVerified(entity, integrity) => SignalData::DeviceVerified {
+                entity:    id(entity),
+                integrity: IntegrityData::from(integrity),
+            },
+            FirmwareSignal::DeviceRequest(request) => SignalData::DeviceRequest {
+                request: RequestData {
+                    id:        request.id.clone(),
+                    kind:      request.kind,
+                    entity:    request.entity.map(EntityId::from),
+                    device_id: request.device_id.clone(),
+                    message:   request.message.clone(),
+                    image:     request.image.clone(),
+                },
+            },
+            FirmwareSignal::DownloadBegin(entity, size) => {
+                SignalData::DownloadBegin { entity: id(entity), size: *size }
+            }
+            FirmwareSignal::DownloadComplete(entity) => {
+                SignalData::DownloadComplete { entity: id(entity) }
+            }
+            FirmwareSignal::DownloadUpdate(entity, downloaded) => {
+                SignalData::DownloadUpdate { entity: id(entity), downloaded: *downloaded as u64 }
+            }
+            FirmwareSignal::Error(entity, why) => SignalData::Error {
+                entity:  entity.as_ref().map(id),
+                message: format_error(why),
+            },
+            FirmwareSignal::Fwupd(signal) => SignalData::Fwupd { device: FwupdData::from(signal) },
+            FirmwareSignal::Metadata(age) => SignalData::Metadata { age: *age },
+            FirmwareSignal::Scanning => SignalData::Scanning,
+            FirmwareSignal::Security(security, previous) => SignalData::Security {
+                security: security.clone(),
+                previous: previous.clone(),
+            },
+            FirmwareSignal::ScanningComplete => SignalData::ScanningComplete,
+            FirmwareSignal::SystemScheduled => SignalData::SystemScheduled,
+            FirmwareSignal::S76System(info, digest) => SignalData::S76System {
+                info:   InfoData::from(info),
+                digest: digest.as_ref().map(|digest| digest.0.as_str().into()),
+            },
+            FirmwareSignal::ThelioIo(signal) => {
+                SignalData::ThelioIo { board: ThelioIoData::from(signal) }
+            }
+            FirmwareSignal::Verifying(entity) => SignalData::Verifying { entity: id(entity) },
+            FirmwareSignal::Verified(entity, verification) => SignalData::Verified {
+                entity:       id(entity),
+                verification: VerificationData::from(verification),
+            },
+        }
+    }
+}
+
+impl SignalData {
+    /// The entity which the signal concerns, if it concerns one.
+    pub fn entity(&self) -> Option<EntityId> {
+        match self {
+            SignalData::BaselineRecorded { entity }
+            | SignalData::DeviceActivated { entity }
+            | SignalData::DeviceFlashing { entity }
+            | SignalData::DeviceUnlocked { entity }
+            | SignalData::DeviceUpdated { entity }
+            | SignalData::DeviceVerifying { entity }
+            | SignalData::DeviceVerified { entity, .. }
+            | SignalData::DownloadBegin { entity, .. }
+            | SignalData::DownloadComplete { entity }
+            | SignalData::DownloadUpdate { entity, .. }
+            | SignalData::Verifying { entity }
+            | SignalData::Verified { entity, .. } => Some(*entity),
+            SignalData::DeviceRequest { request } => request.entity,
+            SignalData::Error { entity, .. } => *entity,
+            _ => None,
+        }
+    }
+}
+
+/// A fwupd device which an event acts upon.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceTarget {
+    /// The entity of the device.
+    pub entity:    EntityId,
+    /// The ID of the device that fwupd assigned.
+    pub device_id: Box<str>,
+}
+
+/// A release which an event installs on a fwupd device.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UpdateTarget {
+    /// The entity of the device.
+    pub entity:    EntityId,
+    /// The ID of the device that fwupd assigned.
+    pub device_id: Box<str>,
+    /// The version of the release.
+    pub version:   Box<str>,
+}
+
+/// A Thelio I/O board which an event flashes.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BoardTarget {
+    /// The entity of the board.
+    pub entity: EntityId,
+    /// The identifier of the board, as reported by system76-firmware.
+    pub board:  Box<str>,
+}
+
+/// A serializable mirror of `FirmwareEvent`.
+///
+/// Devices and releases are identified by their IDs and versions, rather than the fwupd values
+/// which the event loop acts upon.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum EventData {
+    /// Activate the staged update of a fwupd device.
+    Activate {
+        /// The device to activate.
+        device: DeviceTarget,
+    },
+    /// Upgrade the firmware of a fwupd-compatible device.
+    Fwupd {
+        /// The device and the release to install.
+        update: UpdateTarget,
+    },
+    /// Upgrade the firmware of the devices of a composite device, in order.
+    FwupdComposite {
+        /// The devices and the releases to install, in the order that they are installed.
+        updates: Vec<UpdateTarget>,
+    },
+    /// Stop processing events.
+    Stop,
+    /// Upgrade system firmware for System76 systems.
+    S76System {
+        /// The entity of the system firmware.
+        entity: EntityId,
+        /// The digest of the firmware to install.
+        digest: Box<str>,
+    },
+    /// Search for available firmware devices.
+    Scan,
+    /// Unlock a locked fwupd device.
+    Unlock {
+        /// The device to unlock.
+        device: DeviceTarget,
+    },
+    /// Verify the firmware installed on fwupd devices against their known-good checksums.
+    Verify {
+        /// The devices to verify.
+        devices: Vec<DeviceTarget>,
+    },
+    /// Record the firmware installed on fwupd devices as their known-good baseline.
+    VerifyBaseline {
+        /// The devices to record.
+        devices: Vec<DeviceTarget>,
+    },
+    /// Upgrade the firmware of Thelio I/O boards to the given revision.
+    ThelioIo {
+        /// The digest of the firmware to install.
+        digest:  Box<str>,
+        /// The revision of the firmware.
+        version: Version,
+        /// Every board which is flashed.
+        boards:  Vec<BoardTarget>,
+    },
+}
+
+impl EventData {
+    /// The entities which the event targets, and the device IDs or boards which identify them.
+    ///
+    /// System firmware has no identifier, so it is identified by an empty string.
+    pub fn targets(&self) -> Vec<(EntityId, &str)> {
+        match self {
+            EventData::Activate { device } | EventData::Unlock { device } => {
+                vec![(device.entity, &device.device_id)]
+            }
+            EventData::Fwupd { update } => vec![(update.entity, &update.device_id)],
+            EventData::FwupdComposite { updates } => {
+                updates.iter().map(|update| (update.entity, &*update.device_id)).collect()
+            }
+            EventData::Verify { devices } | EventData::VerifyBaseline { devices } => {
+                devices.iter().map(|device| (device.entity, &*device.device_id)).collect()
+            }
+            EventData::S76System { entity, .. } => vec![(*entity, "")],
+            EventData::ThelioIo { boards, .. } => {
+                boards.iter().map(|board| (board.entity, &*board.board)).collect()
+            }
+            EventData::Scan | EventData::Stop => Vec::new(),
+        }
+    }
+}
+
+impl From<&FirmwareEvent> for EventData {
+    fn from(event: &FirmwareEvent) -> Self {
+        let device = |entity: &Entity, device: &crate::FwupdDevice| DeviceTarget {
+            entity:    EntityId::from(*entity),
+            device_id: Box::from(device.device_id.as_ref()),
+        };
+
+        let update =
+            |entity: &Entity, device: &crate::FwupdDevice, release: &crate::FwupdRelease| {
+                UpdateTarget {
+                    entity:    EntityId::from(*entity),
+                    device_id: Box::from(device.device_id.as_ref()),
+                    version:   release.version.clone(),
+                }
+            };
+
+        match event {
+            FirmwareEvent::Activate(entity, fwupd) => {
+                EventData::Activate { device: device(entity, fwupd) }
+            }
+            FirmwareEvent::Fwupd(entity, fwupd, release) => {
+                EventData::Fwupd { update: update(entity, fwupd, release) }
+            }
+            FirmwareEvent::FwupdComposite(devices) => EventData::FwupdComposite {
+                updates: devices
+                    .iter()
+                    .map(|(entity, fwupd, release)| update(entity, fwupd, release))
+                    .collect(),
+            },
+            FirmwareEvent::Stop => EventData::Stop,
+            FirmwareEvent::S76System(entity, digest) => EventData::S76System {
+                entity: EntityId::from(*entity),
+                digest: digest.0.as_str().into(),
+            },
+            FirmwareEvent::Scan => EventData::Scan,
+            FirmwareEvent::Unlock(entity, fwupd) => {
+                EventData::Unlock { device: device(entity, fwupd) }
+            }
+            FirmwareEvent::Verify(devices) => EventData::Verify {
+                devices: devices.iter().map(|(entity, fwupd)| device(entity, fwupd)).collect(),
+            },
+            FirmwareEvent::VerifyBaseline(devices) => EventData::VerifyBaseline {
+                devices: devices.iter().map(|(entity, fwupd)| device(entity, fwupd)).collect(),
+            },
+            FirmwareEvent::ThelioIo(digest, version, boards) => EventData::ThelioIo {
+                digest:  digest.0.as_str().into(),
+                version: version.clone(),
+                boards:  boards
+                    .iter()
+                    .map(|(entity, board)| BoardTarget {
+                        entity: EntityId::from(*entity),
+                        board:  board.clone(),
+                    })
+                    .collect(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Entities, Error, System76Digest, VersionFormat};
+
+    #[test]
+    fn entity_ids_round_trip() {
+        let mut entities = Entities::default();
+        let _first = entities.create();
+        let entity = entities.create();
+
+        let id = EntityId::from(entity);
+        assert_eq!(Entity::from(id), entity);
+        assert_eq!(serde_json::to_string(&id).unwrap(), id.0.to_string());
+    }
+
+    #[test]
+    fn signals_are_tagged_and_versioned() {
+        let mut entities = Entities::default();
+        let entity = entities.create();
+
+        let signal = FirmwareSignal::DownloadBegin(entity, 1024);
+        let record = Record::new(SignalData::from(&signal));
+        let json = serde_json::to_value(&record).unwrap();
+
+        assert_eq!(json["version"], SCHEMA_VERSION);
+        assert_eq!(json["data"]["signal"], "download_begin");
+        assert_eq!(json["data"]["size"], 1024);
+
+        let read: Record<SignalData> = serde_json::from_value(json).unwrap();
+        assert!(read.is_supported());
+        assert_eq!(read, record);
+    }
+
+    #[test]
+    fn events_identify_their_targets() {
+        let mut entities = Entities::default();
+        let entity = entities.create();
+
+        let event = FirmwareEvent::S76System(entity, System76Digest("abc".into()));
+        let EventData::S76System { entity: id, digest } = EventData::from(&event) else {
+            panic!("expected system firmware");
+        };
+
+        assert_eq!(Entity::from(id), entity);
+        assert_eq!(&*digest, "abc");
+
+        let version = Version::new("1.2.3", VersionFormat::Triplet);
+        let boards = vec![(entity, Box::from("io"))];
+        let event = FirmwareEvent::ThelioIo(System76Digest("abc".into()), version, boards);
+        let json = serde_json::to_value(EventData::from(&event)).unwrap();
+
+        assert_eq!(json["event"], "thelio_io");
+        assert_eq!(json["boards"][0]["board"], "io");
+
+        let event = EventData::from(&event);
+        assert_eq!(event.targets(), vec![(EntityId::from(entity), "io")]);
+        assert!(EventData::Scan.targets().is_empty());
+    }
+
+    #[test]
+    fn signals_identify_their_entities() {
+        let mut entities = Entities::default();
+        let entity = entities.create();
+
+        let signal = SignalData::from(&FirmwareSignal::DownloadUpdate(entity, 512));
+        assert_eq!(signal.entity(), Some(EntityId::from(entity)));
+
+        let signal = SignalData::from(&FirmwareSignal::Error(None, Error::Replayed("no".into())));
+        assert_eq!(signal.entity(), None);
+        assert_eq!(SignalData::from(&FirmwareSignal::ScanningComplete).entity(), None);
+    }
+}
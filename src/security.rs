@@ -0,0 +1,414 @@
+//! Host security attributes, and the Host Security ID (HSI) of the platform, as reported by fwupd.
+//!
+//! The results of each check are stored in the XDG data directory, so that changes to the
+//! security of the platform can be shown after an update.
+
+use dbus::{
+    arg::{prop_cast, PropMap},
+    blocking::{stdintf::org_freedesktop_dbus::Properties, Connection},
+};
+use serde::{Deserialize, Serialize};
+use std::{fs, io, path::PathBuf, time::Duration};
+
+const FWUPD_DEST: &str = "org.freedesktop.fwupd";
+const FWUPD_IFACE: &str = "org.freedesktop.fwupd";
+
+const FLAG_SUCCESS: u64 = 1 << 0;
+const FLAG_OBSOLETED: u64 = 1 << 1;
+const FLAG_ACTION_CONTACT_OEM: u64 = 1 << 11;
+const FLAG_ACTION_CONFIG_FW: u64 = 1 << 12;
+const FLAG_ACTION_CONFIG_OS: u64 = 1 << 13;
+
+/// An error that may occur when fetching or storing host security attributes.
+#[derive(Debug, Error)]
+pub enum Error {
+    /// The system bus could not be connected to.
+    #[error("failed to connect to the system bus")]
+    Connect(#[source] dbus::Error),
+    /// fwupd did not return the security attributes of the host.
+    #[error("failed to get host security attributes from fwupd")]
+    Attributes(#[source] dbus::Error),
+    /// fwupd did not return the Host Security ID.
+    #[error("failed to get host security ID from fwupd")]
+    HostSecurityId(#[source] dbus::Error),
+    /// The XDG base directories could not be determined.
+    #[error("failed to get XDG base directory")]
+    BaseDirectory(#[from] xdg::BaseDirectoriesError),
+    /// The data directory could not be created.
+    #[error("failed to get data directory")]
+    Place(#[source] io::Error),
+    /// The results of the last run exist but could not be read.
+    #[error("failed to read previous security results")]
+    Read(#[source] io::Error),
+    /// The results of this run could not be written.
+    #[error("failed to write security results")]
+    Write(#[source] io::Error),
+}
+
+/// The result of a security check, mirroring `FwupdSecurityAttrResult`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SecurityResult {
+    /// The result is not known.
+    Unknown,
+    /// The feature is enabled.
+    Enabled,
+    /// The feature is not enabled.
+    NotEnabled,
+    /// The value is valid.
+    Valid,
+    /// The value is not valid.
+    NotValid,
+    /// The feature is locked.
+    Locked,
+    /// The feature is not locked.
+    NotLocked,
+    /// The data is encrypted.
+    Encrypted,
+    /// The data is not encrypted.
+    NotEncrypted,
+    /// The system is tainted.
+    Tainted,
+    /// The system is not tainted.
+    NotTainted,
+    /// The item was found.
+    Found,
+    /// The item was not found.
+    NotFound,
+    /// The feature is supported.
+    Supported,
+    /// The feature is not supported.
+    NotSupported,
+}
+
+impl SecurityResult {
+    /// Converts the raw value given by fwupd.
+    pub fn from_fwupd(raw: u32) -> Self {
+        match raw {
+            1 => SecurityResult::Enabled,
+            2 => SecurityResult::NotEnabled,
+            3 => SecurityResult::Valid,
+            4 => SecurityResult::NotValid,
+            5 => SecurityResult::Locked,
+            6 => SecurityResult::NotLocked,
+            7 => SecurityResult::Encrypted,
+            8 => SecurityResult::NotEncrypted,
+            9 => SecurityResult::Tainted,
+            10 => SecurityResult::NotTainted,
+            11 => SecurityResult::Found,
+            12 => SecurityResult::NotFound,
+            13 => SecurityResult::Supported,
+            14 => SecurityResult::NotSupported,
+            _ => SecurityResult::Unknown,
+        }
+    }
+}
+
+/// What the user may do to fix a failed security check.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Remediation {
+    /// Only the hardware vendor can fix the issue, such as with a firmware update.
+    ContactOem,
+    /// The issue may be fixed by changing a setting in the system firmware setup.
+    ConfigureFirmware,
+    /// The issue may be fixed by changing the configuration of the operating system.
+    ConfigureOs,
+}
+
+/// A security check that fwupd performed on the host.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SecurityAttribute {
+    /// The AppStream ID of the check, such as `org.fwupd.hsi.Uefi.SecureBoot`.
+    pub id: Box<str>,
+    /// The name of the check.
+    pub name: Box<str>,
+    /// The HSI level that the check contributes to, or 0 for runtime checks.
+    pub level: u32,
+    /// Whether the check passed.
+    pub passed: bool,
+    /// The result of the check.
+    pub result: SecurityResult,
+    /// What the user may do to fix the issue, if the check failed.
+    pub remediation: Option<Remediation>,
+    /// A description of the check, if fwupd provided one.
+    pub description: Option<Box<str>>,
+    /// A link to further information about the check.
+    pub url: Option<Box<str>>,
+}
+
+impl SecurityAttribute {
+    /// Reads an attribute from the dictionary returned by fwupd's `GetHostSecurityAttrs`.
+    ///
+    /// Returns `None` for attributes without an ID, and for attributes which fwupd marked as
+    /// obsoleted by another attribute.
+    pub fn from_dbus(map: &PropMap) -> Option<Self> {
+        let string = |key: &str| {
+            prop_cast::<String>(map, key)
+                .filter(|value| !value.is_empty())
+                .map(|value| Box::<str>::from(value.as_str()))
+        };
+
+        let flags = prop_cast::<u64>(map, "Flags").copied().unwrap_or(0);
+        if flags & FLAG_OBSOLETED != 0 {
+            return None;
+        }
+
+        let id = string("AppstreamId")?;
+        let passed = flags & FLAG_SUCCESS != 0;
+
+        let remediation = if passed {
+            None
+        } else if flags & FLAG_ACTION_CONTACT_OEM != 0 {
+            Some(Remediation::ContactOem)
+        } else if flags & FLAG_ACTION_CONFIG_FW != 0 {
+            Some(Remediation::ConfigureFirmware)
+        } else if flags & FLAG_ACTION_CONFIG_OS != 0 {
+            Some(Remediation::ConfigureOs)
+        } else {
+            None
+        };
+
+        Some(Self {
+            name: string("Title").or_else(|| string("Name")).unwrap_or_else(|| id.clone()),
+            id,
+            level: prop_cast::<u32>(map, "HsiLevel").copied().unwrap_or(0),
+            passed,
+            result: SecurityResult::from_fwupd(
+                prop_cast::<u32>(map, "HsiResult").copied().unwrap_or(0),
+            ),
+            remediation,
+            description: string("Description"),
+            url: string("Uri"),
+        })
+    }
+}
+
+/// The security of the host, as assessed by fwupd.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HostSecurity {
+    /// The HSI level of the platform, from 0 to 5, if fwupd could determine it.
+    pub hsi: Option<u8>,
+    /// Whether a runtime check failed, which fwupd marks with a `!` after the level.
+    pub runtime_issues: bool,
+    /// Every check which fwupd performed, ordered by level.
+    pub attributes: Vec<SecurityAttribute>,
+}
+
+impl HostSecurity {
+    /// The results of the checks, to be compared with those of a later run.
+    pub fn snapshot(&self) -> SecuritySnapshot {
+        SecuritySnapshot {
+            hsi: self.hsi,
+            results: self
+                .attributes
+                .iter()
+                .map(|attribute| (attribute.id.clone(), attribute.passed))
+                .collect(),
+        }
+    }
+
+    /// The checks which were added, or whose results changed, since a previous run.
+    pub fn changes(&self, previous: &SecuritySnapshot) -> Vec<SecurityChange> {
+        self.attributes
+            .iter()
+            .filter_map(|attribute| {
+                let before = previous.passed(&attribute.id);
+                if before == Some(attribute.passed) {
+                    return None;
+                }
+
+                Some(SecurityChange {
+                    id: attribute.id.clone(),
+                    name: attribute.name.clone(),
+                    previous: before,
+                    passed: attribute.passed,
+                })
+            })
+            .collect()
+    }
+}
+
+/// A check whose result differs from the last run.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SecurityChange {
+    /// The AppStream ID of the check.
+    pub id: Box<str>,
+    /// The name of the check.
+    pub name: Box<str>,
+    /// Whether the check passed on the last run, or `None` if it was not performed.
+    pub previous: Option<bool>,
+    /// Whether the check passed on this run.
+    pub passed: bool,
+}
+
+/// The results of the security checks of a run, as they are stored between runs.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SecuritySnapshot {
+    /// The HSI level of the platform.
+    pub hsi: Option<u8>,
+    /// The ID of each check, and whether it passed.
+    pub results: Vec<(Box<str>, bool)>,
+}
+
+impl SecuritySnapshot {
+    /// Whether a check passed, if it was performed.
+    pub fn passed(&self, id: &str) -> Option<bool> {
+        self.results.iter().find(|(result, _)| &**result == id).map(|&(_, passed)| passed)
+    }
+
+    /// Reads the results of the last run, if there was one.
+    pub fn load() -> Result<Option<Self>, Error> {
+        match fs::read_to_string(snapshot_path()?) {
+            Ok(data) => Ok(Some(Self::parse(&data))),
+            Err(why) if why.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(why) => Err(Error::Read(why)),
+        }
+    }
+
+    /// Stores these results, to be compared with those of the next run.
+    pub fn store(&self) -> Result<(), Error> {
+        fs::write(snapshot_path()?, self.serialize()).map_err(Error::Write)
+    }
+
+    fn parse(data: &str) -> Self {
+        let mut snapshot = Self::default();
+
+        for line in data.lines() {
+            match line.split_once('\t') {
+                Some(("hsi", level)) => snapshot.hsi = level.parse().ok(),
+                Some((id, "pass")) => snapshot.results.push((id.into(), true)),
+                Some((id, "fail")) => snapshot.results.push((id.into(), false)),
+                _ => (),
+            }
+        }
+
+        snapshot
+    }
+
+    fn serialize(&self) -> String {
+        let mut data = String::new();
+
+        if let Some(hsi) = self.hsi {
+            data.push_str(&format!("hsi\t{}\n", hsi));
+        }
+
+        for (id, passed) in &self.results {
+            data.push_str(&[&**id, "\t", if *passed { "pass" } else { "fail" }, "\n"].concat());
+        }
+
+        data
+    }
+}
+
+/// Fetches the security attributes of the host, and its HSI level, from fwupd.
+pub fn host_security() -> Result<HostSecurity, Error> {
+    let connection = Connection::new_system().map_err(Error::Connect)?;
+    let proxy = connection.with_proxy(FWUPD_DEST, "/", Duration::from_secs(30));
+
+    let (attributes,): (Vec<PropMap>,) =
+        proxy.method_call(FWUPD_IFACE, "GetHostSecurityAttrs", ()).map_err(Error::Attributes)?;
+
+    let id: String = proxy.get(FWUPD_IFACE, "HostSecurityId").map_err(Error::HostSecurityId)?;
+    let (hsi, runtime_issues) = parse_hsi(&id);
+
+    let mut attributes =
+        attributes.iter().filter_map(SecurityAttribute::from_dbus).collect::<Vec<_>>();
+    attributes.sort_by_key(|attribute| attribute.level);
+
+    Ok(HostSecurity { hsi, runtime_issues, attributes })
+}
+
+/// Parses a Host Security ID such as `HSI:2! (v1.9.5)`.
+fn parse_hsi(id: &str) -> (Option<u8>, bool) {
+    let level = match id.strip_prefix("HSI:") {
+        Some(level) => level,
+        None => return (None, false),
+    };
+
+    let digits = level.bytes().take_while(u8::is_ascii_digit).count();
+    let runtime_issues = level[digits..].starts_with('!');
+    (level[..digits].parse().ok(), runtime_issues)
+}
+
+/// Convenience function for fetching the path to the stored results.
+fn snapshot_path() -> Result<PathBuf, Error> {
+    xdg::BaseDirectories::with_prefix("com.system76.FirmwareManager")
+        .map_err(Error::BaseDirectory)?
+        .place_data_file("security")
+        .map_err(Error::Place)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dbus::arg::{RefArg, Variant};
+
+    fn attribute(id: &str, passed: bool) -> SecurityAttribute {
+        SecurityAttribute {
+            id: id.into(),
+            name: id.into(),
+            level: 1,
+            passed,
+            result: SecurityResult::Unknown,
+            remediation: None,
+            description: None,
+            url: None,
+        }
+    }
+
+    #[test]
+    fn hsi() {
+        assert_eq!(parse_hsi("HSI:2 (v1.9.5)"), (Some(2), false));
+        assert_eq!(parse_hsi("HSI:0! (v1.9.5)"), (Some(0), true));
+        assert_eq!(parse_hsi("HSI-INVALID:chassis"), (None, false));
+    }
+
+    #[test]
+    fn from_dbus() {
+        let mut map = PropMap::new();
+        let mut insert = |key: &str, value: Box<dyn RefArg>| {
+            map.insert(key.into(), Variant(value));
+        };
+
+        insert("AppstreamId", Box::new(String::from("org.fwupd.hsi.Uefi.SecureBoot")));
+        insert("Name", Box::new(String::from("UEFI secure boot")));
+        insert("HsiLevel", Box::new(1u32));
+        insert("HsiResult", Box::new(2u32));
+        insert("Flags", Box::new(FLAG_ACTION_CONFIG_FW));
+
+        let attribute = SecurityAttribute::from_dbus(&map).unwrap();
+        assert_eq!(&*attribute.name, "UEFI secure boot");
+        assert_eq!(attribute.level, 1);
+        assert!(!attribute.passed);
+        assert_eq!(attribute.result, SecurityResult::NotEnabled);
+        assert_eq!(attribute.remediation, Some(Remediation::ConfigureFirmware));
+        assert_eq!(attribute.url, None);
+
+        map.insert("Flags".into(), Variant(Box::new(FLAG_SUCCESS | FLAG_OBSOLETED)));
+        assert!(SecurityAttribute::from_dbus(&map).is_none());
+    }
+
+    #[test]
+    fn changes() {
+        let previous = SecuritySnapshot::parse("hsi\t1\na\tpass\nb\tfail\n");
+        assert_eq!(previous.hsi, Some(1));
+
+        let security = HostSecurity {
+            hsi: Some(1),
+            runtime_issues: false,
+            attributes: vec![attribute("a", false), attribute("b", false), attribute("c", true)],
+        };
+
+        let changes = security.changes(&previous);
+        assert_eq!(changes.len(), 2);
+        assert_eq!(
+            (&*changes[0].id, changes[0].previous, changes[0].passed),
+            ("a", Some(true), false)
+        );
+        assert_eq!((&*changes[1].id, changes[1].previous, changes[1].passed), ("c", None, true));
+
+        let snapshot = security.snapshot();
+        assert_eq!(SecuritySnapshot::parse(&snapshot.serialize()), snapshot);
+    }
+}
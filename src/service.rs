@@ -0,0 +1,665 @@
+//! The D-Bus interface of the firmware manager service, and a client for its frontends.
+//!
+//! The service hosts the event loop of the core on the session or system bus, so that every
+//! frontend sees the same devices and the same operations in flight. Summaries of devices and
+//! operations are sent over the bus as plain tuples of strings and integers, which this module
+//! converts to and from.
+//!
+//! Frontends which need everything that the core reports, such as changelogs, device requests,
+//! and the results of verification, exchange the records of the schema instead. They dispatch
+//! their events to the service, which also carries unlocking and activating devices, and receive
+//! every signal of its event loop through [`service_event_loop`].
+
+use crate::{
+    recording::replayed_signal, Entity, EntityId, Error, EventData, FirmwareEvent, FirmwareSignal,
+    HistoryEntry, MetadataSource, Record, RequestKind, Severity, SignalData,
+};
+use dbus::{
+    blocking::Connection,
+    message::{MatchRule, MessageType},
+    Message,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::mpsc::{self, Receiver, Sender, TryRecvError},
+    time::Duration,
+};
+
+/// The well-known name that the service owns on the bus.
+pub const SERVICE_NAME: &str = "com.system76.FirmwareManager";
+
+/// The object which implements the interface of the service.
+pub const SERVICE_PATH: &str = "/com/system76/FirmwareManager";
+
+/// The interface of the service.
+pub const SERVICE_INTERFACE: &str = "com.system76.FirmwareManager";
+
+/// The error returned when an operation is requested while another is in progress.
+pub const ERROR_BUSY: &str = "com.system76.FirmwareManager.Error.Busy";
+
+/// The error returned when a device does not exist, or has no update which may be installed.
+pub const ERROR_NOT_FOUND: &str = "com.system76.FirmwareManager.Error.NotFound";
+
+/// Method calls may wait for the service to be started.
+const METHOD_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long [`service_event_loop`] waits for signals before handling the events of the frontend.
+const PROCESS_TIMEOUT: Duration = Duration::from_millis(100);
+
+const DBUS_NAME: &str = "org.freedesktop.DBus";
+const DBUS_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A device as it is sent over the bus.
+pub type DeviceArg = (String, String, String, String, String, bool, bool, bool, String);
+
+/// An operation as it is sent over the bus.
+pub type OperationArg = (String, String, u64, u64);
+
+/// An entry of the history as it is sent over the bus.
+pub type HistoryArg = (u64, String, String, String, String, String, String);
+
+/// The entity of a client, paired with the entity of the service that it refers to.
+pub type EntityArg = (u64, u64);
+
+/// The bus that the service is hosted on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Bus {
+    /// The bus of the user's session, where the service runs as that user.
+    Session,
+    /// The system bus, where the service runs as root and authorizes each caller.
+    System,
+}
+
+impl Bus {
+    /// Connects to the bus.
+    pub fn connect(self) -> Result<Connection, dbus::Error> {
+        match self {
+            Bus::Session => Connection::new_session(),
+            Bus::System => Connection::new_system(),
+        }
+    }
+}
+
+/// A device which the service has discovered.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ServiceDevice {
+    /// The identifier that the device is selected by.
+    pub id: Box<str>,
+    /// The name of the device.
+    pub name: Box<str>,
+    /// The service which manages the firmware of the device.
+    pub source: MetadataSource,
+    /// The currently-installed version, if the device reports it.
+    pub current: Option<Box<str>>,
+    /// The latest version available, if one is known.
+    pub latest: Option<Box<str>>,
+    /// Whether the latest version is newer than the current version.
+    pub upgradeable: bool,
+    /// Whether the update may be installed now.
+    pub can_update: bool,
+    /// Whether the update will only be applied once the system is rebooted.
+    pub needs_reboot: bool,
+    /// The severity of the update, if one is available.
+    pub severity: Option<Severity>,
+}
+
+impl ServiceDevice {
+    /// Converts the device to the arguments that are sent over the bus.
+    pub fn to_arg(&self) -> DeviceArg {
+        (
+            self.id.to_string(),
+            self.name.to_string(),
+            source_str(self.source).to_owned(),
+            self.current.as_deref().unwrap_or("").to_owned(),
+            self.latest.as_deref().unwrap_or("").to_owned(),
+            self.upgradeable,
+            self.can_update,
+            self.needs_reboot,
+            self.severity.map_or("", severity_str).to_owned(),
+        )
+    }
+
+    /// Converts the arguments received from the bus, if they describe a device.
+    pub fn from_arg(arg: DeviceArg) -> Option<Self> {
+        let (id, name, source, current, latest, upgradeable, can_update, needs_reboot, severity) =
+            arg;
+
+        Some(Self {
+            id: id.into(),
+            name: name.into(),
+            source: parse_source(&source)?,
+            current: Some(current).filter(|current| !current.is_empty()).map(Box::from),
+            latest: Some(latest).filter(|latest| !latest.is_empty()).map(Box::from),
+            upgradeable,
+            can_update,
+            needs_reboot,
+            severity: parse_severity(&severity),
+        })
+    }
+}
+
+/// How far an operation on a device has progressed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    /// The operation was requested, and is waiting for authorization or for other operations.
+    Waiting,
+    /// The firmware is being downloaded.
+    Downloading,
+    /// The downloaded firmware is being verified.
+    Verifying,
+    /// The firmware is being flashed.
+    Flashing,
+}
+
+impl Stage {
+    /// The name of the stage as it is sent over the bus.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Stage::Waiting => "waiting",
+            Stage::Downloading => "downloading",
+            Stage::Verifying => "verifying",
+            Stage::Flashing => "flashing",
+        }
+    }
+
+    fn parse(input: &str) -> Option<Self> {
+        match input {
+            "waiting" => Some(Stage::Waiting),
+            "downloading" => Some(Stage::Downloading),
+            "verifying" => Some(Stage::Verifying),
+            "flashing" => Some(Stage::Flashing),
+            _ => None,
+        }
+    }
+}
+
+/// An operation that the service is performing on a device.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Operation {
+    /// The identifier of the device.
+    pub device: Box<str>,
+    /// How far the operation has progressed.
+    pub stage: Stage,
+    /// The number of bytes which have been downloaded.
+    pub downloaded: u64,
+    /// The size of the download, or `0` if it is not known.
+    pub total: u64,
+}
+
+impl Operation {
+    /// Converts the operation to the arguments that are sent over the bus.
+    pub fn to_arg(&self) -> OperationArg {
+        (self.device.to_string(), self.stage.as_str().to_owned(), self.downloaded, self.total)
+    }
+
+    /// Converts the arguments received from the bus, if they describe an operation.
+    pub fn from_arg((device, stage, downloaded, total): OperationArg) -> Option<Self> {
+        Some(Self { device: device.into(), stage: Stage::parse(&stage)?, downloaded, total })
+    }
+}
+
+impl HistoryEntry {
+    /// Converts the entry to the arguments that are sent over the bus.
+    pub fn to_arg(&self) -> HistoryArg {
+        let checksum = self.checksum.as_ref().map_or_else(String::new, ToString::to_string);
+
+        (
+            self.timestamp,
+            self.device_id.to_string(),
+            self.device.to_string(),
+            self.previous.to_string(),
+            self.version.to_string(),
+            checksum,
+            self.outcome.to_string(),
+        )
+    }
+
+    /// Converts the arguments received from the bus, if they describe an entry.
+    pub fn from_arg(arg: HistoryArg) -> Option<Self> {
+        let (timestamp, device_id, device, previous, version, checksum, outcome) = arg;
+
+        Some(Self {
+            timestamp,
+            device_id: device_id.into(),
+            device: device.into(),
+            previous: previous.into(),
+            version: version.into(),
+            checksum: checksum.parse().ok(),
+            outcome: outcome.parse().ok()?,
+        })
+    }
+}
+
+/// Signals that the service emits to every client.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ServiceSignal {
+    /// Devices are being scanned.
+    Scanning,
+    /// Scanning has completed, and the devices may be fetched.
+    ScanningComplete,
+    /// An operation has progressed.
+    Progress(Operation),
+    /// The firmware of a device was updated, or scheduled to be updated on the next reboot.
+    DeviceUpdated(Box<str>),
+    /// System firmware was scheduled for installation.
+    SystemScheduled,
+    /// An error occurred, which concerns a device if one is given.
+    Error(Option<Box<str>>, String),
+    /// A device has a request for user interaction.
+    Request(ServiceRequest),
+    /// A signal of the event loop of the core, which concerns the entities of the service.
+    Core(Box<SignalData>),
+}
+
+/// A request for the user to interact with a device, as the service describes it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ServiceRequest {
+    /// The identifier of the device.
+    pub device: Box<str>,
+    /// When the request is to be acted upon.
+    pub kind: RequestKind,
+    /// The ID of the request, which identifies well-known requests.
+    pub id: Box<str>,
+    /// The instructions which fwupd gave, in English.
+    pub message: String,
+    /// The local path of an image which illustrates the request, if one was given.
+    pub image: Option<PathBuf>,
+}
+
+impl ServiceSignal {
+    /// The name of the signal on the bus.
+    pub fn member(&self) -> &'static str {
+        match self {
+            ServiceSignal::Scanning => "Scanning",
+            ServiceSignal::ScanningComplete => "ScanningComplete",
+            ServiceSignal::Progress(_) => "Progress",
+            ServiceSignal::DeviceUpdated(_) => "DeviceUpdated",
+            ServiceSignal::SystemScheduled => "SystemScheduled",
+            ServiceSignal::Error(..) => "Error",
+            ServiceSignal::Request(_) => "Request",
+            ServiceSignal::Core(_) => "CoreSignal",
+        }
+    }
+
+    /// Creates the message which emits the signal from the service.
+    pub fn to_message(&self) -> Message {
+        let message = Message::new_signal(SERVICE_PATH, SERVICE_INTERFACE, self.member())
+            .expect("signal names are valid");
+
+        match self {
+            ServiceSignal::Progress(operation) => {
+                let (device, stage, downloaded, total) = operation.to_arg();
+                message.append3(device, stage, downloaded).append1(total)
+            }
+            ServiceSignal::DeviceUpdated(device) => message.append1(device.to_string()),
+            ServiceSignal::Error(device, why) => {
+                message.append2(device.as_deref().unwrap_or(""), why.as_str())
+            }
+            ServiceSignal::Request(request) => {
+                let image = request
+                    .image
+                    .as_ref()
+                    .map_or_else(String::new, |image| image.to_string_lossy().into_owned());
+
+                message
+                    .append3(&*request.device, request_kind_str(request.kind), &*request.id)
+                    .append2(request.message.as_str(), image)
+            }
+            ServiceSignal::Core(signal) => message.append1(encode_record(&**signal)),
+            _ => message,
+        }
+    }
+
+    /// Reads the signal from a message of the service, if it is one.
+    pub fn from_message(message: &Message) -> Option<Self> {
+        let signal = match &*message.member()? {
+            "Scanning" => ServiceSignal::Scanning,
+            "ScanningComplete" => ServiceSignal::ScanningComplete,
+            "Progress" => {
+                let arg = message.read_all::<OperationArg>().ok()?;
+                ServiceSignal::Progress(Operation::from_arg(arg)?)
+            }
+            "DeviceUpdated" => ServiceSignal::DeviceUpdated(message.read1::<String>().ok()?.into()),
+            "SystemScheduled" => ServiceSignal::SystemScheduled,
+            "Error" => {
+                let (device, why) = message.read2::<String, String>().ok()?;
+                ServiceSignal::Error(Some(device).filter(|d| !d.is_empty()).map(Box::from), why)
+            }
+            "Request" => {
+                let (device, kind, id, message, image) =
+                    message.read_all::<(String, String, String, String, String)>().ok()?;
+
+                ServiceSignal::Request(ServiceRequest {
+                    device: device.into(),
+                    kind: parse_request_kind(&kind)?,
+                    id: id.into(),
+                    message,
+                    image: Some(image).filter(|image| !image.is_empty()).map(PathBuf::from),
+                })
+            }
+            "CoreSignal" => {
+                ServiceSignal::Core(Box::new(decode_record(&message.read1::<String>().ok()?)?))
+            }
+            _ => return None,
+        };
+
+        Some(signal)
+    }
+}
+
+/// A connection to the service, which frontends use in place of an event loop of their own.
+pub struct ServiceClient {
+    connection: Connection,
+}
+
+impl ServiceClient {
+    /// Connects to the service, if it is running on the bus or may be started by it.
+    pub fn connect(bus: Bus) -> Option<Self> {
+        let connection =
+            bus.connect().map_err(|why| debug!("no D-Bus connection: {}", why)).ok()?;
+
+        let available = {
+            let proxy = connection.with_proxy(DBUS_NAME, "/org/freedesktop/DBus", DBUS_TIMEOUT);
+
+            let running = proxy
+                .method_call(DBUS_NAME, "NameHasOwner", (SERVICE_NAME,))
+                .map_or(false, |(running,): (bool,)| running);
+
+            running
+                || proxy
+                    .method_call(DBUS_NAME, "ListActivatableNames", ())
+                    .map_or(false, |(names,): (Vec<String>,)| {
+                        names.iter().any(|name| name == SERVICE_NAME)
+                    })
+        };
+
+        if available {
+            Some(Self { connection })
+        } else {
+            None
+        }
+    }
+
+    /// Asks the service to scan for devices.
+    ///
+    /// The scan happens in the background, and completes with [`ServiceSignal::ScanningComplete`].
+    pub fn scan(&self) -> Result<(), dbus::Error> { self.call("Scan", ()) }
+
+    /// The devices which the last scan discovered.
+    pub fn devices(&self) -> Result<Vec<ServiceDevice>, dbus::Error> {
+        let (devices,): (Vec<DeviceArg>,) = self.call("Devices", ())?;
+        Ok(devices.into_iter().filter_map(ServiceDevice::from_arg).collect())
+    }
+
+    /// Asks the service to update the devices with the given identifiers.
+    ///
+    /// The caller is asked to authenticate if that is required, and the updates happen once they
+    /// have. Progress, and a failure to authenticate, are reported with signals.
+    pub fn update(&self, devices: &[&str]) -> Result<(), dbus::Error> {
+        self.call("Update", (devices.to_vec(),))
+    }
+
+    /// The operations which are in progress.
+    pub fn operations(&self) -> Result<Vec<Operation>, dbus::Error> {
+        let (operations,): (Vec<OperationArg>,) = self.call("Operations", ())?;
+        Ok(operations.into_iter().filter_map(Operation::from_arg).collect())
+    }
+
+    /// Asks the service to handle an event of the core on behalf of the client.
+    ///
+    /// Devices are identified by their IDs, and the entities of the client are returned paired
+    /// with those of the service, which its signals refer to. Events which act upon devices wait
+    /// until the caller is authorized, and a failure to authenticate is reported with a signal.
+    pub fn dispatch(&self, event: &EventData) -> Result<Vec<(EntityId, EntityId)>, dbus::Error> {
+        let (entities,): (Vec<EntityArg>,) = self.call("Dispatch", (encode_record(event),))?;
+        Ok(entities.into_iter().map(|(ours, theirs)| (EntityId(ours), EntityId(theirs))).collect())
+    }
+
+    /// The history of the system which the service is running on, from oldest to newest.
+    pub fn history(&self) -> Result<Vec<HistoryEntry>, dbus::Error> {
+        let (history,): (Vec<HistoryArg>,) = self.call("History", ())?;
+        Ok(history.into_iter().filter_map(HistoryEntry::from_arg).collect())
+    }
+
+    /// Sends every signal of the service, which is received while processing, to `sender`.
+    pub fn subscribe(&self, sender: Sender<ServiceSignal>) -> Result<(), dbus::Error> {
+        let rule =
+            MatchRule::new().with_type(MessageType::Signal).with_interface(SERVICE_INTERFACE);
+
+        self.connection.add_match(rule, move |(): (), _, message| {
+            if let Some(signal) = ServiceSignal::from_message(message) {
+                let _res = sender.send(signal);
+            }
+
+            true
+        })?;
+
+        Ok(())
+    }
+
+    /// Receives messages from the bus for up to `timeout`, dispatching any signals received.
+    pub fn process(&self, timeout: Duration) -> Result<bool, dbus::Error> {
+        self.connection.process(timeout)
+    }
+
+    fn call<A: dbus::arg::AppendAll, R: dbus::arg::ReadAll>(
+        &self,
+        method: &str,
+        args: A,
+    ) -> Result<R, dbus::Error> {
+        let proxy = self.connection.with_proxy(SERVICE_NAME, SERVICE_PATH, METHOD_TIMEOUT);
+        proxy.method_call(SERVICE_INTERFACE, method, args)
+    }
+}
+
+/// An event loop which hands the events of a frontend to the service, in place of the core's.
+///
+/// The signals of the service's event loop are recreated for the frontend, with the entities of
+/// the service replaced by those of the frontend that its events referred to. Signals about
+/// entities which the frontend did not act upon, such as the progress of another client's update,
+/// are dropped, as the frontend would not recognize them.
+pub fn service_event_loop(
+    client: ServiceClient,
+    receiver: Receiver<FirmwareEvent>,
+    sender: Sender<FirmwareSignal>,
+) {
+    let (signal_tx, signal_rx) = mpsc::channel();
+    if let Err(why) = client.subscribe(signal_tx) {
+        let _res = sender.send(FirmwareSignal::Error(None, Error::Service(why.to_string())));
+        return;
+    }
+
+    let mut entities = HashMap::new();
+
+    loop {
+        loop {
+            let event = match receiver.try_recv() {
+                Ok(FirmwareEvent::Stop) | Err(TryRecvError::Disconnected) => return,
+                Ok(event) => event,
+                Err(TryRecvError::Empty) => break,
+            };
+
+            let event = EventData::from(&event);
+            match client.dispatch(&event) {
+                Ok(associated) => {
+                    for (ours, theirs) in associated {
+                        entities.insert(theirs, Entity::from(ours));
+                    }
+                }
+                Err(why) => {
+                    let why = why.to_string();
+                    let targets = event.targets();
+                    if targets.is_empty() {
+                        let _res = sender.send(FirmwareSignal::Error(None, Error::Service(why)));
+                        continue;
+                    }
+
+                    for (entity, _) in targets {
+                        let why = Error::Service(why.clone());
+                        let _res = sender.send(FirmwareSignal::Error(Some(entity.into()), why));
+                    }
+                }
+            }
+        }
+
+        if let Err(why) = client.process(PROCESS_TIMEOUT) {
+            let _res = sender.send(FirmwareSignal::Error(None, Error::Service(why.to_string())));
+            return;
+        }
+
+        for signal in signal_rx.try_iter() {
+            let ServiceSignal::Core(signal) = signal else {
+                continue;
+            };
+
+            // The service forgets its entities when it scans again.
+            if let SignalData::Scanning = *signal {
+                entities.clear();
+            }
+
+            if signal.entity().is_some_and(|entity| !entities.contains_key(&entity)) {
+                continue;
+            }
+
+            let signal = replayed_signal(*signal, |entity| entities[&entity]);
+            if sender.send(signal).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// Encodes data as a record of the schema, as it is sent over the bus.
+pub fn encode_record<T: Serialize>(data: T) -> String {
+    serde_json::to_string(&Record::new(data)).expect("records of the schema are serializable")
+}
+
+/// Decodes a record of the schema which was received from the bus, if this build can read it.
+pub fn decode_record<T: DeserializeOwned>(input: &str) -> Option<T> {
+    serde_json::from_str::<Record<T>>(input)
+        .ok()
+        .filter(Record::is_supported)
+        .map(|record| record.data)
+}
+
+fn source_str(source: MetadataSource) -> &'static str {
+    match source {
+        MetadataSource::Fwupd => "fwupd",
+        MetadataSource::System76 => "system76",
+        MetadataSource::ThelioIo => "thelio-io",
+    }
+}
+
+fn parse_source(input: &str) -> Option<MetadataSource> {
+    match input {
+        "fwupd" => Some(MetadataSource::Fwupd),
+        "system76" => Some(MetadataSource::System76),
+        "thelio-io" => Some(MetadataSource::ThelioIo),
+        _ => None,
+    }
+}
+
+fn severity_str(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Routine => "routine",
+        Severity::Important => "important",
+        Severity::Security => "security",
+        Severity::Critical => "critical",
+    }
+}
+
+fn parse_severity(input: &str) -> Option<Severity> {
+    match input {
+        "routine" => Some(Severity::Routine),
+        "important" => Some(Severity::Important),
+        "security" => Some(Severity::Security),
+        "critical" => Some(Severity::Critical),
+        _ => None,
+    }
+}
+
+fn request_kind_str(kind: RequestKind) -> &'static str {
+    match kind {
+        RequestKind::Immediate => "immediate",
+        RequestKind::Post => "post",
+    }
+}
+
+fn parse_request_kind(input: &str) -> Option<RequestKind> {
+    match input {
+        "immediate" => Some(RequestKind::Immediate),
+        "post" => Some(RequestKind::Post),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Outcome;
+
+    #[test]
+    fn device_round_trip() {
+        let device = ServiceDevice {
+            id: "0123456789abcdef".into(),
+            name: "Thunderbolt Controller".into(),
+            source: MetadataSource::Fwupd,
+            current: Some("1.0".into()),
+            latest: Some("1.1".into()),
+            upgradeable: true,
+            can_update: true,
+            needs_reboot: false,
+            severity: Some(Severity::Security),
+        };
+
+        assert_eq!(ServiceDevice::from_arg(device.to_arg()), Some(device.clone()));
+
+        let device = ServiceDevice { latest: None, upgradeable: false, severity: None, ..device };
+        assert_eq!(ServiceDevice::from_arg(device.to_arg()), Some(device.clone()));
+
+        let mut arg = device.to_arg();
+        arg.2 = "unknown".into();
+        assert_eq!(ServiceDevice::from_arg(arg), None);
+    }
+
+    #[test]
+    fn operation_round_trip() {
+        for stage in [Stage::Waiting, Stage::Downloading, Stage::Verifying, Stage::Flashing] {
+            let operation =
+                Operation { device: "system76".into(), stage, downloaded: 10, total: 20 };
+            assert_eq!(Operation::from_arg(operation.to_arg()), Some(operation));
+        }
+    }
+
+    #[test]
+    fn history_round_trip() {
+        let entry = HistoryEntry {
+            timestamp: 1_580_688_000,
+            device_id: "0123456789abcdef".into(),
+            device: "Thunderbolt Controller".into(),
+            previous: "1.0".into(),
+            version: "1.1".into(),
+            checksum: "a9993e364706816aba3e25717850c26c9cd0d89d".parse().ok(),
+            outcome: Outcome::Installed,
+        };
+
+        assert_eq!(HistoryEntry::from_arg(entry.to_arg()), Some(entry.clone()));
+
+        let entry = HistoryEntry { checksum: None, ..entry };
+        assert_eq!(HistoryEntry::from_arg(entry.to_arg()), Some(entry));
+    }
+
+    #[test]
+    fn record_round_trip() {
+        let signal = SignalData::DownloadUpdate { entity: EntityId(7), downloaded: 4096 };
+        assert_eq!(decode_record(&encode_record(&signal)), Some(signal));
+
+        let mut record = Record::new(SignalData::ScanningComplete);
+        record.version += 1;
+        let record = serde_json::to_string(&record).unwrap();
+        assert_eq!(decode_record::<SignalData>(&record), None);
+    }
+}
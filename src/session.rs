@@ -0,0 +1,214 @@
+//! Notices when the system resumes from suspend, or the session is unlocked.
+//!
+//! Devices may be connected while the system is suspended, or while the session is locked, where
+//! the frontend is not looking for them. These events tell the frontend to scan again, which also
+//! refreshes the metadata if it has grown stale in the meantime.
+
+use dbus::{
+    arg::{prop_cast, PropMap},
+    blocking::Connection,
+    message::MatchRule,
+};
+use std::{
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
+
+const LOGIN_NAME: &str = "org.freedesktop.login1";
+const LOGIN_PATH: &str = "/org/freedesktop/login1";
+const MANAGER_INTERFACE: &str = "org.freedesktop.login1.Manager";
+const SESSION_INTERFACE: &str = "org.freedesktop.login1.Session";
+const PROPERTIES_INTERFACE: &str = "org.freedesktop.DBus.Properties";
+
+const METHOD_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long to wait for messages when no events are pending.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How long to wait for further events before reporting them.
+///
+/// Resuming is often followed by unlocking, and devices are still being enumerated shortly after
+/// the system resumes, so events within this period are reported as one.
+pub const SESSION_SETTLE: Duration = Duration::from_secs(3);
+
+/// Something which happened to the system or the session, after which devices may have changed.
+///
+/// Events are ordered by how much may have changed, so that coalesced events report the greatest.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SessionEvent {
+    /// The session of this process was unlocked.
+    Unlocked,
+    /// The system resumed from suspend or hibernation.
+    Resumed,
+}
+
+/// Events which have not yet settled.
+#[derive(Debug, Default)]
+struct Pending {
+    event: Option<SessionEvent>,
+    last:  Option<Instant>,
+}
+
+impl Pending {
+    fn push(&mut self, event: SessionEvent, now: Instant) {
+        self.event = self.event.max(Some(event));
+        self.last = Some(now);
+    }
+
+    /// The coalesced event, once no event has been received for `settle`.
+    fn settled(&mut self, now: Instant, settle: Duration) -> Option<SessionEvent> {
+        let last = self.last?;
+        if now.duration_since(last) < settle {
+            return None;
+        }
+
+        self.last = None;
+        self.event.take()
+    }
+}
+
+/// An event loop which reacts to the system resuming, or the session of this process being
+/// unlocked.
+///
+/// Events are coalesced over `SESSION_SETTLE`, so that `func` is called once after resuming
+/// and unlocking. The loop runs in a background thread for the life of the process.
+///
+/// Returns an error if logind cannot be reached, in which case the consumer should go without.
+pub fn session_event_loop<F: Fn(SessionEvent) + Send + 'static>(
+    func: F,
+) -> Result<(), dbus::Error> {
+    trace!("initiating session event loop thread");
+
+    let (ready_tx, ready_rx) = mpsc::sync_channel(1);
+
+    thread::spawn(move || {
+        let (events_tx, events_rx) = mpsc::channel();
+
+        let connection = match connect(events_tx) {
+            Ok(connection) => {
+                let _ = ready_tx.send(Ok(()));
+                connection
+            }
+            Err(why) => {
+                let _ = ready_tx.send(Err(why));
+                return;
+            }
+        };
+
+        let mut pending = Pending::default();
+
+        loop {
+            // Wakes to report pending events once they settle, and otherwise waits for messages.
+            let timeout = if pending.last.is_some() { SESSION_SETTLE } else { IDLE_TIMEOUT };
+
+            if let Err(why) = connection.process(timeout) {
+                error!("session events are no longer received: {}", why);
+                break;
+            }
+
+            let now = Instant::now();
+            for event in events_rx.try_iter() {
+                pending.push(event, now);
+            }
+
+            if let Some(event) = pending.settled(now, SESSION_SETTLE) {
+                info!("devices may have changed, as the {:?} event was received", event);
+                func(event);
+            }
+        }
+
+        trace!("session thread stopped");
+    });
+
+    ready_rx.recv().map_err(|_| {
+        dbus::Error::new_custom(
+            "org.freedesktop.DBus.Error.Failed",
+            "session monitor thread stopped unexpectedly",
+        )
+    })?
+}
+
+/// Watches for resuming and unlocking, sending each event to `events` while processing.
+fn connect(events: mpsc::Sender<SessionEvent>) -> Result<Connection, dbus::Error> {
+    let connection = Connection::new_system()?;
+
+    let sender = events.clone();
+    let rule = MatchRule::new_signal(MANAGER_INTERFACE, "PrepareForSleep");
+    connection.add_match(rule, move |(sleeping,): (bool,), _, _| {
+        // The same signal is sent before suspending, and after resuming.
+        if !sleeping {
+            let _ = sender.send(SessionEvent::Resumed);
+        }
+
+        true
+    })?;
+
+    let path = match session_path(&connection) {
+        Ok(path) => path,
+        Err(why) => {
+            info!("session unlocking is not watched, as the session is unknown: {}", why);
+            return Ok(connection);
+        }
+    };
+
+    // Screen lockers which are asked to unlock by logind are sent this signal.
+    let sender = events.clone();
+    let rule = MatchRule::new_signal(SESSION_INTERFACE, "Unlock").with_path(path.clone());
+    connection.add_match(rule, move |(): (), _, _| {
+        let _ = sender.send(SessionEvent::Unlocked);
+        true
+    })?;
+
+    // Screen lockers which unlock on their own report it through the locked hint.
+    let rule = MatchRule::new_signal(PROPERTIES_INTERFACE, "PropertiesChanged").with_path(path);
+    connection.add_match(
+        rule,
+        move |(interface, changed, _): (String, PropMap, Vec<String>), _, _| {
+            if interface == SESSION_INTERFACE
+                && prop_cast::<bool>(&changed, "LockedHint") == Some(&false)
+            {
+                let _ = events.send(SessionEvent::Unlocked);
+            }
+
+            true
+        },
+    )?;
+
+    Ok(connection)
+}
+
+/// The object path of the logind session that this process belongs to.
+fn session_path(connection: &Connection) -> Result<dbus::Path<'static>, dbus::Error> {
+    let proxy = connection.with_proxy(LOGIN_NAME, LOGIN_PATH, METHOD_TIMEOUT);
+    let (path,): (dbus::Path<'static>,) =
+        proxy.method_call(MANAGER_INTERFACE, "GetSessionByPID", (std::process::id(),))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resume_and_unlock_are_coalesced() {
+        let settle = Duration::from_secs(3);
+        let start = Instant::now();
+        let mut pending = Pending::default();
+
+        pending.push(SessionEvent::Resumed, start);
+        pending.push(SessionEvent::Unlocked, start + Duration::from_secs(2));
+        assert_eq!(pending.settled(start + Duration::from_secs(4), settle), None);
+        assert_eq!(
+            pending.settled(start + Duration::from_secs(5), settle),
+            Some(SessionEvent::Resumed)
+        );
+        assert_eq!(pending.settled(start + Duration::from_secs(9), settle), None);
+
+        pending.push(SessionEvent::Unlocked, start + Duration::from_secs(10));
+        assert_eq!(
+            pending.settled(start + Duration::from_secs(13), settle),
+            Some(SessionEvent::Unlocked)
+        );
+    }
+}
@@ -1,90 +1,398 @@
 //! Functions specific to working with system76 firmware.
 
-use crate::{lowest_revision, FirmwareInfo, FirmwareSignal};
-use std::{error::Error as _, sync::mpsc::Sender};
+use crate::{
+    cache, Config, Entity, Error, FirmwareInfo, FirmwareSignal, MetadataAge, MetadataSource,
+    ReleaseInfo, SystemIdentity, Version, VersionFormat,
+};
+use std::{fs, path::Path, sync::mpsc::Sender};
 use system76_firmware_daemon::{
-    Client as System76Client, SystemInfo as S76SystemInfo, ThelioIoInfo,
+    Client as System76Client, Digest as System76Digest, SystemInfo as S76SystemInfo, ThelioIoInfo,
 };
 
+/// A signal sent when a Thelio I/O board has been discovered.
+#[derive(Debug)]
+pub struct ThelioIoSignal {
+    /// The identifier of the board, as reported by system76-firmware.
+    pub board: Box<str>,
+    /// Generic information about the board's firmware.
+    pub info: FirmwareInfo,
+    /// The digest of the latest firmware, if it could be downloaded.
+    pub digest: Option<System76Digest>,
+}
+
+const SCHEDULED_UPDATE: &str = "/boot/efi/system76-firmware-update";
+
 /// Scan for available System76 firmware
-pub fn s76_scan(client: &System76Client, sender: Sender<FirmwareSignal>) {
+///
+/// When offline, or when the latest firmware could not be downloaded, the metadata cached by a
+/// previous scan is used instead.
+pub fn s76_scan(client: &System76Client, config: &Config, sender: Sender<FirmwareSignal>) {
     info!("scanning for system76 devices");
 
     // Thelio system firmware check.
     if let Ok(current) = client.bios() {
-        let info = match client.download() {
-            Ok(S76SystemInfo { digest, changelog }) => Some((digest, changelog)),
-            Err(why) => {
-                let mut error_message = format!("{}", why);
-                let mut cause = why.source();
-                while let Some(error) = cause {
-                    error_message.push_str(format!(": {}", error).as_str());
-                    cause = error.source();
+        let downloaded = if config.offline {
+            None
+        } else {
+            match client.download() {
+                Ok(S76SystemInfo { digest, changelog }) => {
+                    // The changelog lists the newest version first.
+                    let system = CachedSystem {
+                        digest,
+                        versions: changelog
+                            .versions
+                            .iter()
+                            .rev()
+                            .map(|version| {
+                                [&version.bios, &version.date, &version.description]
+                                    .map(|field| Box::from(field.as_ref()))
+                            })
+                            .collect(),
+                    };
+
+                    if let Err(why) = system.store() {
+                        error!("failed to cache system76 firmware metadata: {}", why);
+                    }
+
+                    Some(system)
+                }
+                Err(why) => {
+                    error!("failed to download system76 changelog: {}", crate::format_error(&why));
+                    None
                 }
-                error!("failed to download system76 changelog: {}", error_message);
-                None
             }
         };
 
-        let name: Box<str> = crate::system_board_identity().map(Box::from).unwrap_or(current.model);
+        let (system, age) = metadata_age(MetadataSource::System76, downloaded, CachedSystem::load);
+        let _res = sender.send(FirmwareSignal::Metadata(age));
+
+        let identity = SystemIdentity::read();
+        if identity.is_system76() && !identity.matches_system76_model(&current.model) {
+            warn!(
+                "system76-firmware reports the {} model, whereas DMI reports {:?}",
+                current.model,
+                identity.system76_model()
+            );
+        }
+
+        let name: Box<str> = identity.name().map_or(current.model, Box::from);
+
+        let releases = system.as_ref().map_or_else(Vec::new, |system| {
+            system
+                .versions
+                .iter()
+                .map(|[bios, date, description]| {
+                    ReleaseInfo::from_system76(bios, date, description)
+                })
+                .collect::<Vec<_>>()
+        });
 
         let fw = FirmwareInfo {
             name,
-            current: current.version,
-            latest: info.as_ref().map(|(_, changelog)| {
-                changelog.versions.iter().next().expect("empty changelog").bios.clone()
-            }),
+            current: Some(Version::new(current.version, VersionFormat::System76)),
+            latest: releases.last().map(|release| release.version.clone()),
             install_duration: 1,
+            releases,
         };
 
-        let _res = sender.send(FirmwareSignal::S76System(fw, info));
+        let _res = sender.send(FirmwareSignal::S76System(fw, system.map(|system| system.digest)));
     }
 
     info!("scanning for Thelio I/O devices");
 
     // Thelio I/O system firmware check.
-    let event = match client.thelio_io_list() {
+    match client.thelio_io_list() {
         Ok(list) => {
-            if list.is_empty() {
-                None
-            } else {
-                let lowest_revision = lowest_revision(list.iter().map(|(_, rev)| rev.as_ref()));
-
-                let current =
-                    Box::from(if lowest_revision.is_empty() { "N/A" } else { lowest_revision });
-
-                let (latest, digest) = match client.thelio_io_download() {
-                    Ok(info) => {
-                        let ThelioIoInfo { digest, revision } = info;
-                        (Some(revision), Some(digest))
-                    }
-                    Err(why) => {
-                        error!("failed to download Thelio I/O digest: {:?}", why);
-                        (None, None)
+            if !list.is_empty() {
+                let downloaded = if config.offline {
+                    None
+                } else {
+                    match client.thelio_io_download() {
+                        Ok(ThelioIoInfo { digest, revision }) => {
+                            let thelio = CachedThelioIo { digest, revision };
+                            if let Err(why) = thelio.store() {
+                                error!("failed to cache Thelio I/O firmware metadata: {}", why);
+                            }
+
+                            Some(thelio)
+                        }
+                        Err(why) => {
+                            error!("failed to download Thelio I/O digest: {:?}", why);
+                            None
+                        }
                     }
                 };
 
-                let fw = FirmwareInfo {
-                    name: "Thelio I/O".into(),
-                    current,
-                    latest,
-                    install_duration: 15,
+                let (thelio, age) =
+                    metadata_age(MetadataSource::ThelioIo, downloaded, CachedThelioIo::load);
+                let _res = sender.send(FirmwareSignal::Metadata(age));
+
+                let (latest, digest) = match thelio {
+                    Some(CachedThelioIo { digest, revision }) => {
+                        (Some(Version::plain(revision)), Some(digest))
+                    }
+                    None => (None, None),
                 };
 
-                Some(FirmwareSignal::ThelioIo(fw, digest))
+                for (board, revision) in list.iter() {
+                    let board: &str = board.as_ref();
+                    let revision: &str = revision.as_ref();
+
+                    let fw = FirmwareInfo {
+                        name: ["Thelio I/O (", board, ")"].concat().into(),
+                        // Boards which have not been flashed report no revision.
+                        current: Some(revision)
+                            .filter(|revision| !revision.is_empty())
+                            .map(Version::plain),
+                        latest: latest.clone(),
+                        install_duration: 15,
+                        releases: Vec::new(),
+                    };
+
+                    let _res = sender.send(FirmwareSignal::ThelioIo(ThelioIoSignal {
+                        board: board.into(),
+                        info: fw,
+                        digest: digest.clone(),
+                    }));
+                }
             }
         }
-        Err(why) => Some(FirmwareSignal::Error(None, why.into())),
-    };
-
-    if let Some(event) = event {
-        let _res = sender.send(event);
+        Err(why) => {
+            let _res = sender.send(FirmwareSignal::Error(None, why.into()));
+        }
     }
 
     info!("finished scanning for system76 devices")
 }
 
+/// Whether a System76 system firmware update is scheduled to be installed on the next reboot.
+///
+/// system76-firmware stages scheduled updates in the EFI system partition until they are
+/// installed or unscheduled.
+pub fn s76_system_scheduled() -> bool { Path::new(SCHEDULED_UPDATE).exists() }
+
+/// Falls back to cached metadata if none was downloaded, and describes the age of the metadata.
+fn metadata_age<T, F: FnOnce() -> Option<(T, u64)>>(
+    source: MetadataSource,
+    downloaded: Option<T>,
+    load: F,
+) -> (Option<T>, MetadataAge) {
+    match downloaded {
+        Some(metadata) => {
+            let refreshed = Some(crate::timestamp::current());
+            (Some(metadata), MetadataAge { source, refreshed, offline: false })
+        }
+        None => {
+            let (metadata, refreshed) = match load() {
+                Some((metadata, refreshed)) => (Some(metadata), Some(refreshed)),
+                None => (None, None),
+            };
+
+            (metadata, MetadataAge { source, refreshed, offline: true })
+        }
+    }
+}
+
+
+/// The digest and changelog of System76 system firmware, as cached by the last online scan.
+///
+/// Stored as the digest on the first line, followed by a line of tab-separated fields for each
+/// version in the changelog, from oldest to newest.
+struct CachedSystem {
+    digest: System76Digest,
+    /// The BIOS version, date, and description of each version.
+    versions: Vec<[Box<str>; 3]>,
+}
+
+impl CachedSystem {
+    const FILE: &'static str = "system76/system";
+
+    fn load() -> Option<(Self, u64)> {
+        let path = cache::cached(Self::FILE)?;
+        let refreshed = cache::modified(&path)?;
+        let data = fs::read_to_string(&path).ok()?;
+        Self::parse(&data).map(|system| (system, refreshed))
+    }
+
+    fn parse(data: &str) -> Option<Self> {
+        let mut lines = data.lines();
+        let digest = System76Digest(lines.next()?.to_owned());
+
+        let versions = lines
+            .map(|line| {
+                let mut fields = line.splitn(3, '\t').map(|field| Box::from(unescape(field)));
+                Some([fields.next()?, fields.next()?, fields.next()?])
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        Some(Self { digest, versions })
+    }
+
+    fn serialize(&self) -> String {
+        let mut data = [&*self.digest.0, "\n"].concat();
+
+        for version in &self.versions {
+            let fields = version.iter().map(|field| escape(field)).collect::<Vec<_>>();
+            data.push_str(&fields.join("\t"));
+            data.push('\n');
+        }
+
+        data
+    }
+
+    fn store(&self) -> Result<(), cache::Error> {
+        let path = cache::cache(Self::FILE)?;
+        fs::write(&path, self.serialize()).map_err(cache::Error::Place)
+    }
+}
+
+/// The digest and revision of Thelio I/O firmware, as cached by the last online scan.
+struct CachedThelioIo {
+    digest: System76Digest,
+    revision: Box<str>,
+}
+
+impl CachedThelioIo {
+    const FILE: &'static str = "system76/thelio-io";
+
+    fn load() -> Option<(Self, u64)> {
+        let path = cache::cached(Self::FILE)?;
+        let refreshed = cache::modified(&path)?;
+        let data = fs::read_to_string(&path).ok()?;
+        let (digest, revision) = data.trim_end().split_once('\t')?;
+        let thelio = Self { digest: System76Digest(digest.to_owned()), revision: revision.into() };
+        Some((thelio, refreshed))
+    }
+
+    fn store(&self) -> Result<(), cache::Error> {
+        let path = cache::cache(Self::FILE)?;
+        let data = [&*self.digest.0, "\t", &self.revision, "\n"].concat();
+        fs::write(&path, data).map_err(cache::Error::Place)
+    }
+}
+
+/// Escapes backslashes, tabs, and newlines so that a field fits on a single line.
+fn escape(field: &str) -> String {
+    field.replace('\\', "\\\\").replace('\t', "\\t").replace('\n', "\\n")
+}
+
+fn unescape(field: &str) -> String {
+    let mut output = String::with_capacity(field.len());
+    let mut chars = field.chars();
+
+    while let Some(character) = chars.next() {
+        if character != '\\' {
+            output.push(character);
+            continue;
+        }
+
+        match chars.next() {
+            Some('t') => output.push('\t'),
+            Some('n') => output.push('\n'),
+            Some(other) => output.push(other),
+            None => output.push('\\'),
+        }
+    }
+
+    output
+}
+
 /// Check if the system76-firmware-daemon service is active.
 pub fn s76_firmware_is_active() -> bool {
     crate::systemd_service_is_active("system76-firmware-daemon")
 }
+
+/// Flashes every connected Thelio I/O board, and reports the outcome of each board.
+///
+/// All boards are flashed together by system76-firmware, so the revision of each board is read
+/// back afterwards to determine which boards were updated, and which were not.
+pub fn thelio_io_update(
+    client: &System76Client,
+    digest: &System76Digest,
+    latest: &Version,
+    boards: &[(Entity, Box<str>)],
+    sender: &Sender<FirmwareSignal>,
+) {
+    for &(entity, _) in boards {
+        let _res = sender.send(FirmwareSignal::DeviceFlashing(entity));
+    }
+
+    // The daemon reports why the update failed, whereas the board errors only name the boards.
+    let update_result = client.thelio_io_update(digest);
+    let update_failed = update_result.is_err();
+    if let Err(why) = update_result {
+        let _res = sender.send(FirmwareSignal::Error(None, why.into()));
+    }
+
+    let revisions = match client.thelio_io_list() {
+        Ok(list) => list,
+        Err(why) => {
+            error!(
+                "failed to read Thelio I/O revisions after update: {}",
+                crate::format_error(&why)
+            );
+            for (entity, board) in boards {
+                let _res = sender.send(if !update_failed {
+                    FirmwareSignal::DeviceUpdated(*entity)
+                } else {
+                    FirmwareSignal::Error(Some(*entity), Error::ThelioIoBoard(board.clone()))
+                });
+            }
+
+            return;
+        }
+    };
+
+    let is_updated = |board: &str| {
+        revisions.iter().any(|(id, revision)| {
+            let (id, revision): (&str, &str) = (id.as_ref(), revision.as_ref());
+            id == board && Version::plain(revision) >= *latest
+        })
+    };
+
+    let updated = boards.iter().filter(|(_, board)| is_updated(board)).count();
+
+    if updated != 0 && updated != boards.len() {
+        let _res = sender.send(FirmwareSignal::Error(
+            None,
+            Error::ThelioIoPartial { updated, total: boards.len() },
+        ));
+    }
+
+    for (entity, board) in boards {
+        let _res = sender.send(if is_updated(board) {
+            FirmwareSignal::DeviceUpdated(*entity)
+        } else {
+            FirmwareSignal::Error(Some(*entity), Error::ThelioIoBoard(board.clone()))
+        });
+    }
+
+    info!("updated {} of {} Thelio I/O boards", updated, boards.len());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escaping() {
+        let field = "line\tone\nline \\two";
+        assert!(!escape(field).contains(['\t', '\n']));
+        assert_eq!(unescape(&escape(field)), field);
+    }
+
+    #[test]
+    fn cached_system_round_trip() {
+        let system = CachedSystem {
+            digest: System76Digest("0123abcd".into()),
+            versions: vec![
+                ["2019-08-15_fc5e3fe".into(), "2019-08-15".into(), "- Fix\tboot\n- Update".into()],
+                ["2020-01-02_abc1234".into(), "2020-01-02".into(), "".into()],
+            ],
+        };
+
+        let parsed = CachedSystem::parse(&system.serialize()).unwrap();
+        assert_eq!(parsed.digest.0, system.digest.0);
+        assert_eq!(parsed.versions, system.versions);
+    }
+}
@@ -1,47 +1,381 @@
-use apply::Apply;
 use futures::{
-    future::{AbortHandle, Abortable},
-    stream::StreamExt,
+    future::{self, AbortHandle, Abortable},
+    stream::{self, Stream, StreamExt},
 };
-use std::thread;
-use tokio_udev::{EventType, MonitorBuilder};
+use std::{io, path::PathBuf, sync::mpsc, thread, time::Duration};
+use tokio_udev::{AsyncMonitorSocket, Event, EventType, MonitorBuilder};
+
+/// An error that may occur when monitoring devices.
+#[derive(Debug, Error)]
+pub enum Error {
+    /// The runtime which monitors devices could not be created.
+    #[error("failed to create the runtime for monitoring devices")]
+    Runtime(#[source] io::Error),
+    /// The udev monitor could not be created.
+    #[error("failed to create udev monitor")]
+    Monitor(#[source] io::Error),
+    /// A filter for a kind of device could not be added to the monitor.
+    #[error("failed to watch devices of the {0} subsystem")]
+    Filter(Box<str>, #[source] io::Error),
+    /// The monitor could not listen for events, such as in a container without udev.
+    #[error("failed to listen for udev events")]
+    Listen(#[source] io::Error),
+    /// The thread which monitors devices stopped before it began to monitor them.
+    #[error("device monitor thread stopped unexpectedly")]
+    Stopped,
+}
+
+/// A kind of device to watch for, by its udev subsystem and optionally its device type.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct HotplugFilter {
+    /// The subsystem of the device, such as `usb`.
+    pub subsystem: Box<str>,
+    /// The device type within the subsystem, such as `usb_device`.
+    pub devtype:   Option<Box<str>>,
+}
+
+impl HotplugFilter {
+    /// Watches every device of a subsystem.
+    pub fn subsystem(subsystem: &str) -> Self {
+        Self { subsystem: subsystem.into(), devtype: None }
+    }
+
+    /// Watches devices of one type within a subsystem.
+    pub fn devtype(subsystem: &str, devtype: &str) -> Self {
+        Self { subsystem: subsystem.into(), devtype: Some(devtype.into()) }
+    }
+}
+
+/// Which devices to watch, and how long to wait for a burst of events to settle.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HotplugConfig {
+    /// The kinds of devices to watch.
+    pub filters:  Vec<HotplugFilter>,
+    /// How long to wait after an event for further events, which are reported together.
+    ///
+    /// Plugging in a dock adds many devices at once, which would otherwise each begin a rescan.
+    pub debounce: Duration,
+}
+
+impl HotplugConfig {
+    /// Watches only USB devices, which is what `usb_hotplug_event_loop` watches.
+    pub fn usb() -> Self {
+        Self { filters: vec![HotplugFilter::devtype("usb", "usb_device")], ..Self::default() }
+    }
+}
+
+impl Default for HotplugConfig {
+    /// Watches USB and Thunderbolt devices, NVMe drives, HID receivers, and displays.
+    fn default() -> Self {
+        Self {
+            filters:  vec![
+                HotplugFilter::devtype("usb", "usb_device"),
+                HotplugFilter::subsystem("thunderbolt"),
+                HotplugFilter::subsystem("nvme"),
+                HotplugFilter::subsystem("hidraw"),
+                HotplugFilter::subsystem("drm"),
+            ],
+            debounce: Duration::from_secs(1),
+        }
+    }
+}
+
+/// What happened to a device.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum HotplugAction {
+    /// The device was connected.
+    Add,
+    /// The device was disconnected.
+    Remove,
+    /// The device changed, such as a display being connected to a DRM connector.
+    Change,
+}
+
+/// A device which was connected, disconnected, or changed.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct HotplugDevice {
+    /// What happened to the device.
+    pub action:    HotplugAction,
+    /// The subsystem of the device.
+    pub subsystem: Box<str>,
+    /// The device type within the subsystem, if it has one.
+    pub devtype:   Option<Box<str>>,
+    /// The path of the device in sysfs, which identifies it.
+    pub syspath:   PathBuf,
+    /// The event carried `HOTPLUG=1`, as DRM devices report when a display is connected or
+    /// disconnected.
+    pub hotplug:   bool,
+}
+
+impl HotplugDevice {
+    /// Returns `true` if the event may have connected or disconnected a device with firmware.
+    ///
+    /// Displays plugged straight into a DisplayPort or HDMI port only change their DRM
+    /// connector, so those changes are kept, while other changes, such as a power state, are
+    /// dropped.
+    pub fn needs_rescan(&self) -> bool {
+        match self.action {
+            HotplugAction::Add | HotplugAction::Remove => true,
+            HotplugAction::Change => {
+                &*self.subsystem == "drm"
+                    && (self.hotplug || self.devtype.as_deref() == Some("drm_connector"))
+            }
+        }
+    }
+
+    fn from_event(event: &Event) -> Option<Self> {
+        let action = match event.event_type() {
+            EventType::Add => HotplugAction::Add,
+            EventType::Remove => HotplugAction::Remove,
+            EventType::Change => HotplugAction::Change,
+            _ => return None,
+        };
+
+        Some(Self {
+            action,
+            subsystem: event.subsystem()?.to_string_lossy().into(),
+            devtype: event.devtype().map(|devtype| devtype.to_string_lossy().into()),
+            syspath: event.syspath().to_path_buf(),
+            hotplug: event.property_value("HOTPLUG").map_or(false, |value| value == "1"),
+        })
+    }
+}
+
+/// Adds a device to a burst of events, replacing an earlier event of the same device.
+fn coalesce(pending: &mut Vec<HotplugDevice>, device: HotplugDevice) {
+    match pending.iter_mut().find(|pending| pending.syspath == device.syspath) {
+        Some(pending) => *pending = device,
+        None => pending.push(device),
+    }
+}
 
 /// Convenience function for an event loop which reacts to USB hotplug events.
-pub fn usb_hotplug_event_loop<F: Fn() + Send + 'static>(func: F) -> Option<AbortHandle> {
-    trace!("initiating USB hotplug event loop thread");
+pub fn usb_hotplug_event_loop<F: Fn() + Send + 'static>(func: F) -> Result<AbortHandle, Error> {
+    hotplug_event_loop(HotplugConfig::usb(), move |devices| {
+        if devices.iter().any(HotplugDevice::needs_rescan) {
+            func();
+        }
+    })
+}
+
+/// An event loop which reacts to devices being connected, disconnected, or changed.
+///
+/// Events are debounced, so that a burst of events calls `func` once with every device that
+/// changed, from which the consumer may decide whether to scan for firmware again.
+///
+/// Returns an error if udev cannot be monitored, such as in a container or sandbox, in which case
+/// the consumer should go without hotplug support.
+pub fn hotplug_event_loop<F: Fn(Vec<HotplugDevice>) + Send + 'static>(
+    config: HotplugConfig,
+    func: F,
+) -> Result<AbortHandle, Error> {
+    let HotplugConfig { filters, debounce } = config;
+    hotplug_event_loop_with(move || hotplug_events(&filters), debounce, func)
+}
+
+/// An event loop which reacts to the devices of an event source, in place of udev.
+///
+/// The source is created on the thread of the event loop, within its runtime, and the error it
+/// returns, if any, is returned from this function.
+pub fn hotplug_event_loop_with<S, C, F>(
+    source: C,
+    debounce: Duration,
+    func: F,
+) -> Result<AbortHandle, Error>
+where
+    S: Stream<Item = HotplugDevice>,
+    C: FnOnce() -> Result<S, Error> + Send + 'static,
+    F: Fn(Vec<HotplugDevice>) + Send + 'static,
+{
+    trace!("initiating hotplug event loop thread");
 
     let (abort_handle, abort_registration) = AbortHandle::new_pair();
+    let (ready_tx, ready_rx) = mpsc::sync_channel(1);
 
     thread::spawn(move || {
-        trace!("USB hotplug events now being processed");
-
-        let _ =
-            tokio::runtime::Builder::new_current_thread().enable_io().build().unwrap().block_on(
-                async move {
-                    let _res = MonitorBuilder::new()
-                        .expect("couldn't create monitor builder")
-                        .match_subsystem_devtype("usb", "usb_device")
-                        .expect("failed to add filter for USB devices")
-                        .listen()
-                        .expect("couldn't create MonitorSocket")
-                        .apply(tokio_udev::AsyncMonitorSocket::new)
-                        .expect("couldn't create AsyncMonitorSocket")
-                        .for_each(move |event| {
-                            if let Ok(event) = event {
-                                if let EventType::Add | EventType::Remove = event.event_type() {
-                                    func();
-                                }
-                            }
-
-                            futures::future::ready(())
-                        })
-                        .apply(|future| Abortable::new(future, abort_registration))
-                        .await;
-                },
-            );
-
-        trace!("usb hotplug thread stopped");
+        let runtime =
+            match tokio::runtime::Builder::new_current_thread().enable_io().enable_time().build() {
+                Ok(runtime) => runtime,
+                Err(why) => {
+                    let _ = ready_tx.send(Err(Error::Runtime(why)));
+                    return;
+                }
+            };
+
+        runtime.block_on(async move {
+            let events = match source() {
+                Ok(events) => events,
+                Err(why) => {
+                    let _ = ready_tx.send(Err(why));
+                    return;
+                }
+            };
+
+            let _ = ready_tx.send(Ok(()));
+            trace!("hotplug events now being processed");
+
+            let batches = debounce_hotplug(events, debounce).for_each(|devices| {
+                func(devices);
+                future::ready(())
+            });
+
+            let _res = Abortable::new(batches, abort_registration).await;
+        });
+
+        trace!("hotplug thread stopped");
     });
 
-    Some(abort_handle)
+    ready_rx.recv().map_err(|_| Error::Stopped)?.map(|()| abort_handle)
+}
+
+/// A stream of the devices which udev reports as connected, disconnected, or changed.
+///
+/// Must be called within a tokio runtime which has IO enabled.
+pub fn hotplug_events(
+    filters: &[HotplugFilter],
+) -> Result<impl Stream<Item = HotplugDevice>, Error> {
+    let mut builder = MonitorBuilder::new().map_err(Error::Monitor)?;
+    for filter in filters {
+        let subsystem = &*filter.subsystem;
+        builder = match filter.devtype.as_deref() {
+            Some(devtype) => builder.match_subsystem_devtype(subsystem, devtype),
+            None => builder.match_subsystem(subsystem),
+        }
+        .map_err(|why| Error::Filter(subsystem.into(), why))?;
+    }
+
+    let socket = builder.listen().and_then(AsyncMonitorSocket::new).map_err(Error::Listen)?;
+
+    let events = socket.filter_map(|event| {
+        let device = match event {
+            Ok(event) => HotplugDevice::from_event(&event),
+            Err(why) => {
+                warn!("failed to receive udev event: {}", why);
+                None
+            }
+        };
+
+        future::ready(device)
+    });
+
+    Ok(events)
+}
+
+/// Gathers devices into bursts, which end once no device has changed for `debounce`.
+///
+/// Must be polled within a tokio runtime which has time enabled.
+pub fn debounce_hotplug<S: Stream<Item = HotplugDevice>>(
+    events: S,
+    debounce: Duration,
+) -> impl Stream<Item = Vec<HotplugDevice>> {
+    stream::unfold(Some(Box::pin(events)), move |events| async move {
+        let mut events = events?;
+        let mut pending = Vec::new();
+
+        loop {
+            let event = if pending.is_empty() {
+                events.next().await
+            } else {
+                match tokio::time::timeout(debounce, events.next()).await {
+                    Ok(event) => event,
+                    Err(_) => return Some((pending, Some(events))),
+                }
+            };
+
+            match event {
+                Some(device) => coalesce(&mut pending, device),
+                // Devices which changed before the source ended are still reported.
+                None if pending.is_empty() => return None,
+                None => return Some((pending, None)),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(action: HotplugAction, syspath: &str) -> HotplugDevice {
+        HotplugDevice {
+            action,
+            subsystem: "usb".into(),
+            devtype: Some("usb_device".into()),
+            syspath: syspath.into(),
+            hotplug: false,
+        }
+    }
+
+    #[test]
+    fn rescans_after_connections_and_display_hotplugs() {
+        let drm = |devtype: &str, hotplug| HotplugDevice {
+            action: HotplugAction::Change,
+            subsystem: "drm".into(),
+            devtype: Some(devtype.into()),
+            syspath: "/sys/devices/card0".into(),
+            hotplug,
+        };
+
+        assert!(device(HotplugAction::Add, "/sys/devices/dock").needs_rescan());
+        assert!(device(HotplugAction::Remove, "/sys/devices/dock").needs_rescan());
+        assert!(!device(HotplugAction::Change, "/sys/devices/dock").needs_rescan());
+
+        assert!(drm("drm_minor", true).needs_rescan());
+        assert!(drm("drm_connector", false).needs_rescan());
+        assert!(!drm("drm_minor", false).needs_rescan());
+
+        let mut usb = device(HotplugAction::Change, "/sys/devices/dock");
+        usb.hotplug = true;
+        assert!(!usb.needs_rescan());
+    }
+
+    #[test]
+    fn bursts_are_coalesced() {
+        let dock = || device(HotplugAction::Add, "/sys/devices/dock");
+        let removed = device(HotplugAction::Remove, "/sys/devices/dock");
+        let keyboard = device(HotplugAction::Add, "/sys/devices/keyboard");
+
+        let burst = stream::iter(vec![dock(), keyboard.clone(), removed.clone()]);
+        let later = stream::once(async {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            dock()
+        });
+
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap();
+        let batches = runtime.block_on(
+            debounce_hotplug(burst.chain(later), Duration::from_millis(20)).collect::<Vec<_>>(),
+        );
+
+        assert_eq!(batches, vec![vec![removed, keyboard], vec![dock()]]);
+    }
+
+    #[test]
+    fn event_loop_reports_source() {
+        let (tx, rx) = mpsc::channel();
+        let devices = vec![device(HotplugAction::Add, "/sys/devices/dock")];
+        let source = devices.clone();
+
+        let handle = hotplug_event_loop_with(
+            move || Ok(stream::iter(source)),
+            Duration::from_millis(10),
+            move |devices| {
+                let _ = tx.send(devices);
+            },
+        )
+        .unwrap();
+
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5)).unwrap(), devices);
+        handle.abort();
+    }
+
+    #[test]
+    fn event_loop_returns_source_error() {
+        let result = hotplug_event_loop_with(
+            || Err::<stream::Empty<HotplugDevice>, _>(Error::Listen(io::ErrorKind::Other.into())),
+            Duration::from_millis(10),
+            |_| (),
+        );
+
+        assert!(matches!(result, Err(Error::Listen(_))));
+    }
 }
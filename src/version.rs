@@ -0,0 +1,391 @@
+//! Version strings which are compared according to the format that they were written in.
+
+use serde::{Deserialize, Serialize};
+use std::{cmp::Ordering, fmt};
+
+/// The format that a version string was written in.
+///
+/// The numbered variants mirror `FwupdVersionFormat`, which fwupd assigns to each device.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VersionFormat {
+    /// The format was not reported.
+    Unknown,
+    /// An opaque string which carries no numeric meaning.
+    #[default]
+    Plain,
+    /// A single number, such as `123`.
+    Number,
+    /// Two numbers, such as `1.2`.
+    Pair,
+    /// Three numbers, such as `1.2.3`.
+    Triplet,
+    /// Four numbers, such as `1.2.3.4`.
+    Quad,
+    /// Binary-coded decimal, such as `1.2`.
+    Bcd,
+    /// Intel Management Engine versions, such as `11.8.50.3425`.
+    IntelMe,
+    /// Intel Management Engine versions, with a different interpretation of the first byte.
+    IntelMe2,
+    /// Legacy Microsoft Surface versions.
+    SurfaceLegacy,
+    /// Microsoft Surface versions.
+    Surface,
+    /// Dell BIOS versions, such as `1.2.3`.
+    DellBios,
+    /// A hexadecimal number, such as `0x0000001a`.
+    Hex,
+    /// System76 BIOS versions, which may be a date with a git revision, as in
+    /// `2022-05-17_a1b2c3d`.
+    System76,
+}
+
+impl VersionFormat {
+    /// Converts the raw `FwupdVersionFormat` value reported by fwupd.
+    pub fn from_fwupd(raw: u32) -> Self {
+        match raw {
+            1 => VersionFormat::Plain,
+            2 => VersionFormat::Number,
+            3 => VersionFormat::Pair,
+            4 => VersionFormat::Triplet,
+            5 => VersionFormat::Quad,
+            6 => VersionFormat::Bcd,
+            7 => VersionFormat::IntelMe,
+            8 => VersionFormat::IntelMe2,
+            9 => VersionFormat::SurfaceLegacy,
+            10 => VersionFormat::Surface,
+            11 => VersionFormat::DellBios,
+            12 => VersionFormat::Hex,
+            _ => VersionFormat::Unknown,
+        }
+    }
+
+    /// Compares two version strings written in this format.
+    ///
+    /// Strings which parse in the expected format rank before those which do not, and strings
+    /// which do not parse are ordered by a natural comparison, so that the order stays total.
+    pub fn compare(self, a: &str, b: &str) -> Ordering {
+        use VersionFormat::*;
+        match self {
+            Number | Pair | Triplet | Quad | Bcd | IntelMe | IntelMe2 | SurfaceLegacy | Surface
+            | DellBios => compare_parsed(a, b, parse_dotted, |a, b| compare_components(a, b)),
+            Hex => compare_parsed(a, b, parse_hex, Ord::cmp),
+            System76 => compare_system76(a, b),
+            Unknown | Plain => compare_natural(a, b),
+        }
+    }
+
+    /// The position of the format in the fixed order that versions of differing formats are
+    /// compared by.
+    fn rank(self) -> u8 { self as u8 }
+}
+
+/// A version string paired with the format that it should be compared with.
+///
+/// Equality and ordering follow the format, so `1.0` and `1.0.0` are equal as a triplet.
+/// Versions of differing formats are never equal, and are ordered by the declaration order of
+/// their formats.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Version {
+    raw:    Box<str>,
+    format: VersionFormat,
+}
+
+impl Version {
+    /// Creates a version which will be compared according to `format`.
+    pub fn new<S: Into<Box<str>>>(raw: S, format: VersionFormat) -> Self {
+        Self { raw: raw.into(), format }
+    }
+
+    /// Creates a version which has no known format.
+    pub fn plain<S: Into<Box<str>>>(raw: S) -> Self { Self::new(raw, VersionFormat::Plain) }
+
+    /// The version string, as it was reported.
+    pub fn as_str(&self) -> &str { &self.raw }
+
+    /// The format this version is compared with.
+    pub fn format(&self) -> VersionFormat { self.format }
+
+    /// True if the version string is empty.
+    pub fn is_empty(&self) -> bool { self.raw.is_empty() }
+}
+
+impl AsRef<str> for Version {
+    fn as_ref(&self) -> &str { &self.raw }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { f.write_str(&self.raw) }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.format
+            .rank()
+            .cmp(&other.format.rank())
+            .then_with(|| self.format.compare(&self.raw, &other.raw))
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> { Some(self.cmp(other)) }
+}
+
+impl PartialEq for Version {
+    fn eq(&self, other: &Self) -> bool { self.cmp(other) == Ordering::Equal }
+}
+
+impl Eq for Version {}
+
+/// Compares the values that two strings parse to, ranking strings which parse before those which
+/// do not, and ordering strings which do not parse by a natural comparison.
+fn compare_parsed<T>(
+    a: &str,
+    b: &str,
+    parse: fn(&str) -> Option<T>,
+    compare: fn(&T, &T) -> Ordering,
+) -> Ordering {
+    match (parse(a), parse(b)) {
+        (Some(x), Some(y)) => compare(&x, &y),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => compare_natural(a, b),
+    }
+}
+
+/// Parses dot-separated decimal numbers.
+fn parse_dotted(input: &str) -> Option<Vec<u64>> {
+    input.trim().split('.').map(|part| part.parse::<u64>().ok()).collect()
+}
+
+/// Parses a hexadecimal number, with or without a `0x` prefix.
+fn parse_hex(input: &str) -> Option<u64> {
+    let input = input.trim();
+    let input = input.strip_prefix("0x").or_else(|| input.strip_prefix("0X")).unwrap_or(input);
+    u64::from_str_radix(input, 16).ok()
+}
+
+/// Compares System76 BIOS versions.
+///
+/// Dated versions are ordered by their date alone, as the git revision that follows carries no
+/// order. Dated versions are always newer than the vendor versions that they replaced, which are
+/// ordered by a natural comparison.
+fn compare_system76(a: &str, b: &str) -> Ordering {
+    match (system76_date(a), system76_date(b)) {
+        (Some(x), Some(y)) => x.cmp(&y),
+        (Some(_), None) => Ordering::Greater,
+        (None, Some(_)) => Ordering::Less,
+        (None, None) => compare_natural(a, b),
+    }
+}
+
+/// Parses the `YYYY-MM-DD` prefix of a System76 BIOS version.
+fn system76_date(input: &str) -> Option<[u16; 3]> {
+    let date = input.trim().split(['_', ' ']).next()?;
+    let mut fields = date.splitn(3, '-');
+    let year = fields.next().filter(|year| year.len() == 4)?.parse::<u16>().ok()?;
+    let month = fields.next()?.parse::<u16>().ok().filter(|month| (1..=12).contains(month))?;
+    let day = fields.next()?.parse::<u16>().ok().filter(|day| (1..=31).contains(day))?;
+    Some([year, month, day])
+}
+
+/// Compares two sequences of numbers, treating missing components as zero.
+fn compare_components(a: &[u64], b: &[u64]) -> Ordering {
+    let length = a.len().max(b.len());
+    (0..length)
+        .map(|idx| a.get(idx).unwrap_or(&0).cmp(b.get(idx).unwrap_or(&0)))
+        .find(|ordering| *ordering != Ordering::Equal)
+        .unwrap_or(Ordering::Equal)
+}
+
+/// Compares strings character by character, except that runs of digits compare by value.
+fn compare_natural(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a.trim(), b.trim());
+
+    loop {
+        let (next_a, next_b) = match (a.chars().next(), b.chars().next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) => (x, y),
+        };
+
+        let ordering = if next_a.is_ascii_digit() && next_b.is_ascii_digit() {
+            let (number_a, rest_a) = split_digits(a);
+            let (number_b, rest_b) = split_digits(b);
+            a = rest_a;
+            b = rest_b;
+            compare_digits(number_a, number_b)
+        } else {
+            a = &a[next_a.len_utf8()..];
+            b = &b[next_b.len_utf8()..];
+            next_a.cmp(&next_b)
+        };
+
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+/// Splits the leading run of ASCII digits from the rest of the string.
+fn split_digits(input: &str) -> (&str, &str) {
+    let end = input.find(|c: char| !c.is_ascii_digit()).unwrap_or(input.len());
+    input.split_at(end)
+}
+
+/// Compares two runs of digits by value, without overflowing on long runs.
+fn compare_digits(a: &str, b: &str) -> Ordering {
+    let a = a.trim_start_matches('0');
+    let b = b.trim_start_matches('0');
+    a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const DOTTED: &[VersionFormat] = &[
+        VersionFormat::Number,
+        VersionFormat::Pair,
+        VersionFormat::Triplet,
+        VersionFormat::Quad,
+        VersionFormat::Bcd,
+        VersionFormat::IntelMe,
+        VersionFormat::IntelMe2,
+        VersionFormat::SurfaceLegacy,
+        VersionFormat::Surface,
+        VersionFormat::DellBios,
+    ];
+
+    fn dotted(components: &[u32]) -> String {
+        components.iter().map(u32::to_string).collect::<Vec<_>>().join(".")
+    }
+
+    /// Versions of any format, whose strings may or may not parse in that format.
+    fn mixed_version() -> impl Strategy<Value = Version> {
+        let format = prop::sample::select(vec![
+            VersionFormat::Unknown,
+            VersionFormat::Plain,
+            VersionFormat::Triplet,
+            VersionFormat::Hex,
+            VersionFormat::System76,
+        ]);
+        let raw = prop_oneof![
+            prop::collection::vec(0u32..20, 1..4).prop_map(|components| dotted(&components)),
+            (0u32..0x20).prop_map(|value| format!("0x{:x}", value)),
+            (2019u16..2021, 1u16..=2)
+                .prop_map(|(year, month)| format!("{}-{:02}-01_a", year, month)),
+            "[0-9a-fx.\\-]{0,6}",
+        ];
+        (raw, format).prop_map(|(raw, format)| Version::new(raw, format))
+    }
+
+    #[test]
+    fn from_fwupd() {
+        assert_eq!(VersionFormat::from_fwupd(0), VersionFormat::Unknown);
+        assert_eq!(VersionFormat::from_fwupd(4), VersionFormat::Triplet);
+        assert_eq!(VersionFormat::from_fwupd(12), VersionFormat::Hex);
+        assert_eq!(VersionFormat::from_fwupd(255), VersionFormat::Unknown);
+    }
+
+    #[test]
+    fn triplet_ignores_trailing_zeroes() {
+        let a = Version::new("1.0", VersionFormat::Triplet);
+        let b = Version::new("1.0.0", VersionFormat::Triplet);
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn system76_dates() {
+        let format = VersionFormat::System76;
+        assert_eq!(format.compare("2020-02-03_35b3d61", "2019-12-20_ffffff0"), Ordering::Greater);
+        assert_eq!(format.compare("2020-02-03_35b3d61", "2020-02-03_0000000"), Ordering::Equal);
+        assert_eq!(format.compare("1.07.09RSA1", "2019-05-01_abcdef0"), Ordering::Less);
+        assert_eq!(format.compare("1.07.09", "1.07.10"), Ordering::Less);
+    }
+
+    #[test]
+    fn natural() {
+        assert_eq!(compare_natural("F3", "F10"), Ordering::Less);
+        assert_eq!(compare_natural("", "F3"), Ordering::Less);
+        assert_eq!(compare_natural("0.l2.12.0", "0.2.11.0"), Ordering::Greater);
+        assert_eq!(compare_natural("99999999999999999999999", "1"), Ordering::Greater);
+    }
+
+    proptest! {
+        #[test]
+        fn dotted_formats_match_numeric_order(
+            a in prop::collection::vec(0u32..100_000, 1..5),
+            b in prop::collection::vec(0u32..100_000, 1..5),
+        ) {
+            let wide = |input: &[u32]| input.iter().map(|&x| u64::from(x)).collect::<Vec<_>>();
+            let expected = compare_components(&wide(&a), &wide(&b));
+            for &format in DOTTED {
+                prop_assert_eq!(format.compare(&dotted(&a), &dotted(&b)), expected);
+            }
+        }
+
+        #[test]
+        fn hex_matches_numeric_order(a in any::<u32>(), b in any::<u32>(), prefix in any::<bool>()) {
+            let prefix = if prefix { "0x" } else { "" };
+            let (x, y) = (format!("{}{:08x}", prefix, a), format!("{}{:x}", prefix, b));
+            prop_assert_eq!(VersionFormat::Hex.compare(&x, &y), a.cmp(&b));
+        }
+
+        #[test]
+        fn plain_orders_numbers_by_value(a in any::<u64>(), b in any::<u64>(), name in "[A-Z]{1,3}") {
+            let (x, y) = (format!("{}{}", name, a), format!("{}{}", name, b));
+            prop_assert_eq!(VersionFormat::Plain.compare(&x, &y), a.cmp(&b));
+            prop_assert_eq!(VersionFormat::Unknown.compare(&x, &y), a.cmp(&b));
+        }
+
+        #[test]
+        fn plain_is_antisymmetric(a in "[0-9A-Za-z.\\-]{0,12}", b in "[0-9A-Za-z.\\-]{0,12}") {
+            let format = VersionFormat::Plain;
+            prop_assert_eq!(format.compare(&a, &b), format.compare(&b, &a).reverse());
+            prop_assert_eq!(format.compare(&a, &a), Ordering::Equal);
+        }
+
+        #[test]
+        fn system76_orders_by_date(
+            a in (2015u16..2040, 1u16..=12, 1u16..=28),
+            b in (2015u16..2040, 1u16..=12, 1u16..=28),
+            rev_a in "[0-9a-f]{7}",
+            rev_b in "[0-9a-f]{7}",
+        ) {
+            let x = format!("{:04}-{:02}-{:02}_{}", a.0, a.1, a.2, rev_a);
+            let y = format!("{:04}-{:02}-{:02}_{}", b.0, b.1, b.2, rev_b);
+            prop_assert_eq!(VersionFormat::System76.compare(&x, &y), a.cmp(&b));
+        }
+
+        #[test]
+        fn mixed_ordering_is_total(
+            a in mixed_version(),
+            b in mixed_version(),
+            c in mixed_version(),
+        ) {
+            prop_assert_eq!(a.cmp(&b), b.cmp(&a).reverse());
+            prop_assert_eq!(a.cmp(&a), Ordering::Equal);
+            if a <= b && b <= c {
+                prop_assert!(a <= c, "{:?} <= {:?} <= {:?}", a, b, c);
+            }
+            if a == b && b == c {
+                prop_assert_eq!(&a, &c);
+            }
+        }
+
+        #[test]
+        fn version_ordering_is_consistent(
+            a in prop::collection::vec(0u32..1000, 1..4),
+            b in prop::collection::vec(0u32..1000, 1..4),
+        ) {
+            let x = Version::new(dotted(&a), VersionFormat::Triplet);
+            let y = Version::new(dotted(&b), VersionFormat::Triplet);
+            prop_assert_eq!(x == y, x.cmp(&y) == Ordering::Equal);
+            prop_assert_eq!(x < y, y > x);
+        }
+    }
+}
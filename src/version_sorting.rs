@@ -1,19 +1,24 @@
 //! Simple version-sorting trait and function.
 
+use crate::version::{Version, VersionFormat};
+
 /// Trait which describes a type which contains a version string.
 pub trait Versioned {
     fn version(&self) -> &str;
+
+    /// The version string, paired with the format it is compared with.
+    fn versioned(&self, format: VersionFormat) -> Version { Version::new(self.version(), format) }
 }
 
 // Sort from oldest to latest.
-pub fn sort_versions<V: Versioned>(input: &mut [V]) {
-    input.sort_by(|a, b| human_sort::compare(a.version(), b.version()));
+pub fn sort_versions<V: Versioned>(format: VersionFormat, input: &mut [V]) {
+    input.sort_by(|a, b| format.compare(a.version(), b.version()));
 }
 
 /// Sort from latest to oldest.
 #[cfg(test)] // Only used in tests
-pub fn sort_versions_reverse<V: Versioned>(input: &mut [V]) {
-    input.sort_by(|a, b| human_sort::compare(b.version(), a.version()));
+pub fn sort_versions_reverse<V: Versioned>(format: VersionFormat, input: &mut [V]) {
+    input.sort_by(|a, b| format.compare(b.version(), a.version()));
 }
 
 impl Versioned for fwupd_dbus::Release {
@@ -59,7 +64,7 @@ mod tests {
             Foo { version: "0.l2.12.0".into() },
         ];
 
-        sort_versions(&mut input);
+        sort_versions(VersionFormat::Plain, &mut input);
         assert_eq!(input, expected);
     }
 
@@ -75,7 +80,7 @@ mod tests {
             Foo { version: "0.2.8.1".into() },
         ];
 
-        sort_versions_reverse(&mut input);
+        sort_versions_reverse(VersionFormat::Plain, &mut input);
         assert_eq!(input, expected);
     }
 }
@@ -0,0 +1,162 @@
+mod localize;
+mod state;
+mod ui;
+
+use self::state::State;
+use crossterm::{
+    event::{self, Event as TermEvent, KeyCode, KeyEvent, KeyEventKind},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use firmware_manager::{authorize, check_authorization, event_loop, Action, FirmwareSignal};
+use i18n_embed::DesktopLanguageRequester;
+use ratatui::{backend::CrosstermBackend, Terminal};
+use std::{
+    io::{self, Stdout},
+    sync::mpsc::{self, Sender},
+    thread,
+};
+
+/// Events which the frontend responds to.
+#[allow(clippy::large_enum_variant)]
+enum Event {
+    Firmware(FirmwareSignal),
+    Terminal(TermEvent),
+}
+
+fn main() {
+    translate();
+
+    // Authentication may prompt on the console, so it happens before the screen is taken over.
+    let authorized = match check_authorization(Action::UpdateFirmware) {
+        authorization if authorization.is_authorized() => true,
+        authorization if authorization.is_possible() => {
+            authorize(Action::UpdateFirmware).is_authorized()
+        }
+        _ => false,
+    };
+
+    let mut terminal = match setup() {
+        Ok(terminal) => terminal,
+        Err(why) => {
+            eprintln!("failed to set up the terminal: {}", why);
+            std::process::exit(1);
+        }
+    };
+
+    // The terminal is restored before a panic is printed, so that the message is readable.
+    let panic_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let _ = restore();
+        panic_hook(info);
+    }));
+
+    let result = run(&mut terminal, authorized);
+
+    let _ = restore();
+
+    if let Err(why) = result {
+        eprintln!("{}", why);
+        std::process::exit(1);
+    }
+}
+
+fn run(terminal: &mut Terminal<CrosstermBackend<Stdout>>, authorized: bool) -> io::Result<()> {
+    let (event_tx, event_rx) = mpsc::channel();
+    let (tx, rx) = mpsc::channel();
+
+    // Signals from the firmware manager core and input from the terminal are received together.
+    let (signal_tx, signal_rx) = mpsc::channel();
+    let background = thread::spawn(move || event_loop(event_rx, signal_tx));
+
+    forward(tx.clone(), move || signal_rx.recv().ok().map(Event::Firmware));
+    forward(tx, || event::read().ok().map(Event::Terminal));
+
+    let mut state = State::new(event_tx, authorized);
+    state.scan();
+
+    let result = loop {
+        if let Err(why) = terminal.draw(|frame| ui::draw(frame, &state)) {
+            break Err(why);
+        }
+
+        match rx.recv() {
+            Ok(Event::Firmware(signal)) => state.signal(signal),
+            Ok(Event::Terminal(TermEvent::Key(key))) if key.kind == KeyEventKind::Press => {
+                if key_pressed(&mut state, key) {
+                    break Ok(());
+                }
+            }
+            Ok(Event::Terminal(_)) => (),
+            Err(_) => break Ok(()),
+        }
+    };
+
+    let _ = state.sender.send(firmware_manager::FirmwareEvent::Stop);
+    let _ = background.join();
+
+    result
+}
+
+/// Responds to a key press, returning `true` if the application should quit.
+fn key_pressed(state: &mut State, key: KeyEvent) -> bool {
+    if state.prompt.is_some() {
+        match key.code {
+            KeyCode::Enter | KeyCode::Char('y') => state.confirm(),
+            KeyCode::Esc | KeyCode::Char('n') => state.dismiss(),
+            _ => (),
+        }
+
+        return false;
+    }
+
+    match key.code {
+        KeyCode::Up | KeyCode::Char('k') => state.select_previous(),
+        KeyCode::Down | KeyCode::Char('j') => state.select_next(),
+        KeyCode::Enter | KeyCode::Char(' ') => state.toggle_changelog(),
+        KeyCode::Char('u') => state.activate(),
+        KeyCode::F(5) | KeyCode::Char('r') => state.scan(),
+        // Flashing must not be interrupted.
+        KeyCode::Esc | KeyCode::Char('q') if state.is_flashing() => {
+            state.message = Some(fl!("error-flashing"));
+        }
+        KeyCode::Esc | KeyCode::Char('q') => return true,
+        _ => (),
+    }
+
+    false
+}
+
+/// Forwards events from a blocking source to the main loop, until either side closes.
+fn forward<F: FnMut() -> Option<Event> + Send + 'static>(sender: Sender<Event>, mut next: F) {
+    thread::spawn(move || {
+        while let Some(event) = next() {
+            if sender.send(event).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+fn setup() -> io::Result<Terminal<CrosstermBackend<Stdout>>> {
+    enable_raw_mode()?;
+    execute!(io::stdout(), EnterAlternateScreen)?;
+    Terminal::new(CrosstermBackend::new(io::stdout()))
+}
+
+fn restore() -> io::Result<()> {
+    disable_raw_mode()?;
+    execute!(io::stdout(), LeaveAlternateScreen)
+}
+
+fn translate() {
+    let localizer = crate::localize::localizer();
+    let requested_languages = DesktopLanguageRequester::requested_languages();
+
+    if let Err(error) = localizer.select(&requested_languages) {
+        eprintln!("Error while loading languages for firmware-manager-tui {}", error);
+    }
+
+    // Unicode isolation marks would be drawn as-is by the terminal.
+    crate::localize::LANGUAGE_LOADER.set_use_isolating(false);
+}
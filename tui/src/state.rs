@@ -0,0 +1,375 @@
+use crate::fl;
+use firmware_manager::*;
+use slotmap::{SecondaryMap, SparseSecondaryMap};
+use std::{process::Command, sync::mpsc::Sender, sync::Arc};
+
+/// The progress of an update which is in flight.
+#[derive(Clone, Copy, Debug)]
+pub enum Progress {
+    /// The update was requested, and has not begun yet.
+    Waiting,
+    Downloading {
+        downloaded: u64,
+        total: u64,
+    },
+    /// The checksum of the downloaded firmware is being verified.
+    Verifying,
+    Flashing,
+}
+
+/// An action which must be taken before a fwupd device can be updated.
+pub enum DeviceAction {
+    Activate(Arc<FwupdDevice>),
+    Unlock(Arc<FwupdDevice>),
+}
+
+/// A modal prompt which is shown above the device list.
+pub enum Prompt {
+    /// Asks for confirmation before installing system firmware, which reboots the system.
+    Reboot(Entity),
+    Request(DeviceRequest),
+}
+
+/// Storage for device entities, and the components assigned to them.
+#[derive(Default)]
+pub struct Components {
+    /// Information about the firmware of each device.
+    pub info: SecondaryMap<Entity, FirmwareInfo>,
+
+    /// Explains why a device cannot be updated right now.
+    pub problems: SparseSecondaryMap<Entity, String>,
+
+    /// Actions which must be taken before a device can be updated.
+    pub actions: SparseSecondaryMap<Entity, DeviceAction>,
+
+    /// The device and latest release of fwupd devices which can be updated.
+    pub fwupd: SparseSecondaryMap<Entity, (Arc<FwupdDevice>, Arc<FwupdRelease>)>,
+
+    /// The digest of System76 system firmware which can be updated.
+    pub system76: SparseSecondaryMap<Entity, System76Digest>,
+
+    /// The identifier and firmware digest of each Thelio I/O board.
+    pub thelio: SparseSecondaryMap<Entity, (Box<str>, Option<System76Digest>)>,
+
+    /// Devices with updates which can be installed right now.
+    pub ready: SparseSecondaryMap<Entity, ()>,
+
+    /// The progress of updates which are in flight.
+    pub progress: SparseSecondaryMap<Entity, Progress>,
+
+    /// The outcome of the last update of a device, or of an action upon it.
+    pub status: SparseSecondaryMap<Entity, String>,
+
+    /// Devices whose changelogs are revealed.
+    pub expanded: SparseSecondaryMap<Entity, ()>,
+}
+
+pub struct State {
+    pub components: Components,
+    pub entities: Entities,
+
+    /// System firmware, in the order that it was discovered.
+    pub system: Vec<Entity>,
+
+    /// Device firmware, in the order that it was discovered.
+    pub devices: Vec<Entity>,
+
+    /// The index of the selected device, with system firmware listed first.
+    pub selected: usize,
+
+    pub prompt: Option<Prompt>,
+
+    /// A message about the system as a whole, such as an error without a device.
+    pub message: Option<String>,
+
+    pub scanning: bool,
+
+    /// Whether the user may update firmware, which disables updates if not.
+    pub authorized: bool,
+
+    pub sender: Sender<FirmwareEvent>,
+}
+
+impl State {
+    pub fn new(sender: Sender<FirmwareEvent>, authorized: bool) -> Self {
+        Self {
+            components: Components::default(),
+            entities: Entities::default(),
+            system: Vec::new(),
+            devices: Vec::new(),
+            selected: 0,
+            prompt: None,
+            message: if authorized { None } else { Some(fl!("error-not-authorized")) },
+            scanning: false,
+            authorized,
+            sender,
+        }
+    }
+
+    /// Every device, in the order that they are listed.
+    pub fn rows(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.system.iter().chain(self.devices.iter()).copied()
+    }
+
+    pub fn selected(&self) -> Option<Entity> { self.rows().nth(self.selected) }
+
+    /// Whether firmware is being flashed, which must not be interrupted.
+    pub fn is_flashing(&self) -> bool { !self.components.progress.is_empty() }
+
+    pub fn select_next(&mut self) {
+        let rows = self.system.len() + self.devices.len();
+        self.selected = (self.selected + 1).min(rows.saturating_sub(1));
+    }
+
+    pub fn select_previous(&mut self) { self.selected = self.selected.saturating_sub(1); }
+
+    /// Reveals or hides the changelog of the selected device.
+    pub fn toggle_changelog(&mut self) {
+        if let Some(entity) = self.selected() {
+            if self.components.expanded.remove(entity).is_none() {
+                self.components.expanded.insert(entity, ());
+            }
+        }
+    }
+
+    /// Requests a scan, unless firmware is being flashed.
+    pub fn scan(&mut self) {
+        if !self.is_flashing() {
+            let _ = self.sender.send(FirmwareEvent::Scan);
+        }
+    }
+
+    /// Acts upon the selected device: taking the action that it requires, or updating it.
+    pub fn activate(&mut self) {
+        let entity = match self.selected() {
+            Some(entity) if self.authorized && !self.is_flashing() => entity,
+            _ => return,
+        };
+
+        if let Some(action) = self.components.actions.get(entity) {
+            let event = match action {
+                DeviceAction::Activate(device) => FirmwareEvent::Activate(entity, device.clone()),
+                DeviceAction::Unlock(device) => FirmwareEvent::Unlock(entity, device.clone()),
+            };
+
+            self.components.progress.insert(entity, Progress::Waiting);
+            let _ = self.sender.send(event);
+        } else if self.components.ready.contains_key(entity) {
+            if self.entities.is_system(entity) {
+                self.prompt = Some(Prompt::Reboot(entity));
+            } else {
+                self.update(entity);
+            }
+        }
+    }
+
+    /// Closes the prompt, installing system firmware if that was what the prompt asked.
+    pub fn confirm(&mut self) {
+        if let Some(Prompt::Reboot(entity)) = self.prompt.take() {
+            self.update(entity);
+        }
+    }
+
+    pub fn dismiss(&mut self) { self.prompt = None; }
+
+    fn update(&mut self, entity: Entity) {
+        self.components.status.remove(entity);
+
+        let event = if let Some((device, release)) = self.components.fwupd.get(entity) {
+            self.components.progress.insert(entity, Progress::Waiting);
+            FirmwareEvent::Fwupd(entity, device.clone(), release.clone())
+        } else if let Some(digest) = self.components.system76.get(entity) {
+            self.components.progress.insert(entity, Progress::Waiting);
+            FirmwareEvent::S76System(entity, digest.clone())
+        } else if let Some((_, Some(digest))) = self.components.thelio.get(entity) {
+            let Some(latest) = self.components.info[entity].latest.clone() else {
+                return;
+            };
+
+            // All boards are flashed together, so every board will be updated.
+            let boards = self
+                .components
+                .thelio
+                .iter()
+                .map(|(entity, (board, _))| (entity, board.clone()))
+                .collect::<Vec<_>>();
+
+            for &(entity, _) in &boards {
+                self.components.progress.insert(entity, Progress::Waiting);
+            }
+
+            FirmwareEvent::ThelioIo(digest.clone(), latest, boards)
+        } else {
+            return;
+        };
+
+        let _ = self.sender.send(event);
+    }
+
+    /// Updates the state from a signal of the firmware manager core.
+    pub fn signal(&mut self, signal: FirmwareSignal) {
+        match signal {
+            FirmwareSignal::Scanning => {
+                self.entities.clear();
+                self.components = Components::default();
+                self.system.clear();
+                self.devices.clear();
+                self.selected = 0;
+                self.scanning = true;
+            }
+            FirmwareSignal::ScanningComplete => self.scanning = false,
+            FirmwareSignal::Fwupd(signal) => self.fwupd(signal),
+            FirmwareSignal::S76System(info, digest) => self.system76_system(info, digest),
+            FirmwareSignal::ThelioIo(signal) => self.thelio_io(signal),
+            FirmwareSignal::DownloadBegin(entity, total) => {
+                let progress = Progress::Downloading { downloaded: 0, total };
+                self.components.progress.insert(entity, progress);
+            }
+            FirmwareSignal::DownloadUpdate(entity, bytes) => {
+                if let Some(Progress::Downloading { downloaded, .. }) =
+                    self.components.progress.get_mut(entity)
+                {
+                    *downloaded += bytes as u64;
+                }
+            }
+            FirmwareSignal::Verifying(entity) => {
+                self.components.progress.insert(entity, Progress::Verifying);
+            }
+            FirmwareSignal::DeviceFlashing(entity) => {
+                self.components.progress.insert(entity, Progress::Flashing);
+            }
+            FirmwareSignal::DeviceUpdated(entity) => self.device_updated(entity),
+            FirmwareSignal::SystemScheduled => reboot(),
+            // Activating or unlocking a device changes what can be done with it.
+            FirmwareSignal::DeviceActivated(_) | FirmwareSignal::DeviceUnlocked(_) => {
+                self.components.progress.clear();
+                self.scan();
+            }
+            FirmwareSignal::DeviceRequest(request) => self.prompt = Some(Prompt::Request(request)),
+            FirmwareSignal::Error(entity, why) => {
+                let message = format_error(&why);
+                match entity {
+                    Some(entity) => {
+                        self.components.progress.remove(entity);
+                        self.components.status.insert(entity, message);
+                    }
+                    None => self.message = Some(message),
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn create_device(&mut self, info: FirmwareInfo, system: bool) -> Entity {
+        let entity = self.entities.create();
+
+        if system {
+            self.entities.associate_system(entity);
+            self.system.push(entity);
+        } else {
+            self.devices.push(entity);
+        }
+
+        self.components.info.insert(entity, info);
+        entity
+    }
+
+    fn fwupd(&mut self, signal: FwupdSignal) {
+        // Explain why the device cannot be updated right now, if it cannot.
+        let mut problems = signal.blocking_problems().map(problem_text).collect::<Vec<_>>();
+
+        // Locked devices and staged updates must be acted upon before anything else.
+        let action = if signal.is_locked() {
+            problems.insert(0, fl!("device-locked"));
+            Some(DeviceAction::Unlock(Arc::new(signal.device.clone())))
+        } else if signal.needs_activation() {
+            problems.insert(0, fl!("device-needs-activation"));
+            Some(DeviceAction::Activate(Arc::new(signal.device.clone())))
+        } else {
+            None
+        };
+
+        let can_update = signal.can_update() && action.is_none();
+        let FwupdSignal { info, device, releases, .. } = signal;
+
+        // Firmware which is installed on reboot is system firmware.
+        let entity = self.create_device(info, device.needs_reboot());
+
+        if !problems.is_empty() {
+            self.components.problems.insert(entity, problems.join(" "));
+        }
+
+        if let Some(action) = action {
+            self.components.actions.insert(entity, action);
+        }
+
+        if let Some(release) = releases.last().filter(|_| can_update) {
+            let release = Arc::new(release.clone());
+            self.components.fwupd.insert(entity, (Arc::new(device), release));
+            self.components.ready.insert(entity, ());
+        }
+    }
+
+    fn system76_system(&mut self, info: FirmwareInfo, digest: Option<System76Digest>) {
+        let upgradeable = info.is_upgradeable();
+        let entity = self.create_device(info, true);
+
+        if let Some(digest) = digest.filter(|_| upgradeable) {
+            self.components.system76.insert(entity, digest);
+            self.components.ready.insert(entity, ());
+        }
+    }
+
+    fn thelio_io(&mut self, signal: ThelioIoSignal) {
+        let ThelioIoSignal { board, info, digest } = signal;
+        let upgradeable = info.is_upgradeable() && digest.is_some();
+        let entity = self.create_device(info, false);
+
+        self.components.thelio.insert(entity, (board, digest));
+        if upgradeable {
+            self.components.ready.insert(entity, ());
+        }
+    }
+
+    fn device_updated(&mut self, entity: Entity) {
+        self.components.progress.remove(entity);
+        self.components.ready.remove(entity);
+
+        if let Some(info) = self.components.info.get_mut(entity) {
+            if let Some(latest) = info.latest.clone() {
+                info.current = Some(latest);
+            }
+        }
+
+        self.components.status.insert(entity, fl!("status-updated"));
+
+        if self.entities.is_system(entity) {
+            reboot();
+        }
+    }
+}
+
+/// Explains a problem which prevents a device from being updated.
+fn problem_text(problem: DeviceProblem) -> String {
+    match problem {
+        DeviceProblem::SystemPowerTooLow => fl!("problem-system-power-too-low"),
+        DeviceProblem::Unreachable => fl!("problem-unreachable"),
+        DeviceProblem::PowerTooLow => fl!("problem-power-too-low"),
+        DeviceProblem::UpdatePending => fl!("problem-update-pending"),
+        DeviceProblem::RequireAcPower => fl!("problem-require-ac-power"),
+        DeviceProblem::LidIsClosed => fl!("problem-lid-is-closed"),
+        DeviceProblem::IsEmulated => fl!("problem-is-emulated"),
+        DeviceProblem::MissingLicense => fl!("problem-missing-license"),
+        DeviceProblem::SystemInhibit => fl!("problem-system-inhibit"),
+        DeviceProblem::UpdateInProgress => fl!("problem-update-in-progress"),
+        DeviceProblem::InUse => fl!("problem-in-use"),
+        DeviceProblem::DisplayRequired => fl!("problem-display-required"),
+        DeviceProblem::LowerPriority => fl!("problem-lower-priority"),
+    }
+}
+
+fn reboot() {
+    if let Err(why) = Command::new("systemctl").arg("reboot").status() {
+        eprintln!("failed to reboot: {}", why);
+    }
+}
@@ -0,0 +1,250 @@
+use crate::{
+    fl,
+    state::{Progress, Prompt, State},
+};
+use firmware_manager::{DeviceRequest, Entity, RequestKind, WellKnownRequest};
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span, Text},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Wrap},
+    Frame,
+};
+
+/// Draws the device list, with the prompt above it if one is open.
+pub fn draw(frame: &mut Frame, state: &State) {
+    let layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(3), Constraint::Length(1), Constraint::Length(1)])
+        .split(frame.size());
+
+    draw_devices(frame, state, layout[0]);
+
+    let message = match (&state.message, state.scanning) {
+        (Some(message), _) => Span::styled(message.as_str(), Style::default().fg(Color::Red)),
+        (None, true) => Span::raw(fl!("scanning")),
+        (None, false) => Span::raw(""),
+    };
+
+    frame.render_widget(Paragraph::new(Line::from(message)), layout[1]);
+
+    let help = Span::styled(fl!("help"), Style::default().add_modifier(Modifier::DIM));
+    frame.render_widget(Paragraph::new(Line::from(help)), layout[2]);
+
+    match &state.prompt {
+        Some(Prompt::Reboot(entity)) => {
+            let name = state.components.info.get(*entity).map_or("", |info| &info.name);
+            let body = fl!("prompt-reboot", device = name);
+            draw_prompt(frame, &fl!("header-firmware-update"), &body, &fl!("prompt-confirm"));
+        }
+        Some(Prompt::Request(request)) => {
+            let heading = match request.kind {
+                RequestKind::Immediate => fl!("request-immediate"),
+                RequestKind::Post => fl!("request-post"),
+            };
+
+            let mut body = instructions(request);
+            if let Some(ref image) = request.image {
+                body.push_str("\n\n");
+                body.push_str(&fl!("request-image", path = image.display().to_string()));
+            }
+
+            draw_prompt(frame, &heading, &body, &fl!("prompt-dismiss"));
+        }
+        None => (),
+    }
+}
+
+/// Lists system firmware and device firmware in separate sections.
+fn draw_devices(frame: &mut Frame, state: &State, area: Rect) {
+    let heading = Style::default().add_modifier(Modifier::BOLD);
+
+    let mut items = Vec::new();
+    let mut selected = None;
+    let mut index = 0;
+
+    let sections = [
+        (fl!("header-system-firmware"), &state.system),
+        (fl!("header-device-firmware"), &state.devices),
+    ];
+
+    for (header, entities) in sections {
+        if entities.is_empty() {
+            continue;
+        }
+
+        items.push(ListItem::new(Line::from(Span::styled(header, heading))));
+
+        for &entity in entities {
+            if index == state.selected {
+                selected = Some(items.len());
+            }
+
+            items.push(ListItem::new(device(state, entity, area.width)));
+            index += 1;
+        }
+    }
+
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(fl!("title")))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+
+    let mut list_state = ListState::default();
+    list_state.select(selected);
+
+    frame.render_stateful_widget(list, area, &mut list_state);
+}
+
+/// The row of a device, followed by its problems, status, and changelog.
+fn device(state: &State, entity: Entity, width: u16) -> Text<'static> {
+    let components = &state.components;
+    let info = &components.info[entity];
+
+    let version = match info.latest.as_ref() {
+        Some(latest) if info.is_upgradeable() => format!("{} → {}", info.current_label(), latest),
+        _ => info.current_label().to_owned(),
+    };
+
+    let action = if let Some(progress) = components.progress.get(entity) {
+        progress_text(*progress)
+    } else if components.actions.contains_key(entity) {
+        format!("[{}]", fl!("button-action"))
+    } else if components.ready.contains_key(entity) {
+        format!("[{}]", fl!("button-update"))
+    } else {
+        String::new()
+    };
+
+    let mut lines = vec![Line::from(vec![
+        Span::raw(format!("  {}  ", info.name)),
+        Span::styled(version, Style::default().add_modifier(Modifier::DIM)),
+        Span::raw("  "),
+        Span::styled(action, Style::default().fg(Color::Cyan)),
+    ])];
+
+    let notes = [
+        (components.problems.get(entity), Color::Yellow),
+        (components.status.get(entity), Color::Green),
+    ];
+
+    for (note, color) in notes {
+        if let Some(note) = note {
+            let style = Style::default().fg(color);
+            lines.push(Line::from(Span::styled(format!("    {}", note), style)));
+        }
+    }
+
+    if components.expanded.contains_key(entity) {
+        // The newest release is shown first, as in the GTK changelog.
+        let mut releases = info.pending_releases().collect::<Vec<_>>();
+        if releases.is_empty() {
+            let current = info.current.as_ref();
+            releases.extend(
+                info.releases.iter().filter(|release| Some(&release.version) == current),
+            );
+        }
+
+        if releases.is_empty() {
+            lines.push(Line::from(format!("    {}", fl!("changelog-none"))));
+        }
+
+        for release in releases.into_iter().rev() {
+            let header = match release.date {
+                Some(date) => format!("    {} ({})", release.version, date),
+                None => format!("    {}", release.version),
+            };
+
+            let style = Style::default().add_modifier(Modifier::BOLD);
+            lines.push(Line::from(Span::styled(header, style)));
+
+            for line in wrap(&release.changelog.to_string(), width.saturating_sub(10).into()) {
+                lines.push(Line::from(format!("      {}", line)));
+            }
+        }
+    }
+
+    Text::from(lines)
+}
+
+fn progress_text(progress: Progress) -> String {
+    match progress {
+        Progress::Waiting => fl!("progress-waiting"),
+        Progress::Downloading { downloaded, total } if total != 0 => {
+            let percent = (downloaded * 100 / total).min(100);
+            let filled = percent as usize / 5;
+            let bar = ["#".repeat(filled), " ".repeat(20 - filled)].concat();
+            format!("{} [{}] {}%", fl!("progress-downloading"), bar, percent)
+        }
+        Progress::Downloading { .. } => fl!("progress-downloading"),
+        Progress::Verifying => fl!("progress-verifying"),
+        Progress::Flashing => fl!("progress-flashing"),
+    }
+}
+
+/// Draws a modal prompt in the center of the screen.
+fn draw_prompt(frame: &mut Frame, heading: &str, body: &str, keys: &str) {
+    let area = centered(frame.size(), 60, 12);
+
+    let text = Text::from(vec![
+        Line::from(body.to_owned()),
+        Line::from(""),
+        Line::from(Span::styled(keys.to_owned(), Style::default().add_modifier(Modifier::DIM))),
+    ]);
+
+    let prompt = Paragraph::new(text)
+        .wrap(Wrap { trim: true })
+        .block(Block::default().borders(Borders::ALL).title(heading.to_owned()));
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(prompt, area);
+}
+
+/// A rectangle of at most the given size, in the center of `area`.
+fn centered(area: Rect, width: u16, height: u16) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect::new(area.x + (area.width - width) / 2, area.y + (area.height - height) / 2, width, height)
+}
+
+/// Wraps text to the width, preserving the lines that it already has.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            if !line.is_empty() && line.chars().count() + word.chars().count() >= width {
+                lines.push(std::mem::take(&mut line));
+            }
+
+            if !line.is_empty() {
+                line.push(' ');
+            }
+
+            line.push_str(word);
+        }
+
+        lines.push(line);
+    }
+
+    lines
+}
+
+/// Localized instructions for well-known requests, or else the instructions that fwupd gave.
+fn instructions(request: &DeviceRequest) -> String {
+    let well_known = match request.well_known() {
+        Some(request) => request,
+        None if !request.message.is_empty() => return request.message.clone(),
+        None => return fl!("request-generic"),
+    };
+
+    match well_known {
+        WellKnownRequest::DoNotPowerOff => fl!("request-do-not-power-off"),
+        WellKnownRequest::InsertUsbCable => fl!("request-insert-usb-cable"),
+        WellKnownRequest::PressUnlock => fl!("request-press-unlock"),
+        WellKnownRequest::RemoveReplug => fl!("request-remove-replug"),
+        WellKnownRequest::RemoveUsbCable => fl!("request-remove-usb-cable"),
+        WellKnownRequest::ReplugInstall => fl!("request-replug-install"),
+        WellKnownRequest::ReplugPower => fl!("request-replug-power"),
+    }
+}